@@ -0,0 +1,224 @@
+use crate::sigv4::{presign_url, Credentials, PresignRequest, S3Location};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const MAX_UPLOAD_BYTES: u64 = 25 * 1024 * 1024;
+const UPLOAD_URL_TTL_SECS: u32 = 15 * 60;
+const DOWNLOAD_URL_TTL_SECS: u32 = 60 * 60;
+
+/// The subset of an AppSync direct Lambda resolver event we rely on.
+#[derive(Debug, Deserialize)]
+pub struct AppSyncEvent {
+    #[serde(default)]
+    pub arguments: Value,
+    pub identity: Option<Identity>,
+    pub source: Option<Value>,
+    pub info: Info,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Identity {
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Info {
+    pub field_name: String,
+    pub parent_type_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateUploadUrlArgs {
+    file_name: String,
+    mime_type: String,
+    size: u64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadTarget {
+    pub key: String,
+    pub upload_url: String,
+    pub expires_at: f64,
+}
+
+pub struct Handler {
+    pub location: S3Location,
+    pub credentials: Credentials,
+}
+
+impl Handler {
+    pub fn from_env() -> Result<Self, String> {
+        Ok(Self {
+            location: S3Location {
+                bucket: std::env::var("ATTACHMENTS_BUCKET")
+                    .map_err(|_| "ATTACHMENTS_BUCKET is not set".to_string())?,
+                region: std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+                endpoint: std::env::var("S3_ENDPOINT").ok().filter(|e| !e.is_empty()),
+            },
+            credentials: Credentials::from_env()?,
+        })
+    }
+
+    pub fn handle(&self, event: AppSyncEvent) -> Result<Value, String> {
+        match (
+            event.info.parent_type_name.as_str(),
+            event.info.field_name.as_str(),
+        ) {
+            ("Mutation", "createUploadUrl") => {
+                let identity = event.identity.ok_or("Unauthorized")?;
+                let args: CreateUploadUrlArgs =
+                    serde_json::from_value(event.arguments).map_err(|e| e.to_string())?;
+                let target = self.create_upload_url(&identity.username, args)?;
+                serde_json::to_value(target).map_err(|e| e.to_string())
+            }
+            ("Attachment", "url") => {
+                let key = event
+                    .source
+                    .as_ref()
+                    .and_then(|source| source.get("key"))
+                    .and_then(Value::as_str)
+                    .ok_or("Attachment has no key")?;
+                Ok(Value::String(self.download_url(key)))
+            }
+            (parent, field) => Err(format!("Unsupported field {}.{}", parent, field)),
+        }
+    }
+
+    fn create_upload_url(
+        &self,
+        username: &str,
+        args: CreateUploadUrlArgs,
+    ) -> Result<UploadTarget, String> {
+        if args.size == 0 || args.size > MAX_UPLOAD_BYTES {
+            return Err(format!(
+                "Attachments must be between 1 byte and {} MB",
+                MAX_UPLOAD_BYTES / 1024 / 1024
+            ));
+        }
+        if args.mime_type.trim().is_empty() || !args.mime_type.contains('/') {
+            return Err("Invalid MIME type".to_string());
+        }
+
+        let key = format!(
+            "uploads/{}/{}/{}",
+            username,
+            uuid::Uuid::new_v4(),
+            sanitize_file_name(&args.file_name)
+        );
+        let now = Utc::now();
+        let upload_url = presign_url(
+            &self.location,
+            &self.credentials,
+            &PresignRequest {
+                method: "PUT",
+                key: &key,
+                expires_in: UPLOAD_URL_TTL_SECS,
+                headers: vec![
+                    ("content-length", args.size.to_string()),
+                    ("content-type", args.mime_type),
+                ],
+                now,
+            },
+        );
+
+        Ok(UploadTarget {
+            key,
+            upload_url,
+            expires_at: (now.timestamp_millis() + UPLOAD_URL_TTL_SECS as i64 * 1000) as f64,
+        })
+    }
+
+    pub fn download_url(&self, key: &str) -> String {
+        presign_url(
+            &self.location,
+            &self.credentials,
+            &PresignRequest {
+                method: "GET",
+                key,
+                expires_in: DOWNLOAD_URL_TTL_SECS,
+                headers: Vec::new(),
+                now: Utc::now(),
+            },
+        )
+    }
+}
+
+/// Keeps keys readable while dropping path separators and control characters.
+fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim_matches(|c| c == '.' || c == ' ');
+
+    if cleaned.is_empty() {
+        "file".to_string()
+    } else {
+        cleaned.chars().take(128).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn handler() -> Handler {
+        Handler {
+            location: S3Location {
+                bucket: "attachments".to_string(),
+                region: "us-east-1".to_string(),
+                endpoint: Some("http://localhost:9000".to_string()),
+            },
+            credentials: Credentials {
+                access_key_id: "minioadmin".to_string(),
+                secret_access_key: "minioadmin".to_string(),
+                session_token: None,
+            },
+        }
+    }
+
+    fn event(field: &str, arguments: Value) -> AppSyncEvent {
+        serde_json::from_value(json!({
+            "arguments": arguments,
+            "identity": { "username": "alice" },
+            "info": { "fieldName": field, "parentTypeName": "Mutation" }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn upload_url_is_scoped_to_the_caller() {
+        let result = handler()
+            .handle(event(
+                "createUploadUrl",
+                json!({ "fileName": "../cat.png", "mimeType": "image/png", "size": 1024 }),
+            ))
+            .unwrap();
+
+        let key = result["key"].as_str().unwrap();
+        assert!(key.starts_with("uploads/alice/"));
+        assert!(key.ends_with("/_cat.png"));
+        assert!(result["uploadUrl"]
+            .as_str()
+            .unwrap()
+            .contains("X-Amz-SignedHeaders=content-length%3Bcontent-type%3Bhost"));
+    }
+
+    #[test]
+    fn rejects_oversized_uploads() {
+        let result = handler().handle(event(
+            "createUploadUrl",
+            json!({ "fileName": "big.bin", "mimeType": "application/octet-stream", "size": MAX_UPLOAD_BYTES + 1 }),
+        ));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,15 @@
+use attachments::handler::{AppSyncEvent, Handler};
+use lambda_runtime::{service_fn, Error, LambdaEvent};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let handler = Handler::from_env()?;
+    let handler = &handler;
+
+    lambda_runtime::run(service_fn(
+        move |event: LambdaEvent<AppSyncEvent>| async move {
+            handler.handle(event.payload).map_err(Error::from)
+        },
+    ))
+    .await
+}
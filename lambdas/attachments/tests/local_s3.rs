@@ -0,0 +1,60 @@
+//! Round-trips an object through presigned URLs against an S3-compatible
+//! stand-in. Start one with `scripts/local_s3.sh` and run:
+//!
+//!     LOCAL_S3_ENDPOINT=http://localhost:9000 cargo test -p attachments
+//!
+//! The test is a no-op when `LOCAL_S3_ENDPOINT` is not set.
+
+use attachments::handler::{AppSyncEvent, Handler};
+use attachments::sigv4::{Credentials, S3Location};
+use serde_json::json;
+
+#[tokio::test]
+async fn presigned_upload_and_download_round_trip() {
+    let Ok(endpoint) = std::env::var("LOCAL_S3_ENDPOINT") else {
+        eprintln!("LOCAL_S3_ENDPOINT not set, skipping");
+        return;
+    };
+
+    let handler = Handler {
+        location: S3Location {
+            bucket: std::env::var("LOCAL_S3_BUCKET").unwrap_or_else(|_| "attachments".to_string()),
+            region: "us-east-1".to_string(),
+            endpoint: Some(endpoint),
+        },
+        credentials: Credentials {
+            access_key_id: std::env::var("LOCAL_S3_ACCESS_KEY")
+                .unwrap_or_else(|_| "minioadmin".to_string()),
+            secret_access_key: std::env::var("LOCAL_S3_SECRET_KEY")
+                .unwrap_or_else(|_| "minioadmin".to_string()),
+            session_token: None,
+        },
+    };
+
+    let body = b"hello from rusty chat".to_vec();
+    let event: AppSyncEvent = serde_json::from_value(json!({
+        "arguments": { "fileName": "hello.txt", "mimeType": "text/plain", "size": body.len() },
+        "identity": { "username": "alice" },
+        "info": { "fieldName": "createUploadUrl", "parentTypeName": "Mutation" }
+    }))
+    .unwrap();
+    let target = handler.handle(event).unwrap();
+
+    let client = reqwest::Client::new();
+    let upload = client
+        .put(target["uploadUrl"].as_str().unwrap())
+        .header("Content-Type", "text/plain")
+        .body(body.clone())
+        .send()
+        .await
+        .unwrap();
+    assert!(upload.status().is_success(), "upload failed: {:?}", upload);
+
+    let download = client
+        .get(handler.download_url(target["key"].as_str().unwrap()))
+        .send()
+        .await
+        .unwrap();
+    assert!(download.status().is_success());
+    assert_eq!(download.bytes().await.unwrap().to_vec(), body);
+}
@@ -0,0 +1,219 @@
+use crate::sender::Subscription;
+use crate::settings::{ChatSetting, DoNotDisturb, NotificationSettings};
+use crate::sigv4::{sign, Credentials, SignRequest};
+use chrono::Utc;
+use serde_json::{json, Map, Value};
+use std::future::Future;
+
+/// What the sender needs to know about a recipient.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recipient {
+    pub status: Option<String>,
+    pub last_seen: Option<f64>,
+    pub subscriptions: Vec<Subscription>,
+    pub settings: NotificationSettings,
+}
+
+pub trait Store {
+    fn recipient(&self, username: &str) -> impl Future<Output = Result<Recipient, String>>;
+    fn remove_subscription(
+        &self,
+        username: &str,
+        device_id: &str,
+    ) -> impl Future<Output = Result<(), String>>;
+}
+
+/// The chat table, through DynamoDB's JSON API.
+pub struct DynamoStore {
+    pub client: reqwest::Client,
+    pub table: String,
+    pub region: String,
+    /// Only set for stand-ins such as DynamoDB Local.
+    pub endpoint: Option<String>,
+    pub credentials: Credentials,
+}
+
+impl DynamoStore {
+    pub fn from_env(client: reqwest::Client) -> Result<Self, String> {
+        Ok(Self {
+            client,
+            table: std::env::var("TABLE_NAME").map_err(|_| "TABLE_NAME is not set".to_string())?,
+            region: std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            endpoint: std::env::var("DYNAMODB_ENDPOINT")
+                .ok()
+                .filter(|e| !e.is_empty()),
+            credentials: Credentials::from_env()?,
+        })
+    }
+
+    async fn call(&self, operation: &str, body: Value) -> Result<Value, String> {
+        let endpoint = self
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://dynamodb.{}.amazonaws.com", self.region));
+        let host = endpoint
+            .split_once("://")
+            .map_or(endpoint.as_str(), |(_, host)| host)
+            .trim_end_matches('/')
+            .to_string();
+        let body = body.to_string();
+        let target = format!("DynamoDB_20120810.{}", operation);
+        let headers = vec![
+            ("content-type", "application/x-amz-json-1.0".to_string()),
+            ("host", host),
+            ("x-amz-target", target),
+        ];
+        let signed = sign(
+            &self.credentials,
+            &SignRequest {
+                method: "POST",
+                path: "/",
+                headers: headers.clone(),
+                body: body.as_bytes(),
+                service: "dynamodb",
+                region: &self.region,
+                now: Utc::now(),
+            },
+        );
+
+        let mut request = self
+            .client
+            .post(format!("{}/", endpoint.trim_end_matches('/')));
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(*name, value);
+        }
+        for (name, value) in signed {
+            request = request.header(name, value);
+        }
+        let response = request.body(body).send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        let text = response.text().await.map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            return Err(format!(
+                "DynamoDB {} failed with {}: {}",
+                operation, status, text
+            ));
+        }
+        serde_json::from_str(&text).map_err(|e| e.to_string())
+    }
+
+    /// Every item in partition `pk` whose sort key starts with `prefix`.
+    async fn query_prefix(
+        &self,
+        pk: &str,
+        prefix: &str,
+    ) -> Result<Vec<Map<String, Value>>, String> {
+        let mut items = Vec::new();
+        let mut start_key = Value::Null;
+        loop {
+            let mut query = json!({
+                "TableName": self.table,
+                "KeyConditionExpression": "PK = :pk AND begins_with(SK, :prefix)",
+                "ExpressionAttributeValues": {
+                    ":pk": { "S": pk },
+                    ":prefix": { "S": prefix }
+                }
+            });
+            if !start_key.is_null() {
+                query["ExclusiveStartKey"] = start_key;
+            }
+            let page = self.call("Query", query).await?;
+            items.extend(
+                page["Items"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|item| item.as_object().cloned()),
+            );
+            match page.get("LastEvaluatedKey") {
+                Some(key) => start_key = key.clone(),
+                None => return Ok(items),
+            }
+        }
+    }
+}
+
+impl Store for DynamoStore {
+    async fn recipient(&self, username: &str) -> Result<Recipient, String> {
+        let pk = format!("USER#{}", username);
+        let user = self
+            .call(
+                "GetItem",
+                json!({
+                    "TableName": self.table,
+                    "Key": { "PK": { "S": pk }, "SK": { "S": "METADATA" } },
+                    "ProjectionExpression": "#status, lastSeen",
+                    "ExpressionAttributeNames": { "#status": "status" }
+                }),
+            )
+            .await?;
+        let user = user.get("Item").and_then(Value::as_object);
+
+        let mut subscriptions = Vec::new();
+        for item in self.query_prefix(&pk, "PUSH#").await? {
+            let field = |name: &str| string(&item, name).ok_or(format!("missing {}", name));
+            subscriptions.push(Subscription {
+                device_id: field("deviceId")?,
+                endpoint: field("endpoint")?,
+                p256dh: field("p256dh")?,
+                auth: field("auth")?,
+            });
+        }
+
+        let mut settings = NotificationSettings::default();
+        for item in self.query_prefix(&pk, "NOTIFY#").await? {
+            if string(&item, "SK").as_deref() == Some("NOTIFY#DND") {
+                settings.do_not_disturb = Some(DoNotDisturb {
+                    enabled: boolean(&item, "enabled"),
+                    start: string(&item, "start").unwrap_or_default(),
+                    end: string(&item, "end").unwrap_or_default(),
+                    time_zone: string(&item, "timeZone").unwrap_or_default(),
+                });
+            } else if let Some(chat_id) = string(&item, "chatId") {
+                settings.chats.push(ChatSetting {
+                    chat_id,
+                    mentions_only: boolean(&item, "mentionsOnly"),
+                    mute: string(&item, "mute"),
+                    muted_until: number(&item, "mutedUntil"),
+                });
+            }
+        }
+
+        Ok(Recipient {
+            status: user.and_then(|u| string(u, "status")),
+            last_seen: user.and_then(|u| number(u, "lastSeen")),
+            subscriptions,
+            settings,
+        })
+    }
+
+    async fn remove_subscription(&self, username: &str, device_id: &str) -> Result<(), String> {
+        self.call(
+            "DeleteItem",
+            json!({
+                "TableName": self.table,
+                "Key": {
+                    "PK": { "S": format!("USER#{}", username) },
+                    "SK": { "S": format!("PUSH#{}", device_id) }
+                }
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+}
+
+fn string(item: &Map<String, Value>, name: &str) -> Option<String> {
+    item.get(name)?.get("S")?.as_str().map(str::to_string)
+}
+
+fn number(item: &Map<String, Value>, name: &str) -> Option<f64> {
+    item.get(name)?.get("N")?.as_str()?.parse().ok()
+}
+
+fn boolean(item: &Map<String, Value>, name: &str) -> bool {
+    item.get(name)
+        .and_then(|v| v.get("BOOL"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
@@ -0,0 +1,111 @@
+//! Message encryption for Web Push (RFC 8291), using the `aes128gcm`
+//! content coding (RFC 8188) with a single record.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes128Gcm, Nonce};
+use hkdf::Hkdf;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{PublicKey, SecretKey};
+use rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+
+pub const RECORD_SIZE: u32 = 4096;
+/// Padding delimiter plus the AEAD tag.
+const RECORD_OVERHEAD: usize = 1 + 16;
+/// Largest payload that fits in one record.
+pub const MAX_PAYLOAD: usize = RECORD_SIZE as usize - RECORD_OVERHEAD;
+
+/// Encrypts `payload` for a subscription's `p256dh` key and `auth` secret,
+/// with a fresh server key pair and salt.
+pub fn encrypt(payload: &[u8], ua_public: &[u8], auth_secret: &[u8]) -> Result<Vec<u8>, String> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    encrypt_with(
+        payload,
+        ua_public,
+        auth_secret,
+        &SecretKey::random(&mut OsRng),
+        &salt,
+    )
+}
+
+pub fn encrypt_with(
+    payload: &[u8],
+    ua_public: &[u8],
+    auth_secret: &[u8],
+    as_secret: &SecretKey,
+    salt: &[u8; 16],
+) -> Result<Vec<u8>, String> {
+    if payload.len() > MAX_PAYLOAD {
+        return Err(format!("Payload exceeds {} bytes", MAX_PAYLOAD));
+    }
+    let ua_key = PublicKey::from_sec1_bytes(ua_public).map_err(|_| "Invalid p256dh key")?;
+    let ua_public = ua_key.to_encoded_point(false);
+    let as_public = as_secret.public_key().to_encoded_point(false);
+
+    let shared = p256::ecdh::diffie_hellman(as_secret.to_nonzero_scalar(), ua_key.as_affine());
+    let key_info = [
+        b"WebPush: info\0".as_slice(),
+        ua_public.as_bytes(),
+        as_public.as_bytes(),
+    ]
+    .concat();
+    let ikm = expand(auth_secret, shared.raw_secret_bytes(), &key_info, 32)?;
+    let cek = expand(salt, &ikm, b"Content-Encoding: aes128gcm\0", 16)?;
+    let nonce = expand(salt, &ikm, b"Content-Encoding: nonce\0", 12)?;
+
+    // Last (and only) record: payload, then the 0x02 delimiter, no padding
+    let mut record = payload.to_vec();
+    record.push(2);
+    let ciphertext = Aes128Gcm::new_from_slice(&cek)
+        .map_err(|e| e.to_string())?
+        .encrypt(Nonce::from_slice(&nonce), record.as_slice())
+        .map_err(|e| e.to_string())?;
+
+    let mut body = Vec::with_capacity(21 + as_public.len() + ciphertext.len());
+    body.extend_from_slice(salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(as_public.len() as u8);
+    body.extend_from_slice(as_public.as_bytes());
+    body.extend_from_slice(&ciphertext);
+    Ok(body)
+}
+
+fn expand(salt: &[u8], ikm: &[u8], info: &[u8], len: usize) -> Result<Vec<u8>, String> {
+    let mut okm = vec![0u8; len];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut okm)
+        .map_err(|e| e.to_string())?;
+    Ok(okm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+
+    fn b64(s: &str) -> Vec<u8> {
+        URL_SAFE_NO_PAD.decode(s).unwrap()
+    }
+
+    // RFC 8291, Appendix A
+    #[test]
+    fn matches_rfc_8291_example() {
+        let as_secret =
+            SecretKey::from_slice(&b64("yfWPiYE-n46HLnH0KqZOF1fJJU3MYrct3AELtAQ-oRw")).unwrap();
+        let salt: [u8; 16] = b64("DGv6ra1nlYgDCS1FRnbzlw").try_into().unwrap();
+        let body = encrypt_with(
+            b"When I grow up, I want to be a watermelon",
+            &b64("BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4"),
+            &b64("BTBZMqHH6r4Tts7J_aSIgg"),
+            &as_secret,
+            &salt,
+        )
+        .unwrap();
+        assert_eq!(
+            URL_SAFE_NO_PAD.encode(body),
+            "DGv6ra1nlYgDCS1FRnbzlwAAEABBBP4z9KsN6nGRTbVYI_c7VJSPQTBtkgcy27mlmlMoZIIgDll6e3vCYLocInmYWAmS6TlzAC8wEqKK6PBru3jl7A_yl95bQpu6cVPTpK4Mqgkf1CXztLVBSt2Ks3oZwbuwXPXLWyouBWLVWGNWQexSgSxsj_Qulcy4a-fN"
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use crate::dynamo::{Recipient, Store};
+use crate::sender::{Delivery, Notification, PushSender};
+use crate::stream::{self, NewMessage, StreamEvent};
+
+/// Matches the client's PRESENCE_STALE_MS: a user whose last heartbeat is
+/// older than this has no tab open, whatever their stored status says.
+pub const PRESENCE_STALE_MS: f64 = 3.0 * 60.0 * 1000.0;
+/// Longest message preview sent, in characters.
+const PREVIEW_CHARS: usize = 100;
+
+pub struct Handler<S> {
+    pub store: S,
+    pub sender: PushSender,
+}
+
+impl<S: Store> Handler<S> {
+    /// Pushes each new message to its recipient's devices if they're
+    /// offline and their notification settings allow it; an open tab
+    /// notifies by itself. Failed pushes are logged
+    /// rather than returned, so the stream batch isn't retried and other
+    /// devices don't get duplicates. Returns how many pushes were accepted.
+    pub async fn handle(&self, event: StreamEvent, now_ms: f64) -> Result<usize, String> {
+        let mut accepted = 0;
+        for message in stream::new_messages(event) {
+            let recipient = self.store.recipient(&message.receiver).await?;
+            if !is_offline(&recipient, now_ms) || !recipient.settings.allows(&message, now_ms) {
+                continue;
+            }
+            let notification = notification_for(&message);
+            for subscription in &recipient.subscriptions {
+                match self
+                    .sender
+                    .send(subscription, &notification, (now_ms / 1000.0) as i64)
+                    .await
+                {
+                    Ok(Delivery::Accepted) => accepted += 1,
+                    Ok(Delivery::Expired) => {
+                        self.store
+                            .remove_subscription(&message.receiver, &subscription.device_id)
+                            .await?
+                    }
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+        }
+        Ok(accepted)
+    }
+}
+
+fn is_offline(recipient: &Recipient, now_ms: f64) -> bool {
+    let stale = recipient
+        .last_seen
+        .is_none_or(|t| now_ms - t > PRESENCE_STALE_MS);
+    stale || recipient.status.as_deref().is_none_or(|s| s == "offline")
+}
+
+fn notification_for(message: &NewMessage) -> Notification {
+    let body = match &message.attachment_name {
+        Some(name) => format!("📎 {}", name),
+        None => {
+            let mut preview: String = message.content.chars().take(PREVIEW_CHARS).collect();
+            if message.content.chars().nth(PREVIEW_CHARS).is_some() {
+                preview.push('…');
+            }
+            preview
+        }
+    };
+    Notification {
+        title: message.sender.clone(),
+        body,
+        chat_id: message.chat_id.clone(),
+        sender: message.sender.clone(),
+    }
+}
@@ -0,0 +1,8 @@
+pub mod dynamo;
+pub mod ece;
+pub mod handler;
+pub mod sender;
+pub mod settings;
+pub mod sigv4;
+pub mod stream;
+pub mod vapid;
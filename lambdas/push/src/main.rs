@@ -0,0 +1,32 @@
+use lambda_runtime::{service_fn, Error, LambdaEvent};
+use push::dynamo::DynamoStore;
+use push::handler::Handler;
+use push::sender::PushSender;
+use push::stream::StreamEvent;
+use push::vapid::Vapid;
+
+/// Consumes the table's stream and pushes new messages to offline recipients.
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let client = reqwest::Client::new();
+    let vapid = Vapid::new(
+        &std::env::var("VAPID_PRIVATE_KEY").map_err(|_| "VAPID_PRIVATE_KEY is not set")?,
+        &std::env::var("VAPID_SUBJECT").map_err(|_| "VAPID_SUBJECT is not set")?,
+    )?;
+    let handler = Handler {
+        store: DynamoStore::from_env(client.clone())?,
+        sender: PushSender { client, vapid },
+    };
+    let handler = &handler;
+
+    lambda_runtime::run(service_fn(
+        move |event: LambdaEvent<StreamEvent>| async move {
+            let now_ms = chrono::Utc::now().timestamp_millis() as f64;
+            handler
+                .handle(event.payload, now_ms)
+                .await
+                .map_err(Error::from)
+        },
+    ))
+    .await
+}
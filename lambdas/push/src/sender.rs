@@ -0,0 +1,88 @@
+use crate::ece;
+use crate::vapid::Vapid;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+/// Seconds a push service keeps an undelivered notification.
+const TTL_SECS: u32 = 24 * 60 * 60;
+
+/// A browser's push subscription, as stored by registerPushSubscription.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Subscription {
+    pub device_id: String,
+    pub endpoint: String,
+    /// Base64url P-256 public key of the browser.
+    pub p256dh: String,
+    /// Base64url authentication secret.
+    pub auth: String,
+}
+
+/// What the service worker shows; see public/sw.js.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    pub chat_id: String,
+    pub sender: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Delivery {
+    Accepted,
+    /// The subscription is gone and should be forgotten.
+    Expired,
+}
+
+pub struct PushSender {
+    pub client: reqwest::Client,
+    pub vapid: Vapid,
+}
+
+impl PushSender {
+    pub async fn send(
+        &self,
+        subscription: &Subscription,
+        notification: &Notification,
+        now_secs: i64,
+    ) -> Result<Delivery, String> {
+        let decode = |value: &str| {
+            URL_SAFE_NO_PAD
+                .decode(value.trim_end_matches('='))
+                .map_err(|_| format!("Subscription {} has invalid keys", subscription.device_id))
+        };
+        let payload = serde_json::to_vec(notification).map_err(|e| e.to_string())?;
+        let body = ece::encrypt(
+            &payload,
+            &decode(&subscription.p256dh)?,
+            &decode(&subscription.auth)?,
+        )?;
+
+        let response = self
+            .client
+            .post(&subscription.endpoint)
+            .header(
+                "Authorization",
+                self.vapid.authorization(&subscription.endpoint, now_secs)?,
+            )
+            .header("Content-Encoding", "aes128gcm")
+            .header("Content-Type", "application/octet-stream")
+            .header("TTL", TTL_SECS.to_string())
+            .header("Urgency", "high")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        match response.status().as_u16() {
+            200..=299 => Ok(Delivery::Accepted),
+            404 | 410 => Ok(Delivery::Expired),
+            status => Err(format!(
+                "Push to {} failed with {}",
+                subscription.device_id, status
+            )),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! The recipient's notification settings, as the app stores them through
+//! setChatNotificationSetting and setDoNotDisturb.
+
+use crate::stream::NewMessage;
+use chrono::{DateTime, Timelike, Utc};
+use chrono_tz::Tz;
+
+/// One chat's settings (SK `NOTIFY#CHAT#<chatId>`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChatSetting {
+    pub chat_id: String,
+    pub mentions_only: bool,
+    /// TIMED, UNTIL_TURNED_OFF or FOREVER.
+    pub mute: Option<String>,
+    pub muted_until: Option<f64>,
+}
+
+impl ChatSetting {
+    pub fn is_muted(&self, now_ms: f64) -> bool {
+        match self.mute.as_deref() {
+            None => false,
+            Some("TIMED") => self.muted_until.is_some_and(|until| now_ms < until),
+            Some(_) => true,
+        }
+    }
+}
+
+/// Quiet hours (SK `NOTIFY#DND`): "HH:MM" in an IANA time zone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoNotDisturb {
+    pub enabled: bool,
+    pub start: String,
+    pub end: String,
+    pub time_zone: String,
+}
+
+impl DoNotDisturb {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        let (Some(start), Some(end), Ok(tz)) = (
+            parse_time(&self.start),
+            parse_time(&self.end),
+            self.time_zone.parse::<Tz>(),
+        ) else {
+            return false;
+        };
+        if !self.enabled || start == end {
+            return false;
+        }
+        let local = now.with_timezone(&tz);
+        let minutes = local.hour() * 60 + local.minute();
+        if start < end {
+            (start..end).contains(&minutes)
+        } else {
+            minutes >= start || minutes < end
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NotificationSettings {
+    pub chats: Vec<ChatSetting>,
+    pub do_not_disturb: Option<DoNotDisturb>,
+}
+
+impl NotificationSettings {
+    /// Whether `message` may be pushed to its recipient at `now_ms`.
+    pub fn allows(&self, message: &NewMessage, now_ms: f64) -> bool {
+        if let Some(chat) = self.chats.iter().find(|c| c.chat_id == message.chat_id) {
+            if chat.is_muted(now_ms)
+                || (chat.mentions_only && !message.mentions.contains(&message.receiver))
+            {
+                return false;
+            }
+        }
+        let now = DateTime::from_timestamp_millis(now_ms as i64).unwrap_or_default();
+        !self
+            .do_not_disturb
+            .as_ref()
+            .is_some_and(|dnd| dnd.is_active(now))
+    }
+}
+
+/// Minutes after midnight for "HH:MM".
+fn parse_time(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn quiet_hours_follow_the_time_zone() {
+        let dnd = DoNotDisturb {
+            enabled: true,
+            start: "22:00".to_string(),
+            end: "07:00".to_string(),
+            time_zone: "America/New_York".to_string(),
+        };
+        // 03:30 UTC is 23:30 the evening before in New York (EDT)
+        let now = Utc.with_ymd_and_hms(2024, 7, 1, 3, 30, 0).unwrap();
+        assert!(dnd.is_active(now));
+        // 12:00 UTC is 08:00 in New York
+        assert!(!dnd.is_active(Utc.with_ymd_and_hms(2024, 7, 1, 12, 0, 0).unwrap()));
+
+        let unknown = DoNotDisturb {
+            time_zone: "Mars/Olympus_Mons".to_string(),
+            ..dnd
+        };
+        assert!(!unknown.is_active(now));
+    }
+}
@@ -0,0 +1,82 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// The subset of a DynamoDB stream event we rely on.
+#[derive(Debug, Deserialize)]
+pub struct StreamEvent {
+    #[serde(rename = "Records")]
+    pub records: Vec<StreamRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamRecord {
+    /// INSERT, MODIFY or REMOVE
+    pub event_name: String,
+    pub dynamodb: StreamData,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StreamData {
+    pub keys: Map<String, Value>,
+    pub new_image: Option<Map<String, Value>>,
+}
+
+/// A message someone should be told about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewMessage {
+    pub message_id: String,
+    pub chat_id: String,
+    pub sender: String,
+    pub receiver: String,
+    pub content: String,
+    pub attachment_name: Option<String>,
+    /// Usernames mentioned in the content.
+    pub mentions: Vec<String>,
+}
+
+/// Newly written messages in the batch. Edits, deletes, imported history
+/// and items other than messages are left out.
+pub fn new_messages(event: StreamEvent) -> Vec<NewMessage> {
+    event
+        .records
+        .into_iter()
+        .filter(|record| record.event_name == "INSERT")
+        .filter(|record| {
+            string(&record.dynamodb.keys, "SK").is_some_and(|sk| sk.starts_with("MSG#"))
+        })
+        .filter_map(|record| {
+            let image = record.dynamodb.new_image?;
+            let message_id = string(&image, "messageId")?;
+            if message_id.starts_with("import-") {
+                return None;
+            }
+            Some(NewMessage {
+                message_id,
+                chat_id: string(&image, "chatId")?,
+                sender: string(&image, "sender")?,
+                receiver: string(&image, "receiver")?,
+                content: string(&image, "content").unwrap_or_default(),
+                attachment_name: image
+                    .get("attachment")
+                    .and_then(|a| a.get("M"))
+                    .and_then(Value::as_object)
+                    .and_then(|a| string(a, "fileName")),
+                mentions: image
+                    .get("mentions")
+                    .and_then(|m| m.get("L"))
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|m| string(m.get("M")?.as_object()?, "username"))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// A string attribute (`{"S": "..."}`).
+fn string(item: &Map<String, Value>, name: &str) -> Option<String> {
+    item.get(name)?.get("S")?.as_str().map(str::to_string)
+}
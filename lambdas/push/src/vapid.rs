@@ -0,0 +1,84 @@
+//! Voluntary application server identification (RFC 8292).
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use serde_json::json;
+
+/// How long a signed token stays valid; push services reject more than 24h.
+pub const TOKEN_TTL_SECS: i64 = 12 * 60 * 60;
+
+pub struct Vapid {
+    key: SigningKey,
+    /// `mailto:` or `https:` contact for the push service operator.
+    subject: String,
+}
+
+impl Vapid {
+    /// `private_key` is the raw 32-byte P-256 scalar, base64url encoded.
+    pub fn new(private_key: &str, subject: &str) -> Result<Self, String> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(private_key.trim())
+            .map_err(|_| "VAPID private key is not base64url")?;
+        Ok(Self {
+            key: SigningKey::from_slice(&bytes).map_err(|_| "Invalid VAPID private key")?,
+            subject: subject.to_string(),
+        })
+    }
+
+    /// The uncompressed public key, base64url encoded. Clients pass this as
+    /// `applicationServerKey` when subscribing.
+    pub fn public_key(&self) -> String {
+        let point = self.key.verifying_key().to_encoded_point(false);
+        URL_SAFE_NO_PAD.encode(point.as_bytes())
+    }
+
+    /// `Authorization` header value for a push to `endpoint`.
+    pub fn authorization(&self, endpoint: &str, now_secs: i64) -> Result<String, String> {
+        let header = URL_SAFE_NO_PAD.encode(json!({ "typ": "JWT", "alg": "ES256" }).to_string());
+        let claims = URL_SAFE_NO_PAD.encode(
+            json!({
+                "aud": audience(endpoint)?,
+                "exp": now_secs + TOKEN_TTL_SECS,
+                "sub": self.subject,
+            })
+            .to_string(),
+        );
+        let signing_input = format!("{}.{}", header, claims);
+        let signature: Signature = self.key.sign(signing_input.as_bytes());
+        Ok(format!(
+            "vapid t={}.{}, k={}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes()),
+            self.public_key()
+        ))
+    }
+}
+
+/// The endpoint's origin: scheme, host and port.
+fn audience(endpoint: &str) -> Result<String, String> {
+    let (scheme, rest) = endpoint
+        .split_once("://")
+        .ok_or_else(|| format!("Invalid push endpoint {}", endpoint))?;
+    let host = rest.split(['/', '?']).next().unwrap_or_default();
+    Ok(format!("{}://{}", scheme, host))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audience_is_the_endpoint_origin() {
+        assert_eq!(
+            audience("https://fcm.googleapis.com/fcm/send/abc?x=1").unwrap(),
+            "https://fcm.googleapis.com"
+        );
+        assert_eq!(
+            audience("http://127.0.0.1:8080/push").unwrap(),
+            "http://127.0.0.1:8080"
+        );
+        assert!(audience("not a url").is_err());
+    }
+}
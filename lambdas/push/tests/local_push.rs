@@ -0,0 +1,284 @@
+//! Runs stream records through the push handler against a local stand-in
+//! for a browser push service, then decrypts what it received the way a
+//! browser would.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes128Gcm, Nonce};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hkdf::Hkdf;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{PublicKey, SecretKey};
+use push::dynamo::{Recipient, Store};
+use push::handler::Handler;
+use push::sender::{Notification, PushSender, Subscription};
+use push::settings::{ChatSetting, NotificationSettings};
+use push::stream::StreamEvent;
+use push::vapid::Vapid;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+// Any valid P-256 private key
+const VAPID_PRIVATE_KEY: &str = "IQ9Ur0ykXoHS9gzfYX0aBjy9lvdrjx_PFUXmie9YRcY";
+const NOW_MS: f64 = 1_700_000_000_000.0;
+
+struct Received {
+    path: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// Accepts every POST; paths containing "gone" answer 410 like an
+/// unsubscribed browser's endpoint.
+async fn push_service() -> (String, Arc<Mutex<Vec<Received>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let log = received.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut data = Vec::new();
+            let mut buf = [0u8; 4096];
+            let (head_len, content_length) = loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                data.extend_from_slice(&buf[..n]);
+                if let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+                    let head = String::from_utf8_lossy(&data[..end]).to_lowercase();
+                    let length = head
+                        .lines()
+                        .find_map(|l| l.strip_prefix("content-length:"))
+                        .map_or(0, |v| v.trim().parse().unwrap());
+                    break (end + 4, length);
+                }
+            };
+            while data.len() < head_len + content_length {
+                let n = socket.read(&mut buf).await.unwrap();
+                data.extend_from_slice(&buf[..n]);
+            }
+
+            let head = String::from_utf8_lossy(&data[..head_len]).to_string();
+            let mut lines = head.lines();
+            let path = lines.next().unwrap().split(' ').nth(1).unwrap().to_string();
+            let headers = lines
+                .filter_map(|l| l.split_once(':'))
+                .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+                .collect();
+            let status = if path.contains("gone") {
+                "410 Gone"
+            } else {
+                "201 Created"
+            };
+            log.lock().unwrap().push(Received {
+                path,
+                headers,
+                body: data[head_len..].to_vec(),
+            });
+            let response = format!(
+                "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                status
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    (url, received)
+}
+
+#[derive(Default)]
+struct FakeStore {
+    recipients: HashMap<String, Recipient>,
+    removed: RefCell<Vec<String>>,
+}
+
+impl Store for FakeStore {
+    async fn recipient(&self, username: &str) -> Result<Recipient, String> {
+        Ok(self.recipients.get(username).cloned().unwrap_or_default())
+    }
+
+    async fn remove_subscription(&self, _username: &str, device_id: &str) -> Result<(), String> {
+        self.removed.borrow_mut().push(device_id.to_string());
+        Ok(())
+    }
+}
+
+struct Browser {
+    key: SecretKey,
+    auth: [u8; 16],
+}
+
+impl Browser {
+    fn new(seed: u8) -> Self {
+        Self {
+            key: SecretKey::from_slice(&[seed; 32]).unwrap(),
+            auth: [seed; 16],
+        }
+    }
+
+    fn subscription(&self, device_id: &str, endpoint: String) -> Subscription {
+        Subscription {
+            device_id: device_id.to_string(),
+            endpoint,
+            p256dh: URL_SAFE_NO_PAD.encode(self.key.public_key().to_encoded_point(false)),
+            auth: URL_SAFE_NO_PAD.encode(self.auth),
+        }
+    }
+
+    /// RFC 8291 decryption of a single-record aes128gcm body.
+    fn decrypt(&self, body: &[u8]) -> Vec<u8> {
+        let salt = &body[..16];
+        let id_len = body[20] as usize;
+        let as_public = PublicKey::from_sec1_bytes(&body[21..21 + id_len]).unwrap();
+        let ciphertext = &body[21 + id_len..];
+
+        let shared =
+            p256::ecdh::diffie_hellman(self.key.to_nonzero_scalar(), as_public.as_affine());
+        let key_info = [
+            b"WebPush: info\0".as_slice(),
+            self.key.public_key().to_encoded_point(false).as_bytes(),
+            as_public.to_encoded_point(false).as_bytes(),
+        ]
+        .concat();
+        let mut ikm = [0u8; 32];
+        Hkdf::<Sha256>::new(Some(&self.auth), shared.raw_secret_bytes())
+            .expand(&key_info, &mut ikm)
+            .unwrap();
+        let prk = Hkdf::<Sha256>::new(Some(salt), &ikm);
+        let mut cek = [0u8; 16];
+        let mut nonce = [0u8; 12];
+        prk.expand(b"Content-Encoding: aes128gcm\0", &mut cek)
+            .unwrap();
+        prk.expand(b"Content-Encoding: nonce\0", &mut nonce)
+            .unwrap();
+
+        let mut plaintext = Aes128Gcm::new_from_slice(&cek)
+            .unwrap()
+            .decrypt(Nonce::from_slice(&nonce), ciphertext)
+            .unwrap();
+        assert_eq!(plaintext.pop(), Some(2), "last record delimiter");
+        plaintext
+    }
+}
+
+fn insert(id: &str, sender: &str, receiver: &str, content: &str) -> Value {
+    let mut users = [sender, receiver];
+    users.sort();
+    let chat_id = format!("CHAT#{}#{}", users[0], users[1]);
+    let sk = format!("MSG#{}#{}", NOW_MS, id);
+    json!({
+        "eventName": "INSERT",
+        "dynamodb": {
+            "Keys": { "PK": { "S": chat_id }, "SK": { "S": sk } },
+            "NewImage": {
+                "messageId": { "S": id },
+                "chatId": { "S": chat_id },
+                "sender": { "S": sender },
+                "receiver": { "S": receiver },
+                "content": { "S": content },
+                "timestamp": { "N": NOW_MS.to_string() }
+            }
+        }
+    })
+}
+
+/// Checks the `vapid t=<jwt>, k=<key>` header and returns the JWT claims.
+fn verify_vapid(authorization: &str) -> Value {
+    let (token, key) = authorization
+        .strip_prefix("vapid t=")
+        .and_then(|rest| rest.split_once(", k="))
+        .unwrap();
+    let key = VerifyingKey::from_sec1_bytes(&URL_SAFE_NO_PAD.decode(key).unwrap()).unwrap();
+    let (signing_input, signature) = token.rsplit_once('.').unwrap();
+    let signature = Signature::from_slice(&URL_SAFE_NO_PAD.decode(signature).unwrap()).unwrap();
+    key.verify(signing_input.as_bytes(), &signature).unwrap();
+
+    let claims = signing_input.split('.').nth(1).unwrap();
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn pushes_encrypted_messages_to_offline_recipients_unless_muted() {
+    let (url, received) = push_service().await;
+    let phone = Browser::new(7);
+    let laptop = Browser::new(9);
+
+    let mut store = FakeStore::default();
+    store.recipients.insert(
+        "bob".to_string(),
+        Recipient {
+            status: Some("offline".to_string()),
+            last_seen: Some(NOW_MS - 60_000.0),
+            subscriptions: vec![
+                phone.subscription("phone", format!("{}/push/phone", url)),
+                laptop.subscription("laptop", format!("{}/push/gone", url)),
+            ],
+            // Carol's chat is muted until bob turns it back on
+            settings: NotificationSettings {
+                chats: vec![ChatSetting {
+                    chat_id: "CHAT#bob#carol".to_string(),
+                    mute: Some("UNTIL_TURNED_OFF".to_string()),
+                    ..ChatSetting::default()
+                }],
+                do_not_disturb: None,
+            },
+        },
+    );
+    // Online with a fresh heartbeat: the open tab notifies instead
+    store.recipients.insert(
+        "alice".to_string(),
+        Recipient {
+            status: Some("away".to_string()),
+            last_seen: Some(NOW_MS - 30_000.0),
+            subscriptions: vec![Browser::new(3).subscription("desk", format!("{}/push/desk", url))],
+            settings: NotificationSettings::default(),
+        },
+    );
+
+    let handler = Handler {
+        store,
+        sender: PushSender {
+            client: reqwest::Client::new(),
+            vapid: Vapid::new(VAPID_PRIVATE_KEY, "mailto:admin@example.com").unwrap(),
+        },
+    };
+    let event: StreamEvent = serde_json::from_value(json!({
+        "Records": [
+            insert("m1", "alice", "bob", "Lunch?"),
+            insert("m2", "bob", "alice", "Sure"),
+            insert("import-abc-1", "alice", "bob", "old history"),
+            insert("m3", "carol", "bob", "Anyone?"),
+        ]
+    }))
+    .unwrap();
+
+    assert_eq!(handler.handle(event, NOW_MS).await.unwrap(), 1);
+    assert_eq!(*handler.store.removed.borrow(), ["laptop"]);
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    let push = received.iter().find(|r| r.path == "/push/phone").unwrap();
+    assert_eq!(push.headers["content-encoding"], "aes128gcm");
+    assert!(push.headers.contains_key("ttl"));
+
+    let claims = verify_vapid(&push.headers["authorization"]);
+    assert_eq!(claims["aud"], url.as_str());
+    assert_eq!(claims["sub"], "mailto:admin@example.com");
+
+    let notification: Notification = serde_json::from_slice(&phone.decrypt(&push.body)).unwrap();
+    assert_eq!(
+        notification,
+        Notification {
+            title: "alice".to_string(),
+            body: "Lunch?".to_string(),
+            chat_id: "CHAT#alice#bob".to_string(),
+            sender: "alice".to_string(),
+        }
+    );
+}
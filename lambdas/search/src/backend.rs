@@ -0,0 +1,251 @@
+use serde_json::Value;
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Mutex;
+use tantivy::collector::TopDocs;
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, RangeQuery, TermQuery};
+use tantivy::schema::{
+    Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, FAST, INDEXED, STORED, STRING,
+};
+use tantivy::tokenizer::{AsciiFoldingFilter, LowerCaser, SimpleTokenizer, TextAnalyzer};
+use tantivy::{doc, Index, IndexReader, IndexWriter, Order, ReloadPolicy, TantivyDocument, Term};
+
+const WRITER_HEAP_BYTES: usize = 50_000_000;
+/// Lowercases and folds diacritics, like the client-side index.
+const TOKENIZER: &str = "folded";
+
+/// A stored message as the index sees it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedMessage {
+    pub message_id: String,
+    pub chat_id: String,
+    pub content: String,
+    pub timestamp: f64,
+    /// The full message, returned as-is in results.
+    pub message: Value,
+}
+
+impl IndexedMessage {
+    /// Chat ids have the form `CHAT#user1#user2`.
+    pub fn members(&self) -> Vec<&str> {
+        chat_members(&self.chat_id)
+    }
+}
+
+pub fn chat_members(chat_id: &str) -> Vec<&str> {
+    match chat_id.split('#').collect::<Vec<_>>().as_slice() {
+        ["CHAT", a, b] => vec![*a, *b],
+        _ => Vec::new(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchQuery {
+    pub text: String,
+    /// Only chats this user belongs to are searched.
+    pub member: String,
+    pub chat_id: Option<String>,
+    /// Inclusive timestamp bounds in milliseconds.
+    pub from: Option<f64>,
+    pub to: Option<f64>,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchPage {
+    /// Newest first.
+    pub items: Vec<Value>,
+    /// Offset of the next page, if there is one.
+    pub next_offset: Option<usize>,
+}
+
+/// Where messages are indexed. Production could put a hosted search service
+/// behind this; the Lambda and tests use [`TantivyBackend`].
+pub trait SearchBackend {
+    /// Adds or replaces a message. Changes are visible after [`commit`].
+    ///
+    /// [`commit`]: SearchBackend::commit
+    fn upsert(&self, message: &IndexedMessage) -> Result<(), String>;
+    fn delete(&self, message_id: &str) -> Result<(), String>;
+    fn commit(&self) -> Result<(), String>;
+    fn search(&self, query: &SearchQuery) -> Result<SearchPage, String>;
+}
+
+struct Fields {
+    message_id: Field,
+    chat_id: Field,
+    members: Field,
+    content: Field,
+    timestamp: Field,
+    message: Field,
+}
+
+/// Embedded tantivy index in a local directory.
+pub struct TantivyBackend {
+    index: Index,
+    reader: IndexReader,
+    /// Opened on first write, so read-only callers don't take the lock.
+    writer: Mutex<Option<IndexWriter>>,
+    fields: Fields,
+}
+
+impl TantivyBackend {
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let directory = tantivy::directory::MmapDirectory::open(dir).map_err(|e| e.to_string())?;
+        let (schema, _) = schema();
+        Self::new(Index::open_or_create(directory, schema).map_err(|e| e.to_string())?)
+    }
+
+    fn new(index: Index) -> Result<Self, String> {
+        index.tokenizers().register(
+            TOKENIZER,
+            TextAnalyzer::builder(SimpleTokenizer::default())
+                .filter(LowerCaser)
+                .filter(AsciiFoldingFilter)
+                .build(),
+        );
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(|e| e.to_string())?;
+        let (_, fields) = schema();
+        Ok(Self {
+            index,
+            reader,
+            writer: Mutex::new(None),
+            fields,
+        })
+    }
+
+    fn with_writer<T>(
+        &self,
+        f: impl FnOnce(&mut IndexWriter) -> tantivy::Result<T>,
+    ) -> Result<T, String> {
+        let mut writer = self.writer.lock().map_err(|e| e.to_string())?;
+        if writer.is_none() {
+            *writer = Some(
+                self.index
+                    .writer(WRITER_HEAP_BYTES)
+                    .map_err(|e| e.to_string())?,
+            );
+        }
+        f(writer.as_mut().expect("writer was just opened")).map_err(|e| e.to_string())
+    }
+
+    fn term_query(&self, field: Field, value: &str) -> Box<dyn Query> {
+        Box::new(TermQuery::new(
+            Term::from_field_text(field, value),
+            IndexRecordOption::Basic,
+        ))
+    }
+}
+
+fn schema() -> (Schema, Fields) {
+    let mut builder = Schema::builder();
+    let content_options = TextOptions::default().set_indexing_options(
+        TextFieldIndexing::default()
+            .set_tokenizer(TOKENIZER)
+            .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+    );
+    let fields = Fields {
+        message_id: builder.add_text_field("message_id", STRING | STORED),
+        chat_id: builder.add_text_field("chat_id", STRING),
+        members: builder.add_text_field("members", STRING),
+        content: builder.add_text_field("content", content_options),
+        timestamp: builder.add_i64_field("timestamp", INDEXED | FAST),
+        message: builder.add_text_field("message", STORED),
+    };
+    (builder.build(), fields)
+}
+
+impl SearchBackend for TantivyBackend {
+    fn upsert(&self, message: &IndexedMessage) -> Result<(), String> {
+        let f = &self.fields;
+        let mut document = doc!(
+            f.message_id => message.message_id.clone(),
+            f.chat_id => message.chat_id.clone(),
+            f.content => message.content.clone(),
+            f.timestamp => message.timestamp as i64,
+            f.message => message.message.to_string(),
+        );
+        for member in message.members() {
+            document.add_text(f.members, member);
+        }
+        self.with_writer(|writer| {
+            writer.delete_term(Term::from_field_text(f.message_id, &message.message_id));
+            writer.add_document(document)?;
+            Ok(())
+        })
+    }
+
+    fn delete(&self, message_id: &str) -> Result<(), String> {
+        self.with_writer(|writer| {
+            writer.delete_term(Term::from_field_text(self.fields.message_id, message_id));
+            Ok(())
+        })
+    }
+
+    fn commit(&self) -> Result<(), String> {
+        self.with_writer(|writer| writer.commit().map(|_| ()))
+    }
+
+    fn search(&self, query: &SearchQuery) -> Result<SearchPage, String> {
+        let f = &self.fields;
+        let mut parser = QueryParser::for_index(&self.index, vec![f.content]);
+        parser.set_conjunction_by_default();
+        // Stray quotes or colons shouldn't fail the whole search
+        let (text_query, _) = parser.parse_query_lenient(&query.text);
+
+        let mut clauses = vec![
+            (Occur::Must, text_query),
+            (Occur::Must, self.term_query(f.members, &query.member)),
+        ];
+        if let Some(chat_id) = &query.chat_id {
+            clauses.push((Occur::Must, self.term_query(f.chat_id, chat_id)));
+        }
+        if query.from.is_some() || query.to.is_some() {
+            let bound =
+                |value: Option<f64>| value.map_or(Bound::Unbounded, |v| Bound::Included(v as i64));
+            clauses.push((
+                Occur::Must,
+                Box::new(RangeQuery::new_i64_bounds(
+                    "timestamp".to_string(),
+                    bound(query.from),
+                    bound(query.to),
+                )),
+            ));
+        }
+
+        self.reader.reload().map_err(|e| e.to_string())?;
+        let searcher = self.reader.searcher();
+        // One extra hit tells us whether there's another page
+        let top = TopDocs::with_limit(query.limit + 1)
+            .and_offset(query.offset)
+            .order_by_fast_field::<i64>("timestamp", Order::Desc);
+        let hits = searcher
+            .search(&BooleanQuery::new(clauses), &top)
+            .map_err(|e| e.to_string())?;
+
+        let has_more = hits.len() > query.limit;
+        let items = hits
+            .into_iter()
+            .take(query.limit)
+            .map(|(_, address)| {
+                let document: TantivyDocument = searcher.doc(address).map_err(|e| e.to_string())?;
+                let stored = document
+                    .get_first(f.message)
+                    .and_then(|value| tantivy::schema::Value::as_str(&value))
+                    .ok_or("Indexed message has no stored body")?;
+                serde_json::from_str(stored).map_err(|e| e.to_string())
+            })
+            .collect::<Result<Vec<Value>, String>>()?;
+
+        Ok(SearchPage {
+            items,
+            next_offset: has_more.then_some(query.offset + query.limit),
+        })
+    }
+}
@@ -0,0 +1,104 @@
+use crate::backend::{chat_members, SearchBackend, SearchQuery};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/// The subset of an AppSync direct Lambda resolver event we rely on.
+#[derive(Debug, Deserialize)]
+pub struct AppSyncEvent {
+    #[serde(default)]
+    pub arguments: Value,
+    pub identity: Option<Identity>,
+    pub info: Info,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Identity {
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Info {
+    pub field_name: String,
+    pub parent_type_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchMessagesArgs {
+    query: String,
+    chat_id: Option<String>,
+    from: Option<f64>,
+    to: Option<f64>,
+    limit: Option<i64>,
+    next_token: Option<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MessagePage {
+    pub items: Vec<Value>,
+    pub next_token: Option<String>,
+}
+
+pub struct Handler<B> {
+    pub backend: B,
+}
+
+impl<B: SearchBackend> Handler<B> {
+    pub fn handle(&self, event: AppSyncEvent) -> Result<Value, String> {
+        match (
+            event.info.parent_type_name.as_str(),
+            event.info.field_name.as_str(),
+        ) {
+            ("Query", "searchMessages") => {
+                let identity = event.identity.ok_or("Unauthorized")?;
+                let args: SearchMessagesArgs =
+                    serde_json::from_value(event.arguments).map_err(|e| e.to_string())?;
+                let page = self.search_messages(&identity.username, args)?;
+                serde_json::to_value(page).map_err(|e| e.to_string())
+            }
+            (parent, field) => Err(format!("Unsupported field {}.{}", parent, field)),
+        }
+    }
+
+    fn search_messages(
+        &self,
+        username: &str,
+        args: SearchMessagesArgs,
+    ) -> Result<MessagePage, String> {
+        if args.query.trim().is_empty() {
+            return Err("Search query is required".to_string());
+        }
+        if let Some(chat_id) = &args.chat_id {
+            if !chat_members(chat_id).contains(&username) {
+                return Err("Unauthorized".to_string());
+            }
+        }
+        // The token is just the offset of the next page
+        let offset = match &args.next_token {
+            Some(token) => token.parse().map_err(|_| "Invalid nextToken".to_string())?,
+            None => 0,
+        };
+        let limit = args.limit.map_or(DEFAULT_PAGE_SIZE, |l| {
+            l.clamp(1, MAX_PAGE_SIZE as i64) as usize
+        });
+
+        let page = self.backend.search(&SearchQuery {
+            text: args.query,
+            member: username.to_string(),
+            chat_id: args.chat_id,
+            from: args.from,
+            to: args.to,
+            offset,
+            limit,
+        })?;
+        Ok(MessagePage {
+            items: page.items,
+            next_token: page.next_offset.map(|offset| offset.to_string()),
+        })
+    }
+}
@@ -0,0 +1,29 @@
+use lambda_runtime::{service_fn, Error, LambdaEvent};
+use search::backend::TantivyBackend;
+use search::handler::{AppSyncEvent, Handler};
+use search::stream::{self, StreamEvent};
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Deployed twice against the same index directory: as the DynamoDB stream
+/// consumer that writes the index and as the resolver that queries it.
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let dir = std::env::var("SEARCH_INDEX_DIR").map_err(|_| "SEARCH_INDEX_DIR is not set")?;
+    let handler = Handler {
+        backend: TantivyBackend::open(&PathBuf::from(dir))?,
+    };
+    let handler = &handler;
+
+    lambda_runtime::run(service_fn(move |event: LambdaEvent<Value>| async move {
+        if event.payload.get("Records").is_some() {
+            let event: StreamEvent = serde_json::from_value(event.payload)?;
+            stream::apply(&handler.backend, event)?;
+            Ok(Value::Null)
+        } else {
+            let event: AppSyncEvent = serde_json::from_value(event.payload)?;
+            handler.handle(event).map_err(Error::from)
+        }
+    }))
+    .await
+}
@@ -0,0 +1,144 @@
+use crate::backend::{IndexedMessage, SearchBackend};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// The subset of a DynamoDB stream event we rely on.
+#[derive(Debug, Deserialize)]
+pub struct StreamEvent {
+    #[serde(rename = "Records")]
+    pub records: Vec<StreamRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamRecord {
+    /// INSERT, MODIFY or REMOVE
+    pub event_name: String,
+    pub dynamodb: StreamData,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StreamData {
+    pub keys: Map<String, Value>,
+    pub new_image: Option<Map<String, Value>>,
+}
+
+/// Table keys that aren't part of the message itself.
+const KEY_ATTRIBUTES: [&str; 4] = ["PK", "SK", "GSI1PK", "GSI1SK"];
+
+/// Applies a batch of stream records to the index and commits once. Items
+/// other than messages (summaries, receipts, counters) are ignored.
+pub fn apply(backend: &impl SearchBackend, event: StreamEvent) -> Result<usize, String> {
+    let mut applied = 0;
+    for record in event.records {
+        let Some(sort_key) = record.dynamodb.keys.get("SK").map(from_attribute) else {
+            continue;
+        };
+        let Some(message_id) = sort_key
+            .as_str()
+            .filter(|sk| sk.starts_with("MSG#"))
+            .and_then(|sk| sk.rsplit_once('#'))
+            .map(|(_, id)| id.to_string())
+        else {
+            continue;
+        };
+
+        match (record.event_name.as_str(), record.dynamodb.new_image) {
+            ("REMOVE", _) => backend.delete(&message_id)?,
+            (_, Some(image)) => backend.upsert(&to_indexed_message(image)?)?,
+            (_, None) => return Err("Stream records must include new images".to_string()),
+        }
+        applied += 1;
+    }
+    if applied > 0 {
+        backend.commit()?;
+    }
+    Ok(applied)
+}
+
+fn to_indexed_message(image: Map<String, Value>) -> Result<IndexedMessage, String> {
+    let message: Map<String, Value> = image
+        .iter()
+        .filter(|(name, _)| !KEY_ATTRIBUTES.contains(&name.as_str()))
+        .map(|(name, value)| (name.clone(), from_attribute(value)))
+        .collect();
+    let text = |name: &str| {
+        message
+            .get(name)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| format!("Message is missing {}", name))
+    };
+
+    Ok(IndexedMessage {
+        message_id: text("messageId")?,
+        chat_id: text("chatId")?,
+        content: text("content")?,
+        timestamp: message
+            .get("timestamp")
+            .and_then(Value::as_f64)
+            .ok_or("Message is missing timestamp")?,
+        message: Value::Object(message),
+    })
+}
+
+/// Converts a DynamoDB attribute value (`{"S": "..."}`, `{"N": "..."}`, ...)
+/// to plain JSON.
+pub fn from_attribute(value: &Value) -> Value {
+    let Some((kind, inner)) = value.as_object().and_then(|v| v.iter().next()) else {
+        return Value::Null;
+    };
+    match (kind.as_str(), inner) {
+        ("S", s) => s.clone(),
+        ("N", Value::String(n)) => number(n),
+        ("BOOL", b) => b.clone(),
+        ("M", Value::Object(map)) => Value::Object(
+            map.iter()
+                .map(|(name, value)| (name.clone(), from_attribute(value)))
+                .collect(),
+        ),
+        ("L", Value::Array(items)) => Value::Array(items.iter().map(from_attribute).collect()),
+        ("SS", set) => set.clone(),
+        ("NS", Value::Array(items)) => {
+            Value::Array(items.iter().filter_map(Value::as_str).map(number).collect())
+        }
+        _ => Value::Null,
+    }
+}
+
+fn number(n: &str) -> Value {
+    n.parse::<i64>()
+        .map(Value::from)
+        .or_else(|_| n.parse::<f64>().map(Value::from))
+        .unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn converts_attribute_values() {
+        let image = json!({
+            "M": {
+                "content": { "S": "hi" },
+                "timestamp": { "N": "1700000000000.5" },
+                "sequence": { "N": "7" },
+                "mentions": { "L": [{ "M": { "username": { "S": "bob" } } }] },
+                "attachment": { "NULL": true }
+            }
+        });
+        assert_eq!(
+            from_attribute(&image),
+            json!({
+                "content": "hi",
+                "timestamp": 1700000000000.5,
+                "sequence": 7,
+                "mentions": [{ "username": "bob" }],
+                "attachment": null
+            })
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Feeds DynamoDB stream records through the indexer and queries them back
+//! through the resolver, using an embedded index in a temp directory.
+
+use search::backend::TantivyBackend;
+use search::handler::{AppSyncEvent, Handler};
+use search::stream::{self, StreamEvent};
+use serde_json::{json, Value};
+
+fn insert(chat_id: &str, id: &str, sender: &str, content: &str, timestamp: u64) -> Value {
+    let sk = format!("MSG#{}#{}", timestamp, id);
+    json!({
+        "eventName": "INSERT",
+        "dynamodb": {
+            "Keys": { "PK": { "S": chat_id }, "SK": { "S": sk } },
+            "NewImage": {
+                "PK": { "S": chat_id },
+                "SK": { "S": sk },
+                "messageId": { "S": id },
+                "chatId": { "S": chat_id },
+                "sender": { "S": sender },
+                "content": { "S": content },
+                "timestamp": { "N": timestamp.to_string() },
+                "status": { "S": "SENT" }
+            }
+        }
+    })
+}
+
+fn search(
+    handler: &Handler<TantivyBackend>,
+    username: &str,
+    arguments: Value,
+) -> Result<Value, String> {
+    let event: AppSyncEvent = serde_json::from_value(json!({
+        "arguments": arguments,
+        "identity": { "username": username },
+        "info": { "fieldName": "searchMessages", "parentTypeName": "Query" }
+    }))
+    .unwrap();
+    handler.handle(event)
+}
+
+fn ids(page: &Value) -> Vec<&str> {
+    page["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["messageId"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn indexes_stream_records_and_respects_membership() {
+    let dir = std::env::temp_dir().join(format!("search-index-{}", uuid::Uuid::new_v4()));
+    let handler = Handler {
+        backend: TantivyBackend::open(&dir).unwrap(),
+    };
+
+    let event: StreamEvent = serde_json::from_value(json!({
+        "Records": [
+            insert("CHAT#alice#bob", "m1", "alice", "Lunch at the café?", 1000),
+            insert("CHAT#alice#bob", "m2", "bob", "Sure, cafe at noon", 2000),
+            insert("CHAT#alice#carol", "m3", "carol", "The cafe is closed today", 3000),
+            {
+                "eventName": "MODIFY",
+                "dynamodb": {
+                    "Keys": { "PK": { "S": "USER#alice" }, "SK": { "S": "CONV#CHAT#alice#bob" } },
+                    "NewImage": { "unreadCount": { "N": "1" } }
+                }
+            }
+        ]
+    }))
+    .unwrap();
+    assert_eq!(stream::apply(&handler.backend, event).unwrap(), 3);
+
+    // Newest first, accents folded, paged
+    let page = search(&handler, "alice", json!({ "query": "Cafe", "limit": 2 })).unwrap();
+    assert_eq!(ids(&page), ["m3", "m2"]);
+    let page = search(
+        &handler,
+        "alice",
+        json!({ "query": "cafe", "limit": 2, "nextToken": page["nextToken"] }),
+    )
+    .unwrap();
+    assert_eq!(ids(&page), ["m1"]);
+    assert!(page["nextToken"].is_null());
+    assert_eq!(page["items"][0]["content"], "Lunch at the café?");
+
+    // Only chats the caller belongs to
+    let page = search(&handler, "bob", json!({ "query": "cafe" })).unwrap();
+    assert_eq!(ids(&page), ["m2", "m1"]);
+    assert!(search(
+        &handler,
+        "bob",
+        json!({ "query": "cafe", "chatId": "CHAT#alice#carol" })
+    )
+    .is_err());
+
+    let page = search(
+        &handler,
+        "alice",
+        json!({ "query": "cafe", "chatId": "CHAT#alice#bob", "from": 1500 }),
+    )
+    .unwrap();
+    assert_eq!(ids(&page), ["m2"]);
+
+    let removed: StreamEvent = serde_json::from_value(json!({
+        "Records": [{
+            "eventName": "REMOVE",
+            "dynamodb": {
+                "Keys": { "PK": { "S": "CHAT#alice#bob" }, "SK": { "S": "MSG#2000#m2" } }
+            }
+        }]
+    }))
+    .unwrap();
+    stream::apply(&handler.backend, removed).unwrap();
+    let page = search(&handler, "bob", json!({ "query": "cafe" })).unwrap();
+    assert_eq!(ids(&page), ["m1"]);
+
+    let _ = std::fs::remove_dir_all(dir);
+}
@@ -0,0 +1,106 @@
+// Precaches the app shell so the app opens offline and installs as a PWA,
+// shows Web Push notifications sent by the push Lambda while no tab is open,
+// and brings the app to the conversation when one is clicked.
+
+// Filled in with the built assets by scripts/precache.sh after each trunk
+// build, so every deployment changes this file and installs a new worker.
+const PRECACHE = { version: "dev", assets: [] };
+
+const CACHE_PREFIX = "rusty-chat-";
+const CACHE = CACHE_PREFIX + PRECACHE.version;
+const SHELL = "index.html";
+const OFFLINE = "offline.html";
+
+self.addEventListener("install", (event) => {
+  // Bypass the HTTP cache: unhashed files such as styles are served with a
+  // long max-age and would otherwise be precached stale
+  event.waitUntil(
+    caches
+      .open(CACHE)
+      .then((cache) =>
+        cache.addAll(
+          PRECACHE.assets.map((asset) => new Request(asset, { cache: "reload" })),
+        ),
+      ),
+  );
+  // The first worker has nothing to replace; later ones wait for the app's
+  // "reload" prompt so a running session never mixes versions
+  if (!self.registration.active) {
+    self.skipWaiting();
+  }
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) =>
+        Promise.all(
+          keys
+            .filter((key) => key.startsWith(CACHE_PREFIX) && key !== CACHE)
+            .map((key) => caches.delete(key)),
+        ),
+      )
+      .then(() => self.clients.claim()),
+  );
+});
+
+self.addEventListener("message", (event) => {
+  if (event.data && event.data.type === "skip-waiting") {
+    self.skipWaiting();
+  }
+});
+
+self.addEventListener("fetch", (event) => {
+  const request = event.request;
+  if (request.method !== "GET" || new URL(request.url).origin !== self.location.origin) {
+    return;
+  }
+  if (request.mode === "navigate") {
+    event.respondWith(
+      caches
+        .match(SHELL)
+        .then((shell) => shell || fetch(request))
+        .catch(() => caches.match(OFFLINE)),
+    );
+    return;
+  }
+  event.respondWith(caches.match(request).then((cached) => cached || fetch(request)));
+});
+
+self.addEventListener("push", (event) => {
+  if (!event.data) {
+    return;
+  }
+  // { title, body, chatId, sender }; see lambdas/push/src/sender.rs
+  const message = event.data.json();
+  event.waitUntil(
+    self.registration.showNotification(message.title, {
+      body: message.body,
+      tag: message.chatId,
+      icon: "icons/icon.svg",
+      data: { sender: message.sender },
+    }),
+  );
+});
+
+self.addEventListener("notificationclick", (event) => {
+  event.notification.close();
+  const sender = event.notification.data && event.notification.data.sender;
+  event.waitUntil(
+    self.clients
+      .matchAll({ type: "window", includeUncontrolled: true })
+      .then((windows) => {
+        const open = windows[0];
+        if (open) {
+          open.postMessage({ type: "open-chat", username: sender });
+          return open.focus();
+        }
+        const url = new URL(self.registration.scope);
+        if (sender) {
+          url.searchParams.set("chat", sender);
+        }
+        return self.clients.openWindow(url.href);
+      }),
+  );
+});
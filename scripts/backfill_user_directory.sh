@@ -0,0 +1,24 @@
+#!/bin/bash
+
+# Adds the GSI2 user directory keys to users created before searchUsers
+# existed. Safe to run more than once.
+
+TABLE=${DYNAMODB_TABLE_NAME:-ChatMessages}
+
+aws dynamodb scan \
+    --table-name "$TABLE" \
+    --filter-expression "begins_with(PK, :user) AND SK = :metadata AND attribute_not_exists(GSI2PK)" \
+    --expression-attribute-values '{":user": {"S": "USER#"}, ":metadata": {"S": "METADATA"}}' \
+    --projection-expression "username" \
+    --output text --query "Items[].username.S" | tr '\t' '\n' | while read -r username; do
+    [ -z "$username" ] && continue
+    key=$(echo "$username" | tr '[:upper:]' '[:lower:]')
+    echo "Indexing $username"
+    aws dynamodb update-item \
+        --table-name "$TABLE" \
+        --key "{\"PK\": {\"S\": \"USER#$username\"}, \"SK\": {\"S\": \"METADATA\"}}" \
+        --update-expression "SET GSI2PK = :pk, GSI2SK = :sk" \
+        --expression-attribute-values "{\":pk\": {\"S\": \"USERDIR#${key:0:1}\"}, \":sk\": {\"S\": \"$key\"}}" || exit 1
+done
+
+echo "User directory backfill complete"
@@ -0,0 +1,15 @@
+#!/bin/bash
+
+# Builds every Lambda under lambdas/ as a bootstrap zip for terraform.
+# Requires cargo-lambda (https://www.cargo-lambda.info).
+
+for dir in lambdas/*/; do
+    name=$(basename "$dir")
+    echo "Building Lambda $name..."
+    cargo lambda build --release --arm64 --output-format zip -p "$name" || {
+        echo "Error: failed to build $name"
+        exit 1
+    }
+done
+
+echo "Lambdas built into target/lambda/"
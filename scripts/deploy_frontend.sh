@@ -34,14 +34,19 @@ aws s3 sync dist/ s3://$BUCKET_NAME/ \
     --delete \
     --cache-control "max-age=31536000,public" \
     --exclude "*.html" \
-    --exclude "*.wasm"
+    --exclude "*.wasm" \
+    --exclude "sw.js" \
+    --exclude "*.webmanifest"
 
-# Upload HTML and WASM files with different cache settings
+# Upload HTML, WASM, the service worker and the manifest with different cache
+# settings; browsers only see a new deployment if sw.js is revalidated
 aws s3 sync dist/ s3://$BUCKET_NAME/ \
     --delete \
     --cache-control "no-cache" \
     --include "*.html" \
-    --include "*.wasm"
+    --include "*.wasm" \
+    --include "sw.js" \
+    --include "*.webmanifest"
 
 # Invalidate CloudFront cache
 echo "Invalidating CloudFront cache..."
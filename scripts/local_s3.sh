@@ -0,0 +1,28 @@
+#!/bin/bash
+
+# Runs MinIO as a local S3 stand-in for attachment uploads.
+# Point the attachments Lambda (or its tests) at it with:
+#   S3_ENDPOINT=http://localhost:9000 / LOCAL_S3_ENDPOINT=http://localhost:9000
+
+BUCKET=${LOCAL_S3_BUCKET:-attachments}
+
+docker run -d --rm --name rusty-chat-s3 \
+    -p 9000:9000 -p 9001:9001 \
+    -e MINIO_ROOT_USER=minioadmin \
+    -e MINIO_ROOT_PASSWORD=minioadmin \
+    minio/minio server /data --console-address ":9001" || {
+    echo "Error: failed to start MinIO"
+    exit 1
+}
+
+echo "Waiting for MinIO..."
+until curl -sf http://localhost:9000/minio/health/live >/dev/null; do
+    sleep 1
+done
+
+docker run --rm --network host --entrypoint sh minio/mc -c "
+    mc alias set local http://localhost:9000 minioadmin minioadmin &&
+    mc mb --ignore-existing local/$BUCKET
+"
+
+echo "Local S3 ready at http://localhost:9000 (bucket: $BUCKET)"
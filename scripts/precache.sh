@@ -0,0 +1,18 @@
+#!/bin/sh
+
+# Trunk post_build hook: writes the built assets and a version derived from
+# their contents into the service worker, which precaches them per version.
+
+set -e
+
+cd "$TRUNK_STAGING_DIR"
+
+assets=$(find . -type f ! -name sw.js | sed 's|^\./||' | LC_ALL=C sort)
+version=$(cat $assets | sha256sum | cut -c1-16)
+list=$(printf '"%s",' $assets | sed 's/,$//')
+
+sed "s|^const PRECACHE = .*|const PRECACHE = { version: \"$version\", assets: [$list] };|" \
+    sw.js > sw.js.tmp
+mv sw.js.tmp sw.js
+
+echo "Precaching $(echo "$assets" | wc -l | tr -d ' ') assets as version $version"
@@ -2,7 +2,10 @@ use crate::components::chat::Chat;
 use crate::components::login::Login;
 use crate::components::signup::SignUp;
 use crate::services::auth::AuthService;
+use crate::services::service_worker::{ServiceWorkerService, UPDATE_CHECK_MS};
 use crate::state::auth_state::{AuthAction, AuthState};
+use gloo_timers::callback::Interval;
+use web_sys::ServiceWorkerRegistration;
 use yew::prelude::*;
 
 #[function_component(App)]
@@ -27,8 +30,60 @@ pub fn app() -> Html {
 
     let show_signup = use_state(|| false);
     let selected_user = use_state(|| None::<String>);
+    // Set once a new deployment is installed and waiting to take over
+    let update_ready = use_state(|| None::<ServiceWorkerRegistration>);
+
+    {
+        let update_ready = update_ready.clone();
+        use_effect_with((), move |_| {
+            let watch = std::rc::Rc::new(std::cell::RefCell::new(None));
+            if ServiceWorkerService::is_supported() {
+                let watch = watch.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let registration = match ServiceWorkerService::register().await {
+                        Ok(registration) => registration,
+                        Err(e) => {
+                            web_sys::console::warn_1(&format!("Service worker: {}", e).into());
+                            return;
+                        }
+                    };
+                    let on_update = {
+                        let registration = registration.clone();
+                        Callback::from(move |_| update_ready.set(Some(registration.clone())))
+                    };
+                    let listener =
+                        ServiceWorkerService::watch_for_updates(&registration, on_update);
+                    let checks = Interval::new(UPDATE_CHECK_MS, move || {
+                        ServiceWorkerService::check_for_update(&registration)
+                    });
+                    *watch.borrow_mut() = Some((listener, checks));
+                });
+            }
+            move || drop(watch.take())
+        });
+    }
+
+    let update_prompt = update_ready.as_ref().map(|registration| {
+        let on_reload = {
+            let registration = registration.clone();
+            Callback::from(move |_| ServiceWorkerService::apply_update(&registration))
+        };
+        let on_dismiss = {
+            let update_ready = update_ready.clone();
+            Callback::from(move |_| update_ready.set(None))
+        };
+        html! {
+            <div class="update-prompt" role="status">
+                <span>{ "A new version of Rusty Chat Sync is available." }</span>
+                <button onclick={on_reload}>{ "Reload" }</button>
+                <button class="update-prompt-dismiss" onclick={on_dismiss}>{ "Later" }</button>
+            </div>
+        }
+    });
 
     html! {
+        <>
+        { for update_prompt }
         if !auth_state.is_authenticated {
             if *show_signup {
                 <SignUp
@@ -61,5 +116,6 @@ pub fn app() -> Html {
                 }
             />
         }
+        </>
     }
 }
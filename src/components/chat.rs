@@ -1,22 +1,42 @@
 use crate::components::chat_status::ChatStatus;
-use crate::components::conversation_list::ConversationList;
+use crate::components::conversation_list::{ConversationList, SearchResultGroup, UserDirectory};
+use crate::components::do_not_disturb::DoNotDisturbPanel;
+use crate::components::import_dialog::{ImportDialog, ImportProgress};
 use crate::components::message_input::MessageInput;
-use crate::components::message_list::MessageList;
-use crate::graphql::mutations::{
-    CreateMessageResponse, CreateMessageVariables, UpdateUserStatusVariables,
-    CREATE_MESSAGE_MUTATION, UPDATE_USER_STATUS_MUTATION,
+use crate::components::message_list::{FailedAction, ListMessages, MessageList};
+use crate::graphql::subscriptions::{
+    NewMessagePayload, ReadReceiptPayload, SubscriptionPayload, TypingPayload, UserStatusPayload,
+    ON_CREATE_MESSAGE_SUBSCRIPTION, ON_NEW_MESSAGE_SUBSCRIPTION, ON_READ_RECEIPT_SUBSCRIPTION,
+    ON_TYPING_SUBSCRIPTION, ON_USER_STATUS_CHANGE_SUBSCRIPTION,
 };
-use crate::graphql::queries::{
-    GetConversationResponse, ListUsersResponse, GET_CONVERSATION_QUERY, LIST_USERS_QUERY,
-};
-use crate::graphql::subscriptions::{SubscriptionPayload, ON_CREATE_MESSAGE_SUBSCRIPTION};
+use crate::models::attachment::Attachment;
+use crate::models::conversation::Conversation;
 use crate::models::message::{Message, MessageStatus};
+use crate::models::notification_settings::{
+    ChatNotificationSetting, DoNotDisturb, MuteDuration, MuteKind, NotificationSettings,
+};
+use crate::models::user::{User, UserStatus};
+use crate::services::cache::{CacheService, CacheSnapshot};
+use crate::services::messages::{MessageService, IMPORT_BATCH_SIZE};
+use crate::services::notifications::NotificationService;
+use crate::services::presence::{PresenceService, HEARTBEAT_MS, IDLE_TIMEOUT_MS};
+use crate::services::push::PushService;
+use crate::services::upload::{PendingUpload, UploadService, MAX_UPLOAD_BYTES};
+use crate::services::users::UserService;
 use crate::state::auth_state::{AuthAction, AuthState};
 use crate::state::chat_state::{ChatAction, ChatState};
-use crate::utils::graphql_client::GraphQLClient;
+use crate::state::message_store::ChatMessages;
+use crate::utils::export::{self, ConversationExport, ExportFormat};
+use crate::utils::image_processing;
+use crate::utils::search::SearchIndex;
 use crate::utils::websocket::AppSyncWebSocket;
+use gloo::events::EventListener;
+use gloo_timers::callback::{Interval, Timeout};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::rc::Rc;
 use wasm_bindgen::JsCast;
+use web_sys::{File, HtmlSelectElement, MessageEvent, NotificationPermission};
 use yew::prelude::*;
 
 #[derive(Properties, PartialEq)]
@@ -28,35 +48,171 @@ pub struct ChatProps {
     pub on_select_user: Callback<Option<String>>,
 }
 
+/// Quiet period before state changes are written to the offline cache.
+const CACHE_DEBOUNCE_MS: u32 = 1000;
+/// Quiet period after the read cursor moves before the server's unread
+/// counters are cleared.
+const MARK_READ_DEBOUNCE_MS: u32 = 2000;
+
+fn is_online() -> bool {
+    web_sys::window().is_none_or(|w| w.navigator().on_line())
+}
+
+/// Server-side results for the new chat panel's query.
+#[derive(Clone, PartialEq)]
+struct UserSearch {
+    query: String,
+    users: Vec<User>,
+    next_token: Option<String>,
+    loading: bool,
+}
+
 #[function_component(Chat)]
 pub fn chat(props: &ChatProps) -> Html {
-    let chat_state = use_reducer(|| ChatState {
-        messages: Vec::new(),
-        conversations: Vec::new(),
-        is_loading: false,
-        error: None,
-        current_chat_id: None,
-        users: Vec::new(),
-    });
+    let chat_state = {
+        let current_user = props.auth_state.user_id.clone();
+        use_reducer(|| ChatState {
+            chats: HashMap::new(),
+            conversations: Vec::new(),
+            is_loading: false,
+            error: None,
+            current_chat_id: None,
+            users: Vec::new(),
+            current_user,
+            receipts: Vec::new(),
+            typing: Vec::new(),
+        })
+    };
 
     let ws = use_state(|| None::<Rc<AppSyncWebSocket>>);
+    let receipts_ws = use_state(|| None::<Rc<AppSyncWebSocket>>);
+    let typing_ws = use_state(|| None::<Rc<AppSyncWebSocket>>);
+    let presence_ws = use_state(|| None::<Rc<AppSyncWebSocket>>);
+    let inbox_ws = use_state(|| None::<Rc<AppSyncWebSocket>>);
+    // Highest read cursor we've sent per chat, to avoid redundant mutations
+    let read_cursors = use_mut_ref(HashMap::<String, f64>::new);
+    // Pending markConversationRead per chat, restarted as the cursor moves
+    let mark_read_timers = use_mut_ref(HashMap::<String, Timeout>::new);
+    let online = use_state(is_online);
+    // Set once the offline cache has been read, so an empty state isn't
+    // written over it first
+    let cache_ready = use_mut_ref(|| false);
+    let cache_snapshot = use_mut_ref(CacheSnapshot::default);
+    let cache_timer = use_mut_ref(|| None::<Timeout>);
     let show_scroll_bottom = use_state(|| false);
     let show_mobile = use_state(|| false);
+    // Grows as messages are loaded or arrive; never rebuilt
+    let search_index = use_mut_ref(SearchIndex::default);
+    // The version of each chat that was last indexed
+    let indexed_chats = use_mut_ref(HashMap::<String, Rc<ChatMessages>>::new);
+    // Bumped when the index grows, so an open search sees new messages
+    let search_index_len = use_state_eq(|| 0usize);
+    let search_query = use_state(String::new);
+    // Server hits for the query they were fetched for; covers history that
+    // was never downloaded
+    let server_hits = use_state(|| (String::new(), Vec::<Message>::new()));
+    let focus_message_id = use_state(|| None::<String>);
+    let exporting = use_state(|| false);
+    let show_import = use_state(|| false);
+    let import_progress = use_state(|| None::<ImportProgress>);
+    let notification_prompt = use_state(NotificationService::should_prompt);
+    // Synced from the server; the push Lambda applies the same settings
+    let notification_settings = use_state(NotificationSettings::default);
+    // What the inbox subscription reads, since it outlives renders
+    let latest_settings = use_mut_ref(NotificationSettings::default);
+    *latest_settings.borrow_mut() = (*notification_settings).clone();
+    let show_dnd = use_state(|| false);
+    // Attachments of messages until they're sent, so failed ones can be
+    // retried
+    let pending_files = use_mut_ref(HashMap::<String, PendingUpload>::new);
+    // A failed message being edited before it's sent again
+    let edit_draft = use_state(|| None::<Message>);
+    // Opens a conversation from a notification; the inbox subscription
+    // outlives renders, so it reads the latest handler through this
+    let open_from_notification = use_mut_ref(Callback::<String>::default);
+    let user_query = use_state(String::new);
+    let user_search = use_state(|| None::<UserSearch>);
+    // Latest user query, so slow responses for older ones are dropped
+    let latest_user_query = use_mut_ref(String::new);
+
+    // Inbox: every message addressed to us, whichever chat it belongs to
+    {
+        let chat_state = chat_state.clone();
+        let inbox_ws = inbox_ws.clone();
+        let token = props.auth_state.token.clone();
+        let username = props.auth_state.user_id.clone().unwrap_or_default();
+        let open_from_notification = open_from_notification.clone();
+        let latest_settings = latest_settings.clone();
+
+        // Reconnects when the network comes back; the fetch effects pick up
+        // whatever arrived meanwhile
+        use_effect_with((token, *online), move |(token, online)| {
+            if let (Some(token), true) = (token, *online) {
+                let delivery_token = token.clone();
+                let websocket = AppSyncWebSocket::new(
+                    token,
+                    ON_NEW_MESSAGE_SUBSCRIPTION,
+                    None,
+                    move |payload| {
+                        let Ok(NewMessagePayload { data }) =
+                            serde_json::from_value::<NewMessagePayload>(payload)
+                        else {
+                            return;
+                        };
+                        let Some(data) = data.on_new_message else {
+                            return;
+                        };
+                        let message = Message::from_message_data(data);
+
+                        let open = open_from_notification.clone();
+                        let sender = message.sender.clone();
+                        NotificationService::notify(
+                            &message,
+                            &username,
+                            &latest_settings.borrow(),
+                            move || open.borrow().emit(sender),
+                        );
+
+                        let chat_id = message.chat_id.clone();
+                        let timestamp = message.timestamp;
+                        let token = delivery_token.clone();
+                        wasm_bindgen_futures::spawn_local(async move {
+                            let _ = MessageService::update_last_seen(
+                                chat_id,
+                                Some(timestamp),
+                                None,
+                                &token,
+                            )
+                            .await;
+                        });
+
+                        chat_state.dispatch(ChatAction::AddMessage(message));
+                    },
+                );
+                inbox_ws.set(Some(Rc::new(websocket)));
+            } else {
+                inbox_ws.set(None);
+            }
+            || ()
+        });
+    }
 
     // WebSocket effect
     {
         let chat_state = chat_state.clone();
         let ws = ws.clone();
+        let receipts_ws = receipts_ws.clone();
+        let typing_ws = typing_ws.clone();
         let token = props.auth_state.token.clone();
         let chat_id = chat_state.current_chat_id.clone();
 
-        use_effect_with((token, chat_id), move |deps| {
-            let (token, chat_id) = deps.clone();
-            if let (Some(token), Some(chat_id)) = (token, chat_id) {
-                let chat_state = chat_state.clone();
+        use_effect_with((token, chat_id, *online), move |deps| {
+            let (token, chat_id, online) = deps.clone();
+            if let (Some(token), Some(chat_id), true) = (token, chat_id, online) {
+                let messages_state = chat_state.clone();
                 let websocket = AppSyncWebSocket::new(
                     &token,
-                    &ON_CREATE_MESSAGE_SUBSCRIPTION,
+                    ON_CREATE_MESSAGE_SUBSCRIPTION,
                     Some(serde_json::json!({
                         "chatId": chat_id
                     })),
@@ -67,11 +223,50 @@ pub fn chat(props: &ChatProps) -> Html {
                             let message = Message::from_message_data(
                                 subscription_data.data.on_create_message,
                             );
-                            chat_state.dispatch(ChatAction::AddMessage(message));
+                            messages_state.dispatch(ChatAction::AddMessage(message));
                         }
                     },
                 );
                 ws.set(Some(Rc::new(websocket)));
+
+                let receipts_state = chat_state.clone();
+                let websocket = AppSyncWebSocket::new(
+                    &token,
+                    ON_READ_RECEIPT_SUBSCRIPTION,
+                    Some(serde_json::json!({
+                        "chatId": chat_id
+                    })),
+                    move |payload| {
+                        if let Ok(ReadReceiptPayload { data }) =
+                            serde_json::from_value::<ReadReceiptPayload>(payload)
+                        {
+                            if let Some(receipt) = data.on_read_receipt {
+                                receipts_state.dispatch(ChatAction::ApplyReceipt(receipt));
+                            }
+                        }
+                    },
+                );
+                receipts_ws.set(Some(Rc::new(websocket)));
+
+                let typing_state = chat_state.clone();
+                let websocket = AppSyncWebSocket::new(
+                    &token,
+                    ON_TYPING_SUBSCRIPTION,
+                    Some(serde_json::json!({
+                        "chatId": chat_id
+                    })),
+                    move |payload| {
+                        if let Ok(TypingPayload { data }) =
+                            serde_json::from_value::<TypingPayload>(payload)
+                        {
+                            if let Some(event) = data.on_typing {
+                                typing_state
+                                    .dispatch(ChatAction::SetTyping(event, js_sys::Date::now()));
+                            }
+                        }
+                    },
+                );
+                typing_ws.set(Some(Rc::new(websocket)));
             }
             || ()
         });
@@ -84,25 +279,38 @@ pub fn chat(props: &ChatProps) -> Html {
         let token = props.auth_state.token.clone();
         let selected_user = props.selected_user.clone();
 
-        use_effect_with((token, selected_user), move |deps| {
-            let (token, selected_user) = deps.clone();
-            if let (Some(token), Some(username)) = (token, selected_user) {
+        use_effect_with((token, selected_user, *online), move |deps| {
+            let (token, selected_user, online) = deps.clone();
+            if let (Some(token), Some(username), Some(me)) =
+                (token, selected_user, auth_state.user_id.clone())
+            {
                 let chat_state = chat_state.clone();
                 let auth_state = auth_state.clone();
-
+                let chat_id = Conversation::chat_id_for(&me, &username);
+                chat_state.dispatch(ChatAction::SetCurrentChatId(Some(chat_id.clone())));
+                // Loaded chats get the newest page too, merged in: messages
+                // from other devices or sent while the subscription was down
+                // only arrive this way. Offline, the cached messages are all
+                // there is.
                 let token_clone = token.clone();
-                wasm_bindgen_futures::spawn_local(async move {
-                    if let Err(e) =
-                        fetch_conversation_messages(&chat_state, username.to_string(), &token_clone)
-                            .await
-                    {
-                        if e.contains("expired") || e.contains("token") {
-                            auth_state.dispatch(AuthAction::Logout);
-                        } else {
-                            chat_state.dispatch(ChatAction::SetError(e));
+                if online {
+                    wasm_bindgen_futures::spawn_local(async move {
+                        let result = fetch_conversation_messages(
+                            &chat_state,
+                            chat_id.clone(),
+                            username.to_string(),
+                            &token_clone,
+                        )
+                        .await;
+                        if let Err(e) = result {
+                            if e.contains("expired") || e.contains("token") {
+                                auth_state.dispatch(AuthAction::Logout);
+                            } else {
+                                chat_state.dispatch(ChatAction::SetChatError(chat_id, e));
+                            }
                         }
-                    }
-                });
+                    });
+                }
             }
             || ()
         });
@@ -116,22 +324,484 @@ pub fn chat(props: &ChatProps) -> Html {
         let show_mobile = show_mobile.clone();
 
         Callback::from(move |username: String| {
-            let mut users = vec![
-                auth_state.user_id.clone().unwrap_or_default(),
-                username.clone(),
-            ];
-            users.sort();
-            let chat_id = format!("CHAT#{}#{}", users[0], users[1]);
+            let chat_id = Conversation::chat_id_for(
+                &auth_state.user_id.clone().unwrap_or_default(),
+                &username,
+            );
+
+            let has_unread = chat_state
+                .conversations
+                .iter()
+                .any(|c| c.chat_id == chat_id && (c.unread_count > 0 || c.mention_count > 0));
+            if let (true, Some(token)) = (has_unread, auth_state.token.clone()) {
+                let chat_id = chat_id.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let _ = MessageService::mark_conversation_read(chat_id, &token).await;
+                });
+            }
+
             chat_state.dispatch(ChatAction::SetCurrentChatId(Some(chat_id)));
             on_select_user.emit(Some(username));
             show_mobile.set(false);
         })
     };
 
-    // Add search handler (minimal for now)
-    let on_search_users = Callback::from(|_query: String| {
-        // We'll implement this later
-    });
+    *open_from_notification.borrow_mut() = on_select_conversation.clone();
+
+    // Notification settings changed on another device apply once this tab
+    // is looked at again
+    {
+        let notification_settings = notification_settings.clone();
+        let token = props.auth_state.token.clone();
+        use_effect_with(token, move |token| {
+            let listener = token.clone().and_then(|token| {
+                let load = move || {
+                    let notification_settings = notification_settings.clone();
+                    let token = token.clone();
+                    wasm_bindgen_futures::spawn_local(async move {
+                        match NotificationService::load_settings(&token).await {
+                            Ok(settings) => notification_settings.set(settings),
+                            Err(e) => web_sys::console::warn_1(
+                                &format!("Notification settings: {}", e).into(),
+                            ),
+                        }
+                    });
+                };
+                load();
+                let document = web_sys::window()?.document()?;
+                let visible = document.clone();
+                Some(EventListener::new(
+                    &document,
+                    "visibilitychange",
+                    move |_| {
+                        if !visible.hidden() {
+                            load();
+                        }
+                    },
+                ))
+            });
+            move || drop(listener)
+        });
+    }
+
+    // Tab title and favicon carry the total unread count, leaving out chats
+    // muted forever
+    {
+        let unread: i32 = chat_state
+            .conversations
+            .iter()
+            .filter(|c| notification_settings.chat(&c.chat_id).mute != Some(MuteKind::Forever))
+            .map(|c| c.unread_count)
+            .sum();
+        use_effect_with(unread, |unread| {
+            NotificationService::set_unread_badge(*unread);
+            || NotificationService::set_unread_badge(0)
+        });
+    }
+
+    // Web Push covers the time no tab is open; re-registering on each
+    // session keeps the stored subscription current
+    {
+        let token = props.auth_state.token.clone();
+        use_effect_with(token, |token| {
+            if let Some(token) = token.clone() {
+                if NotificationService::permission() == NotificationPermission::Granted {
+                    wasm_bindgen_futures::spawn_local(async move {
+                        if let Err(e) = PushService::enable(&token).await {
+                            web_sys::console::warn_1(&format!("Web Push: {}", e).into());
+                        }
+                    });
+                }
+            }
+            || ()
+        });
+    }
+
+    // Notification clicks from the service worker: a message to an open tab,
+    // or `?chat=<username>` on a tab it opened
+    {
+        let open_from_notification = open_from_notification.clone();
+        use_effect_with((), move |_| {
+            let window = web_sys::window();
+            let chat = window
+                .as_ref()
+                .and_then(|w| w.location().search().ok())
+                .and_then(|search| web_sys::UrlSearchParams::new_with_str(&search).ok())
+                .and_then(|params| params.get("chat"));
+            if let (Some(username), Some(window)) = (chat, &window) {
+                if let Ok(history) = window.history() {
+                    let _ = history.replace_state_with_url(
+                        &wasm_bindgen::JsValue::NULL,
+                        "",
+                        Some(&window.location().pathname().unwrap_or_default()),
+                    );
+                }
+                open_from_notification.borrow().emit(username);
+            }
+
+            let listener = window.map(|w| {
+                let open_from_notification = open_from_notification.clone();
+                EventListener::new(&w.navigator().service_worker(), "message", move |e| {
+                    let Some(data) = e.dyn_ref::<MessageEvent>().map(|e| e.data()) else {
+                        return;
+                    };
+                    let field = |name: &str| {
+                        js_sys::Reflect::get(&data, &name.into())
+                            .ok()
+                            .and_then(|v| v.as_string())
+                    };
+                    if let (Some("open-chat"), Some(username)) =
+                        (field("type").as_deref(), field("username"))
+                    {
+                        open_from_notification.borrow().emit(username);
+                    }
+                })
+            });
+            move || drop(listener)
+        });
+    }
+
+    let on_enable_notifications = {
+        let notification_prompt = notification_prompt.clone();
+        let token = props.auth_state.token.clone();
+        Callback::from(move |_| {
+            let notification_prompt = notification_prompt.clone();
+            let token = token.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let permission = NotificationService::request_permission().await;
+                notification_prompt.set(NotificationService::should_prompt());
+                if let (Ok(NotificationPermission::Granted), Some(token)) = (permission, token) {
+                    if let Err(e) = PushService::enable(&token).await {
+                        web_sys::console::warn_1(&format!("Web Push: {}", e).into());
+                    }
+                }
+            });
+        })
+    };
+
+    // "all", "mentions" or a MuteDuration key from the header menu
+    let on_chat_notifications = {
+        let notification_settings = notification_settings.clone();
+        let chat_state = chat_state.clone();
+        let token = props.auth_state.token.clone();
+        Callback::from(move |choice: String| {
+            let (Some(chat_id), Some(token)) = (chat_state.current_chat_id.clone(), token.clone())
+            else {
+                return;
+            };
+            let mut setting = notification_settings.chat(&chat_id);
+            match choice.as_str() {
+                "all" | "mentions" => {
+                    setting.mentions_only = choice == "mentions";
+                    setting.mute = None;
+                    setting.muted_until = None;
+                }
+                key => {
+                    let Some(duration) = MuteDuration::from_key(key) else {
+                        return;
+                    };
+                    let (mute, muted_until) = duration.mute(js_sys::Date::now());
+                    setting.mute = Some(mute);
+                    setting.muted_until = muted_until;
+                }
+            }
+
+            let mut next = (*notification_settings).clone();
+            next.set_chat(setting.clone());
+            notification_settings.set(next);
+            let chat_state = chat_state.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Err(e) = NotificationService::save_chat_setting(&token, &setting).await {
+                    chat_state.dispatch(ChatAction::SetError(e));
+                }
+            });
+        })
+    };
+
+    let on_save_dnd = {
+        let notification_settings = notification_settings.clone();
+        let show_dnd = show_dnd.clone();
+        let chat_state = chat_state.clone();
+        let token = props.auth_state.token.clone();
+        Callback::from(move |dnd: DoNotDisturb| {
+            show_dnd.set(false);
+            let Some(token) = token.clone() else {
+                return;
+            };
+            let mut next = (*notification_settings).clone();
+            next.do_not_disturb = Some(dnd.clone());
+            notification_settings.set(next);
+            let chat_state = chat_state.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Err(e) = NotificationService::save_do_not_disturb(&token, &dnd).await {
+                    chat_state.dispatch(ChatAction::SetError(e));
+                }
+            });
+        })
+    };
+
+    let on_search = {
+        let search_query = search_query.clone();
+        Callback::from(move |query: String| search_query.set(query))
+    };
+
+    let on_select_search_result = {
+        let on_select_conversation = on_select_conversation.clone();
+        let focus_message_id = focus_message_id.clone();
+        Callback::from(move |(username, message_id): (String, String)| {
+            on_select_conversation.emit(username);
+            focus_message_id.set(Some(message_id));
+        })
+    };
+
+    // Debounced so each keystroke doesn't hit the server
+    {
+        let server_hits = server_hits.clone();
+        let token = props.auth_state.token.clone();
+        let query = (*search_query).clone();
+
+        use_effect_with((query, *online), move |(query, online)| {
+            let timer = (*online && !query.trim().is_empty())
+                .then_some(token)
+                .flatten()
+                .map(|token| {
+                    let query = query.clone();
+                    Timeout::new(SEARCH_DEBOUNCE_MS, move || {
+                        wasm_bindgen_futures::spawn_local(async move {
+                            if let Ok(messages) =
+                                MessageService::search(&query, SEARCH_RESULT_LIMIT, &token).await
+                            {
+                                server_hits.set((query, messages));
+                            }
+                        });
+                    })
+                });
+            move || drop(timer)
+        });
+    }
+
+    {
+        let chat_state = chat_state.clone();
+        let user_search = user_search.clone();
+        let latest_user_query = latest_user_query.clone();
+        let token = props.auth_state.token.clone();
+        let query = user_query.trim().to_string();
+
+        use_effect_with((query, *online), move |(query, online)| {
+            *latest_user_query.borrow_mut() = query.clone();
+            // Offline or empty, the panel filters the users we already know
+            let timer = match token.filter(|_| *online && !query.is_empty()) {
+                None => {
+                    user_search.set(None);
+                    None
+                }
+                Some(token) => {
+                    let previous = (*user_search).clone().map(|s| s.users).unwrap_or_default();
+                    user_search.set(Some(UserSearch {
+                        query: query.clone(),
+                        users: previous,
+                        next_token: None,
+                        loading: true,
+                    }));
+                    let query = query.clone();
+                    Some(Timeout::new(SEARCH_DEBOUNCE_MS, move || {
+                        wasm_bindgen_futures::spawn_local(async move {
+                            let result = UserService::search(&query, None, &token).await;
+                            if let Ok((users, _)) = &result {
+                                chat_state.dispatch(ChatAction::AddUsers(users.clone()));
+                            }
+                            if *latest_user_query.borrow() != query {
+                                return;
+                            }
+                            user_search.set(result.ok().map(|(users, next_token)| UserSearch {
+                                query,
+                                users,
+                                next_token,
+                                loading: false,
+                            }));
+                        });
+                    }))
+                }
+            };
+            move || drop(timer)
+        });
+    }
+
+    let on_more_users = {
+        let chat_state = chat_state.clone();
+        let user_search = user_search.clone();
+        let latest_user_query = latest_user_query.clone();
+        let token = props.auth_state.token.clone();
+
+        Callback::from(move |_| {
+            let (Some(search), Some(token)) = ((*user_search).clone(), token.clone()) else {
+                return;
+            };
+            let Some(next_token) = search.next_token.clone().filter(|_| !search.loading) else {
+                return;
+            };
+            user_search.set(Some(UserSearch {
+                loading: true,
+                ..search.clone()
+            }));
+            let chat_state = chat_state.clone();
+            let user_search = user_search.clone();
+            let latest_user_query = latest_user_query.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let result = UserService::search(&search.query, Some(next_token), &token).await;
+                if let Ok((users, _)) = &result {
+                    chat_state.dispatch(ChatAction::AddUsers(users.clone()));
+                }
+                if *latest_user_query.borrow() != search.query {
+                    return;
+                }
+                let mut search = search;
+                search.loading = false;
+                if let Ok((users, next_token)) = result {
+                    search.users.extend(users);
+                    search.next_token = next_token;
+                }
+                user_search.set(Some(search));
+            });
+        })
+    };
+
+    // Index chats whose messages changed. The reducer replaces a chat's Rc
+    // whenever it touches it, so the pointers say which ones did.
+    {
+        let search_index = search_index.clone();
+        let indexed_chats = indexed_chats.clone();
+        let search_index_len = search_index_len.clone();
+        let chats = chat_state.chats.clone();
+        let mut versions: Vec<(String, usize)> = chats
+            .iter()
+            .map(|(chat_id, chat)| (chat_id.clone(), Rc::as_ptr(chat) as usize))
+            .collect();
+        versions.sort();
+
+        use_effect_with(versions, move |_| {
+            let mut index = search_index.borrow_mut();
+            let mut indexed = indexed_chats.borrow_mut();
+            for (chat_id, chat) in &chats {
+                if indexed
+                    .get(chat_id)
+                    .is_some_and(|done| Rc::ptr_eq(done, chat))
+                {
+                    continue;
+                }
+                for msg in chat.messages() {
+                    index.add(msg);
+                }
+                indexed.insert(chat_id.clone(), chat.clone());
+            }
+            search_index_len.set(index.len());
+            || ()
+        });
+    }
+
+    let search_results = if search_query.trim().is_empty() {
+        Vec::new()
+    } else {
+        let index = search_index.borrow();
+        let (hits_query, hits) = &*server_hits;
+        let remote = if *hits_query == *search_query {
+            hits.as_slice()
+        } else {
+            &[]
+        };
+        group_search_results(&chat_state, &index, &search_query, remote)
+    };
+
+    // Offline, the export covers what's stored locally
+    let on_export = {
+        let chat_state = chat_state.clone();
+        let exporting = exporting.clone();
+        let selected_user = props.selected_user.clone();
+        let auth_state = props.auth_state.clone();
+        let online = *online;
+
+        Callback::from(move |format: ExportFormat| {
+            let (Some(other), Some(username)) = (selected_user.clone(), auth_state.user_id.clone())
+            else {
+                return;
+            };
+            let chat_id = Conversation::chat_id_for(&username, &other);
+            let local = chat_state.current_messages().to_vec();
+            let token = auth_state.token.clone().filter(|_| online);
+            let chat_state = chat_state.clone();
+            let exporting = exporting.clone();
+            exporting.set(true);
+
+            wasm_bindgen_futures::spawn_local(async move {
+                let messages = match token {
+                    Some(token) => MessageService::fetch_full_history(&other, &token).await,
+                    None => Ok(local),
+                };
+                let result = messages.and_then(|messages| {
+                    let export = ConversationExport::new(&chat_id, &username, &messages);
+                    export::download(
+                        &export.file_name(format),
+                        format.mime_type(),
+                        &export.render(format),
+                    )
+                });
+                if let Err(e) = result {
+                    chat_state.dispatch(ChatAction::SetError(format!("Export failed: {}", e)));
+                }
+                exporting.set(false);
+            });
+        })
+    };
+
+    // Written in batches; the chat is reloaded afterwards so imported history
+    // shows up in place
+    let on_import = {
+        let chat_state = chat_state.clone();
+        let import_progress = import_progress.clone();
+        let selected_user = props.selected_user.clone();
+        let auth_state = props.auth_state.clone();
+
+        Callback::from(move |messages: Vec<Message>| {
+            let (Some(other), Some(username), Some(token)) = (
+                selected_user.clone(),
+                auth_state.user_id.clone(),
+                auth_state.token.clone(),
+            ) else {
+                return;
+            };
+            let chat_id = Conversation::chat_id_for(&username, &other);
+            let chat_state = chat_state.clone();
+            let import_progress = import_progress.clone();
+            let total = messages.len();
+            import_progress.set(Some(ImportProgress::Running { written: 0, total }));
+
+            wasm_bindgen_futures::spawn_local(async move {
+                let mut written = 0;
+                let mut result = Ok(());
+                for batch in messages.chunks(IMPORT_BATCH_SIZE) {
+                    result = MessageService::import_batch(&chat_id, batch, &token).await;
+                    if result.is_err() {
+                        break;
+                    }
+                    written += batch.len();
+                    import_progress.set(Some(ImportProgress::Running { written, total }));
+                }
+                import_progress.set(Some(match result {
+                    Ok(()) => ImportProgress::Done { written },
+                    Err(e) => ImportProgress::Failed(e),
+                }));
+                if written > 0 {
+                    // The import may predate everything loaded so far
+                    chat_state.dispatch(ChatAction::InvalidateHistory(chat_id.clone()));
+                    if let Err(e) =
+                        fetch_conversation_messages(&chat_state, chat_id, other, &token).await
+                    {
+                        chat_state.dispatch(ChatAction::SetError(e));
+                    }
+                }
+            });
+        })
+    };
 
     // Update message send handler to match MessageInput's expected type
     let on_send = {
@@ -148,19 +818,213 @@ pub fn chat(props: &ChatProps) -> Html {
                 let chat_state = chat_state.clone();
                 let auth_state = auth_state.clone();
                 let content = msg.content.clone();
+                let mentions = msg.mentions.clone();
 
-                let msg = Message::new_text(content, sender.clone(), receiver.clone());
+                let mut msg = Message::new_text(content, sender.clone(), receiver.clone());
+                msg.mentions = mentions;
                 chat_state.dispatch(ChatAction::AddMessage(msg.clone()));
 
                 let receiver = receiver.clone();
 
                 wasm_bindgen_futures::spawn_local(async move {
                     if let Err(e) = handle_message_send(&chat_state, msg, receiver, &token).await {
-                        if e.contains("expired") || e.contains("token") {
-                            auth_state.dispatch(AuthAction::Logout);
-                        } else {
-                            chat_state.dispatch(ChatAction::SetError(e));
+                        report_send_error(&chat_state, &auth_state, e);
+                    }
+                });
+            } else {
+                chat_state.dispatch(ChatAction::SetError(
+                    "Please select a user to send message to".to_string(),
+                ));
+            }
+        })
+    };
+
+    let on_read = {
+        let chat_state = chat_state.clone();
+        let token = props.auth_state.token.clone();
+        let read_cursors = read_cursors.clone();
+        let mark_read_timers = mark_read_timers.clone();
+
+        Callback::from(move |timestamp: f64| {
+            let (Some(token), Some(chat_id)) = (token.clone(), chat_state.current_chat_id.clone())
+            else {
+                return;
+            };
+            {
+                let mut cursors = read_cursors.borrow_mut();
+                let cursor = cursors.entry(chat_id.clone()).or_insert(0.0);
+                if timestamp <= *cursor {
+                    return;
+                }
+                *cursor = timestamp;
+            }
+            // The server counts every message as unread until this runs, so
+            // messages read in the open chat must clear it too
+            let timer = {
+                let chat_id = chat_id.clone();
+                let token = token.clone();
+                Timeout::new(MARK_READ_DEBOUNCE_MS, move || {
+                    wasm_bindgen_futures::spawn_local(async move {
+                        if let Err(e) =
+                            MessageService::mark_conversation_read(chat_id, &token).await
+                        {
+                            web_sys::console::log_1(
+                                &format!("Failed to mark conversation read: {}", e).into(),
+                            );
                         }
+                    });
+                })
+            };
+            mark_read_timers.borrow_mut().insert(chat_id.clone(), timer);
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Err(e) =
+                    MessageService::update_last_seen(chat_id, None, Some(timestamp), &token).await
+                {
+                    web_sys::console::log_1(&format!("Failed to update read cursor: {}", e).into());
+                }
+            });
+        })
+    };
+
+    let on_load_older = {
+        let chat_state = chat_state.clone();
+        let token = props.auth_state.token.clone();
+        let selected_user = props.selected_user.clone();
+
+        Callback::from(move |_| {
+            let (Some(token), Some(other), Some(chat_id), Some(chat)) = (
+                token.clone(),
+                selected_user.clone(),
+                chat_state.current_chat_id.clone(),
+                chat_state.current_chat(),
+            ) else {
+                return;
+            };
+            let Some(cursor) = chat.history_cursor.clone() else {
+                return;
+            };
+            if chat.loading_history || chat.is_loading {
+                return;
+            }
+            chat_state.dispatch(ChatAction::SetLoadingHistory(chat_id.clone(), true));
+            let chat_state = chat_state.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Err(e) =
+                    fetch_older_messages(&chat_state, chat_id.clone(), other, cursor, &token).await
+                {
+                    chat_state.dispatch(ChatAction::SetChatError(chat_id, e));
+                }
+            });
+        })
+    };
+
+    // Expire typing indicators that stopped receiving updates
+    {
+        let chat_state = chat_state.clone();
+        let has_typing = !chat_state.typing.is_empty();
+        use_effect_with(has_typing, move |has_typing| {
+            let interval = has_typing.then(|| {
+                Interval::new(1000, move || {
+                    chat_state.dispatch(ChatAction::ExpireTyping(js_sys::Date::now()))
+                })
+            });
+            move || drop(interval)
+        });
+    }
+
+    let on_typing = {
+        let token = props.auth_state.token.clone();
+        let chat_id = chat_state.current_chat_id.clone();
+
+        Callback::from(move |is_typing: bool| {
+            if let (Some(token), Some(chat_id)) = (token.clone(), chat_id.clone()) {
+                wasm_bindgen_futures::spawn_local(async move {
+                    // Best effort: a lost typing event only affects the indicator
+                    let _ = MessageService::send_typing(chat_id, is_typing, &token).await;
+                });
+            }
+        })
+    };
+
+    let typing_text = {
+        let names: Vec<&str> = chat_state
+            .typing
+            .iter()
+            .filter(|t| chat_state.current_chat_id.as_ref() == Some(&t.chat_id))
+            .map(|t| t.username.as_str())
+            .collect();
+        match names.as_slice() {
+            [] => None,
+            [name] => Some(format!("{} is typing…", name)),
+            names => Some(format!("{} are typing…", names.join(", "))),
+        }
+    };
+
+    let on_attach = {
+        let chat_state = chat_state.clone();
+        let auth_state = props.auth_state.clone();
+        let pending_files = pending_files.clone();
+        let selected_user = props.selected_user.clone();
+        let current_user = props.auth_state.user_id.clone();
+        let token = props.auth_state.token.clone();
+
+        Callback::from(move |file: File| {
+            if let (Some(token), Some(receiver), Some(sender)) =
+                (token.clone(), selected_user.clone(), current_user.clone())
+            {
+                let size = file.size() as u64;
+                let mime_type = match file.type_() {
+                    t if t.is_empty() => "application/octet-stream".to_string(),
+                    t => t,
+                };
+
+                if image_processing::keeps_metadata(&mime_type) {
+                    chat_state.dispatch(ChatAction::SetError(format!(
+                        "{} can't be sent: only JPEG, PNG, WebP, GIF, SVG and BMP images are supported",
+                        file.name()
+                    )));
+                    return;
+                }
+                // Processable images are checked again after downscaling
+                if size > MAX_UPLOAD_BYTES && !image_processing::is_processable(&mime_type) {
+                    chat_state.dispatch(ChatAction::SetError(format!(
+                        "{} is larger than {} MB",
+                        file.name(),
+                        MAX_UPLOAD_BYTES / 1024 / 1024
+                    )));
+                    return;
+                }
+
+                let is_image = mime_type.starts_with("image/");
+                let attachment = Attachment {
+                    key: String::new(),
+                    file_name: file.name(),
+                    mime_type,
+                    size,
+                    width: None,
+                    height: None,
+                    placeholder: None,
+                    // Local preview until the upload completes
+                    url: is_image
+                        .then(|| UploadService::create_object_url(&file))
+                        .flatten(),
+                };
+
+                let msg = Message::new_attachment(sender, receiver.clone(), attachment);
+                chat_state.dispatch(ChatAction::AddMessage(msg.clone()));
+                pending_files
+                    .borrow_mut()
+                    .insert(msg.message_id.clone(), PendingUpload::File(file));
+
+                let chat_state = chat_state.clone();
+                let auth_state = auth_state.clone();
+                let pending_files = pending_files.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    if let Err(e) =
+                        handle_attachment_send(&chat_state, &pending_files, msg, receiver, &token)
+                            .await
+                    {
+                        report_send_error(&chat_state, &auth_state, e);
                     }
                 });
             } else {
@@ -171,6 +1035,75 @@ pub fn chat(props: &ChatProps) -> Html {
         })
     };
 
+    let on_failed_action = {
+        let chat_state = chat_state.clone();
+        let auth_state = props.auth_state.clone();
+        let selected_user = props.selected_user.clone();
+        let token = props.auth_state.token.clone();
+        let pending_files = pending_files.clone();
+        let edit_draft = edit_draft.clone();
+
+        Callback::from(move |(message_id, action): (String, FailedAction)| {
+            let Some(msg) = chat_state
+                .current_chat()
+                .and_then(|chat| chat.find(|m| m.message_id == message_id))
+                .cloned()
+            else {
+                return;
+            };
+            match action {
+                FailedAction::Retry => {
+                    let (Some(token), Some(receiver)) = (token.clone(), selected_user.clone())
+                    else {
+                        return;
+                    };
+                    if msg.attachment.is_some() && !pending_files.borrow().contains_key(&message_id)
+                    {
+                        chat_state.dispatch(ChatAction::SetError(
+                            "The file is no longer available. Attach it again.".to_string(),
+                        ));
+                        return;
+                    }
+                    chat_state.dispatch(ChatAction::UpdateMessageStatus(
+                        message_id.clone(),
+                        MessageStatus::Sending,
+                    ));
+                    let chat_state = chat_state.clone();
+                    let auth_state = auth_state.clone();
+                    let pending_files = pending_files.clone();
+                    wasm_bindgen_futures::spawn_local(async move {
+                        let result = if msg.attachment.is_some() {
+                            handle_attachment_send(
+                                &chat_state,
+                                &pending_files,
+                                msg,
+                                receiver,
+                                &token,
+                            )
+                            .await
+                        } else {
+                            handle_message_send(&chat_state, msg, receiver, &token).await
+                        };
+                        if let Err(e) = result {
+                            report_send_error(&chat_state, &auth_state, e);
+                        }
+                    });
+                }
+                FailedAction::Edit => {
+                    chat_state.dispatch(ChatAction::DiscardMessage(message_id));
+                    edit_draft.set(Some(msg));
+                }
+                FailedAction::Discard => {
+                    pending_files.borrow_mut().remove(&message_id);
+                    if let Some(url) = msg.attachment.and_then(|a| a.url) {
+                        let _ = web_sys::Url::revoke_object_url(&url);
+                    }
+                    chat_state.dispatch(ChatAction::DiscardMessage(message_id));
+                }
+            }
+        })
+    };
+
     // Scroll handlers
     let on_scroll = {
         let show_scroll_bottom = show_scroll_bottom.clone();
@@ -192,20 +1125,247 @@ pub fn chat(props: &ChatProps) -> Html {
         }
     });
 
-    // Fetch users effect
+    // Presence: online while active, away when idle or hidden, offline on
+    // unload. A heartbeat keeps lastSeen fresh so crashed clients go stale.
     {
         let chat_state = chat_state.clone();
-        let auth_state = props.auth_state.clone();
         let token = props.auth_state.token.clone();
 
         use_effect_with(token, move |token| {
-            if let Some(token) = token {
+            let mut cleanup: Option<(Vec<EventListener>, Interval)> = None;
+
+            if let Some(token) = token.clone() {
+                let beacon_token = token.clone();
+                let status = Rc::new(Cell::new(UserStatus::Offline));
+                let last_activity = Rc::new(Cell::new(js_sys::Date::now()));
+
+                let send = {
+                    let chat_state = chat_state.clone();
+                    let status = status.clone();
+                    Rc::new(move |next: UserStatus| {
+                        status.set(next);
+                        let chat_state = chat_state.clone();
+                        let token = token.clone();
+                        wasm_bindgen_futures::spawn_local(async move {
+                            match PresenceService::set_status(next, &token).await {
+                                Ok(update) => {
+                                    chat_state.dispatch(ChatAction::SetUserStatus(update))
+                                }
+                                Err(e) => web_sys::console::log_1(
+                                    &format!("Failed to update status: {}", e).into(),
+                                ),
+                            }
+                        });
+                    })
+                };
+
+                let document = web_sys::window().and_then(|w| w.document());
+                let is_hidden = {
+                    let document = document.clone();
+                    move || document.as_ref().is_some_and(|d| d.hidden())
+                };
+
+                send(if is_hidden() {
+                    UserStatus::Away
+                } else {
+                    UserStatus::Online
+                });
+
+                let on_activity = {
+                    let send = send.clone();
+                    let status = status.clone();
+                    let last_activity = last_activity.clone();
+                    let is_hidden = is_hidden.clone();
+                    move |_: &Event| {
+                        last_activity.set(js_sys::Date::now());
+                        if status.get() == UserStatus::Away && !is_hidden() {
+                            send(UserStatus::Online);
+                        }
+                    }
+                };
+
+                let mut listeners = Vec::new();
+                if let Some(window) = web_sys::window() {
+                    for event in ["pointerdown", "pointermove", "keydown", "focus"] {
+                        listeners.push(EventListener::new(&window, event, on_activity.clone()));
+                    }
+                    listeners.push(EventListener::new(&window, "pagehide", move |_| {
+                        PresenceService::send_offline_beacon(&beacon_token)
+                    }));
+                }
+                if let Some(document) = &document {
+                    let send = send.clone();
+                    let last_activity = last_activity.clone();
+                    let is_hidden = is_hidden.clone();
+                    listeners.push(EventListener::new(
+                        document,
+                        "visibilitychange",
+                        move |_| {
+                            if is_hidden() {
+                                send(UserStatus::Away);
+                            } else {
+                                last_activity.set(js_sys::Date::now());
+                                send(UserStatus::Online);
+                            }
+                        },
+                    ));
+                }
+
+                let heartbeat = Interval::new(HEARTBEAT_MS, move || {
+                    let idle = js_sys::Date::now() - last_activity.get() > IDLE_TIMEOUT_MS;
+                    match status.get() {
+                        UserStatus::Online if idle => send(UserStatus::Away),
+                        current => send(current),
+                    }
+                });
+
+                cleanup = Some((listeners, heartbeat));
+            }
+
+            move || drop(cleanup)
+        });
+    }
+
+    // Everyone shown in the sidebar: mention candidates and presence targets
+    let known_users = {
+        let current_user = props.auth_state.user_id.clone().unwrap_or_default();
+        let mut candidates: Vec<String> = chat_state
+            .users
+            .iter()
+            .map(|u| u.username.clone())
+            .chain(
+                chat_state
+                    .conversations
+                    .iter()
+                    .map(|c| c.other_user.username.clone()),
+            )
+            .filter(|u| *u != current_user)
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    };
+
+    // Live status pills for everyone in the sidebar, over one connection
+    {
+        let chat_state = chat_state.clone();
+        let presence_ws = presence_ws.clone();
+        let token = props.auth_state.token.clone();
+
+        use_effect_with((token, known_users.clone()), move |(token, usernames)| {
+            if let Some(token) = token.as_ref().filter(|_| !usernames.is_empty()) {
+                let subscriptions = usernames
+                    .iter()
+                    .map(|username| {
+                        (
+                            ON_USER_STATUS_CHANGE_SUBSCRIPTION.to_string(),
+                            serde_json::json!({ "username": username }),
+                        )
+                    })
+                    .collect();
+                let websocket =
+                    AppSyncWebSocket::with_subscriptions(token, subscriptions, move |payload| {
+                        if let Ok(UserStatusPayload { data }) =
+                            serde_json::from_value::<UserStatusPayload>(payload)
+                        {
+                            if let Some(update) = data.on_user_status_change {
+                                chat_state.dispatch(ChatAction::SetUserStatus(update));
+                            }
+                        }
+                    });
+                presence_ws.set(Some(Rc::new(websocket)));
+            } else {
+                presence_ws.set(None);
+            }
+            || ()
+        });
+    }
+
+    // Track connectivity; fetches re-run when we come back online
+    {
+        let online = online.clone();
+        use_effect_with((), move |_| {
+            let listeners = web_sys::window().map(|window| {
+                let on_online = {
+                    let online = online.clone();
+                    EventListener::new(&window, "online", move |_| online.set(true))
+                };
+                let on_offline = EventListener::new(&window, "offline", move |_| online.set(false));
+                (on_online, on_offline)
+            });
+            move || drop(listeners)
+        });
+    }
+
+    // Show what the last session saw before the network answers
+    {
+        let chat_state = chat_state.clone();
+        let cache_ready = cache_ready.clone();
+        let username = props.auth_state.user_id.clone();
+
+        use_effect_with(username, move |username| {
+            if let Some(username) = username.clone() {
+                wasm_bindgen_futures::spawn_local(async move {
+                    match CacheService::load(&username).await {
+                        Ok(cached) => chat_state.dispatch(ChatAction::HydrateFromCache(cached)),
+                        Err(e) => web_sys::console::log_1(
+                            &format!("Offline cache unavailable: {}", e).into(),
+                        ),
+                    }
+                    *cache_ready.borrow_mut() = true;
+                });
+            }
+            || ()
+        });
+    }
+
+    // Mirror state into the offline cache once it settles
+    {
+        let chat_state = chat_state.clone();
+        let username = props.auth_state.user_id.clone();
+        let cache_ready = cache_ready.clone();
+        let cache_snapshot = cache_snapshot.clone();
+        let cache_timer = cache_timer.clone();
+
+        use_effect(move || {
+            if let (Some(username), true) = (username, *cache_ready.borrow()) {
+                let timer = Timeout::new(CACHE_DEBOUNCE_MS, move || {
+                    wasm_bindgen_futures::spawn_local(async move {
+                        if let Err(e) =
+                            CacheService::save_changes(&username, &cache_snapshot, &chat_state)
+                                .await
+                        {
+                            web_sys::console::log_1(
+                                &format!("Failed to update offline cache: {}", e).into(),
+                            );
+                        }
+                    });
+                });
+                // Replacing the previous timer cancels it
+                *cache_timer.borrow_mut() = Some(timer);
+            }
+        });
+    }
+
+    // Fetch conversations effect; their members are the users we know
+    {
+        let chat_state = chat_state.clone();
+        let auth_state = props.auth_state.clone();
+        let token = props.auth_state.token.clone();
+
+        use_effect_with((token, *online), move |(token, online)| {
+            if let (Some(token), true) = (token, *online) {
                 let chat_state = chat_state.clone();
                 let auth_state = auth_state.clone();
                 let token_clone = token.clone();
 
                 wasm_bindgen_futures::spawn_local(async move {
-                    if let Err(e) = fetch_users(&chat_state, &token_clone).await {
+                    let result = MessageService::list_conversations(&token_clone).await.map(
+                        |conversations| {
+                            chat_state.dispatch(ChatAction::SetConversations(conversations))
+                        },
+                    );
+                    if let Err(e) = result {
                         if e.contains("expired") || e.contains("token") {
                             auth_state.dispatch(AuthAction::Logout);
                         } else {
@@ -218,13 +1378,48 @@ pub fn chat(props: &ChatProps) -> Html {
         });
     }
 
+    let now = js_sys::Date::now();
+    let chat_notifications = chat_state
+        .current_chat_id
+        .as_ref()
+        .map(|chat_id| notification_settings.chat(chat_id));
+    let dnd_enabled = notification_settings
+        .do_not_disturb
+        .as_ref()
+        .is_some_and(|dnd| dnd.enabled);
+
+    let (chat_loading, has_older, loading_older) = chat_state
+        .current_chat()
+        .map_or((false, false, false), |c| {
+            (c.is_loading, c.history_cursor.is_some(), c.loading_history)
+        });
+
     html! {
         <div class="chat-container">
             <ConversationList
                 conversations={chat_state.conversations.clone()}
                 selected_chat_id={chat_state.current_chat_id.clone()}
-                on_select={on_select_conversation}
-                on_search={on_search_users}
+                on_select={
+                    let focus_message_id = focus_message_id.clone();
+                    on_select_conversation.reform(move |username| {
+                        focus_message_id.set(None);
+                        username
+                    })
+                }
+                on_search={on_search}
+                search_results={search_results}
+                on_select_result={on_select_search_result}
+                on_search_users={
+                    let user_query = user_query.clone();
+                    Callback::from(move |query: String| user_query.set(query))
+                }
+                user_directory={(*user_search).as_ref().map(|search| UserDirectory {
+                    users: search.users.clone(),
+                    loading: search.loading,
+                    has_more: search.next_token.is_some(),
+                })}
+                on_more_users={on_more_users}
+                muted_chats={notification_settings.muted_chats(now)}
                 is_loading={chat_state.is_loading}
                 current_user_id={props.auth_state.user_id.clone().unwrap_or_default()}
                 users={chat_state.users.clone()}
@@ -239,32 +1434,175 @@ pub fn chat(props: &ChatProps) -> Html {
                     <h1>{ "Rusty Chat Sync" }</h1>
                     if let Some(username) = &props.selected_user {
                         <h2>{ format!("Chat with {}", username) }</h2>
+                        <select
+                            class="export-select"
+                            title="Export conversation"
+                            disabled={*exporting}
+                            onchange={move |e: Event| {
+                                let select: HtmlSelectElement = e.target_unchecked_into();
+                                if let Some(format) = ExportFormat::from_extension(&select.value()) {
+                                    on_export.emit(format);
+                                }
+                                select.set_value("");
+                            }}
+                        >
+                            <option value="" selected=true>
+                                { if *exporting { "Exporting…" } else { "Export…" } }
+                            </option>
+                            { for ExportFormat::ALL.into_iter().map(|format| html! {
+                                <option value={format.extension()}>{ format.label() }</option>
+                            }) }
+                        </select>
+                        <select
+                            class="mute-select"
+                            title="Notifications for this chat"
+                            onchange={move |e: Event| {
+                                let select: HtmlSelectElement = e.target_unchecked_into();
+                                on_chat_notifications.emit(select.value());
+                            }}
+                        >
+                            { chat_notifications.as_ref().map_or(
+                                html! {},
+                                |setting| notification_menu(setting, now),
+                            ) }
+                        </select>
+                        <button
+                            class="import-button"
+                            title="Import history from Slack or WhatsApp"
+                            disabled={!*online}
+                            onclick={
+                                let show_import = show_import.clone();
+                                let import_progress = import_progress.clone();
+                                move |_| {
+                                    import_progress.set(None);
+                                    show_import.set(true);
+                                }
+                            }
+                        >
+                            { "Import…" }
+                        </button>
                     }
+                    if let Some(text) = typing_text {
+                        <span class="typing-indicator">{ text }</span>
+                    }
+                    if !*online {
+                        <span class="offline-indicator">{ "Offline — showing saved messages" }</span>
+                    }
+                    <div class="dnd-anchor">
+                        <button
+                            class={classes!("dnd-button", dnd_enabled.then_some("active"))}
+                            title="Do not disturb schedule"
+                            onclick={
+                                let show_dnd = show_dnd.clone();
+                                move |_| show_dnd.set(!*show_dnd)
+                            }
+                        >
+                            { "🌙" }
+                        </button>
+                        if *show_dnd {
+                            <DoNotDisturbPanel
+                                value={notification_settings.do_not_disturb.clone()}
+                                on_save={on_save_dnd}
+                                on_close={
+                                    let show_dnd = show_dnd.clone();
+                                    Callback::from(move |_| show_dnd.set(false))
+                                }
+                            />
+                        }
+                    </div>
                     <button
-                        onclick={let cb = props.on_logout.clone(); move |_| cb.emit(())}
+                        onclick={
+                            let cb = props.on_logout.clone();
+                            let token = props.auth_state.token.clone();
+                            let username = props.auth_state.user_id.clone();
+                            let cache_timer = cache_timer.clone();
+                            move |_| {
+                                // The token is gone once we log out, so this can't wait
+                                if let Some(token) = &token {
+                                    PresenceService::send_offline_beacon(token);
+                                    // The next user of this browser shouldn't get our pushes
+                                    let token = token.clone();
+                                    wasm_bindgen_futures::spawn_local(async move {
+                                        let _ = PushService::disable(&token).await;
+                                    });
+                                }
+                                // Saved messages shouldn't outlive the session
+                                cache_timer.borrow_mut().take();
+                                if let Some(username) = username.clone() {
+                                    wasm_bindgen_futures::spawn_local(async move {
+                                        let _ = CacheService::clear(&username).await;
+                                    });
+                                }
+                                cb.emit(())
+                            }
+                        }
                         class="logout-button"
                     >
                         {"Logout"}
                     </button>
                     <ChatStatus
                         is_loading={chat_state.is_loading}
-                        error={chat_state.error.clone()}
+                        error={chat_state.error.clone().or_else(|| {
+                            chat_state.current_chat().and_then(|c| c.error.clone())
+                        })}
                         on_clear_error={
                             let chat_state = chat_state.clone();
                             Callback::from(move |_| chat_state.dispatch(ChatAction::ClearError))
                         }
                     />
                 </div>
+                if *notification_prompt {
+                    <div class="notification-prompt">
+                        <span>{ "Get notified about new messages while this tab is in the background." }</span>
+                        <button onclick={on_enable_notifications}>{ "Enable" }</button>
+                        <button
+                            class="notification-prompt-dismiss"
+                            onclick={
+                                let notification_prompt = notification_prompt.clone();
+                                move |_| {
+                                    NotificationService::dismiss_prompt();
+                                    notification_prompt.set(false);
+                                }
+                            }
+                        >
+                            { "Not now" }
+                        </button>
+                    </div>
+                }
                 <MessageList
-                    messages={chat_state.messages.clone()}
+                    messages={ListMessages(chat_state.current_chat_rc().unwrap_or_default())}
                     current_user_id={props.auth_state.user_id.clone().unwrap_or_default()}
-                    is_loading={chat_state.is_loading}
+                    is_loading={chat_loading && chat_state.current_messages().is_empty()}
                     on_scroll={on_scroll}
                     show_scroll_button={*show_scroll_bottom}
                     on_scroll_to_bottom={scroll_to_bottom}
+                    on_read={on_read}
+                    has_older={has_older}
+                    loading_older={loading_older}
+                    on_load_older={on_load_older}
+                    focus_message_id={(*focus_message_id).clone()}
+                    on_failed_action={on_failed_action}
                 />
+                if let (true, Some(other), Some(username)) =
+                    (*show_import, &props.selected_user, &props.auth_state.user_id)
+                {
+                    <ImportDialog
+                        chat_id={Conversation::chat_id_for(username, other)}
+                        participants={vec![username.clone(), other.clone()]}
+                        progress={(*import_progress).clone()}
+                        on_import={on_import}
+                        on_close={
+                            let show_import = show_import.clone();
+                            Callback::from(move |_| show_import.set(false))
+                        }
+                    />
+                }
                 <MessageInput
                     on_send={on_send}
+                    on_attach={on_attach}
+                    mention_candidates={known_users}
+                    on_typing={on_typing}
+                    edit={(*edit_draft).clone()}
                     disabled={props.selected_user.is_none()}
                 />
             </div>
@@ -272,135 +1610,215 @@ pub fn chat(props: &ChatProps) -> Html {
     }
 }
 
+/// Most search hits shown at once.
+const SEARCH_RESULT_LIMIT: usize = 50;
+const SEARCH_DEBOUNCE_MS: u32 = 300;
+
+/// Options for a chat's notification menu. The selected one describes the
+/// current setting; picking another changes it.
+fn notification_menu(setting: &ChatNotificationSetting, now: f64) -> Html {
+    let muted = setting.is_muted(now);
+    let current = match (muted, setting.mute, setting.muted_until) {
+        (true, Some(MuteKind::Timed), Some(until)) => {
+            let until = chrono::DateTime::from_timestamp_millis(until as i64)
+                .map(|t| t.with_timezone(&chrono::Local).format("%H:%M").to_string())
+                .unwrap_or_default();
+            format!("🔕 Muted until {}", until)
+        }
+        (true, Some(MuteKind::Forever), _) => "🔕 Muted forever".to_string(),
+        (true, _, _) => "🔕 Muted".to_string(),
+        (false, _, _) if setting.mentions_only => "🔔 Mentions only".to_string(),
+        (false, _, _) => "🔔 All messages".to_string(),
+    };
+    html! {
+        <>
+            <option value="" selected=true disabled=true hidden=true>{ current }</option>
+            <option value="all">{ "All messages" }</option>
+            <option value="mentions">{ "Mentions only" }</option>
+            <optgroup label="Mute">
+                { for MuteDuration::ALL.into_iter().map(|d| html! {
+                    <option value={d.key()}>{ d.label() }</option>
+                }) }
+            </optgroup>
+        </>
+    }
+}
+
+/// Looks up the local hits for `query`, adds server hits we don't have, and
+/// groups them by conversation, keeping the newest-first order of each
+/// conversation's best hit.
+fn group_search_results(
+    chat_state: &ChatState,
+    index: &SearchIndex,
+    query: &str,
+    remote: &[Message],
+) -> Vec<SearchResultGroup> {
+    let mut messages: Vec<&Message> = index
+        .search(query, SEARCH_RESULT_LIMIT)
+        .into_iter()
+        .filter_map(|hit| {
+            chat_state
+                .chats
+                .get(&hit.chat_id)
+                .and_then(|chat| chat.find(|m| m.message_id == hit.message_id))
+        })
+        .collect();
+    for msg in remote {
+        if !messages.iter().any(|m| m.message_id == msg.message_id) {
+            messages.push(msg);
+        }
+    }
+    messages.sort_by(|a, b| b.timestamp.total_cmp(&a.timestamp));
+    messages.truncate(SEARCH_RESULT_LIMIT);
+
+    let current_user = chat_state.current_user.clone().unwrap_or_default();
+    let mut groups: Vec<(String, SearchResultGroup)> = Vec::new();
+    for msg in messages {
+        let position = match groups
+            .iter()
+            .position(|(chat_id, _)| *chat_id == msg.chat_id)
+        {
+            Some(position) => position,
+            None => {
+                let username = msg
+                    .chat_id
+                    .trim_start_matches("CHAT#")
+                    .split('#')
+                    .find(|name| *name != current_user)
+                    .unwrap_or(&current_user)
+                    .to_string();
+                groups.push((
+                    msg.chat_id.clone(),
+                    SearchResultGroup {
+                        username,
+                        messages: Vec::new(),
+                    },
+                ));
+                groups.len() - 1
+            }
+        };
+        groups[position].1.messages.push(msg.clone());
+    }
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
 async fn fetch_conversation_messages(
     chat_state: &UseReducerHandle<ChatState>,
+    chat_id: String,
     other_username: String,
     token: &str,
 ) -> Result<(), String> {
-    chat_state.dispatch(ChatAction::SetLoading(true));
+    chat_state.dispatch(ChatAction::SetChatLoading(chat_id.clone(), true));
 
-    let client = GraphQLClient::new()
-        .await
-        .map_err(|e| e.to_string())?
-        .with_token(token.to_string());
+    let (messages, next_token) = MessageService::fetch_page(&other_username, None, token).await?;
 
-    let variables = serde_json::json!({
-        "otherUsername": other_username
-    });
+    let latest_received = messages
+        .iter()
+        .filter(|m| chat_state.current_user.as_ref() != Some(&m.sender))
+        .map(|m| m.timestamp)
+        .reduce(f64::max);
 
-    let response = client
-        .execute_query::<_, GetConversationResponse>(
-            "GetConversation",
-            GET_CONVERSATION_QUERY,
-            variables,
-        )
-        .await
-        .map_err(|e| e.to_string())?;
-
-    if let Some(data) = response.data {
-        let messages: Vec<Message> = data
-            .get_conversation
-            .into_iter()
-            .map(Message::from_message_data)
-            .collect();
+    chat_state.dispatch(ChatAction::SetMessages(
+        chat_id.clone(),
+        messages,
+        next_token,
+    ));
 
-        if let Some(first_msg) = messages.first() {
-            chat_state.dispatch(ChatAction::SetCurrentChatId(Some(
-                first_msg.chat_id.clone(),
-            )));
-        }
+    if let Some(timestamp) = latest_received {
+        MessageService::update_last_seen(chat_id.clone(), Some(timestamp), None, token).await?;
+    }
+    refresh_read_receipts(chat_state, &chat_id, token).await
+}
 
-        chat_state.dispatch(ChatAction::SetMessages(messages));
-    } else if let Some(errors) = response.errors {
-        return Err(errors[0].message.clone());
+/// Receipts that changed while the chat wasn't subscribed to.
+async fn refresh_read_receipts(
+    chat_state: &UseReducerHandle<ChatState>,
+    chat_id: &str,
+    token: &str,
+) -> Result<(), String> {
+    for receipt in MessageService::fetch_read_receipts(chat_id, token).await? {
+        chat_state.dispatch(ChatAction::ApplyReceipt(receipt));
     }
+    Ok(())
+}
 
-    chat_state.dispatch(ChatAction::SetLoading(false));
+async fn fetch_older_messages(
+    chat_state: &UseReducerHandle<ChatState>,
+    chat_id: String,
+    other_username: String,
+    cursor: String,
+    token: &str,
+) -> Result<(), String> {
+    let (messages, next_token) =
+        MessageService::fetch_page(&other_username, Some(cursor), token).await?;
+    chat_state.dispatch(ChatAction::PrependMessages(chat_id, messages, next_token));
     Ok(())
 }
 
+/// Sends `msg` and swaps in the stored message, or marks it failed once the
+/// service gives up.
 async fn handle_message_send(
     chat_state: &UseReducerHandle<ChatState>,
     msg: Message,
     receiver_username: String,
     token: &str,
 ) -> Result<(), String> {
-    let client = GraphQLClient::new()
-        .await
-        .map_err(|e| e.to_string())?
-        .with_token(token.to_string());
-
-    let variables = CreateMessageVariables {
-        content: msg.content.clone(),
-        receiver_username,
-    };
-
-    let response = client
-        .execute_query::<_, CreateMessageResponse>(
-            "CreateMessage",
-            CREATE_MESSAGE_MUTATION,
-            variables,
-        )
-        .await
-        .map_err(|e| e.to_string())?;
-
-    if let Some(data) = response.data {
-        let server_message = Message::from_message_data(data.create_message);
-        chat_state.dispatch(ChatAction::UpdateMessage(msg.message_id, server_message));
-        Ok(())
-    } else if let Some(errors) = response.errors {
-        chat_state.dispatch(ChatAction::UpdateMessageStatus(
-            msg.message_id,
-            MessageStatus::Failed,
-        ));
-        Err(errors[0].message.clone())
-    } else {
-        Err("Unknown error occurred".to_string())
-    }
+    let result = MessageService::send(&msg, &receiver_username, token).await;
+    settle_send(chat_state, msg.message_id, result)
 }
 
-async fn _update_user_status(username: &str, status: &str, token: &str) -> Result<(), String> {
-    let client = GraphQLClient::new()
-        .await
-        .map_err(|e| e.to_string())?
-        .with_token(token.to_string());
-
-    let variables = UpdateUserStatusVariables {
-        username: username.to_string(),
-        status: status.to_string(),
+/// Uploads and sends an attachment message, reporting upload progress on the
+/// local copy.
+async fn handle_attachment_send(
+    chat_state: &UseReducerHandle<ChatState>,
+    pending_files: &RefCell<HashMap<String, PendingUpload>>,
+    msg: Message,
+    receiver_username: String,
+    token: &str,
+) -> Result<(), String> {
+    let message_id = msg.message_id.clone();
+    let on_progress = {
+        let chat_state = chat_state.clone();
+        let message_id = message_id.clone();
+        move |progress| {
+            chat_state.dispatch(ChatAction::SetUploadProgress(message_id.clone(), progress));
+        }
     };
+    let result =
+        UploadService::send_attachment(pending_files, msg, &receiver_username, token, on_progress)
+            .await;
+    settle_send(chat_state, message_id, result)
+}
 
-    // Execute query and only check for errors
-    client
-        .execute_query::<_, serde_json::Value>(
-            // Using serde_json::Value instead of specific response type
-            "UpdateUserStatus",
-            UPDATE_USER_STATUS_MUTATION,
-            variables,
-        )
-        .await
-        .map_err(|e| e.to_string())?;
-
-    Ok(())
+fn settle_send(
+    chat_state: &UseReducerHandle<ChatState>,
+    message_id: String,
+    result: Result<Message, String>,
+) -> Result<(), String> {
+    match result {
+        Ok(server_message) => {
+            chat_state.dispatch(ChatAction::UpdateMessage(message_id, server_message));
+            Ok(())
+        }
+        Err(e) => {
+            chat_state.dispatch(ChatAction::UpdateMessageStatus(
+                message_id,
+                MessageStatus::Failed,
+            ));
+            Err(e)
+        }
+    }
 }
 
-async fn fetch_users(chat_state: &UseReducerHandle<ChatState>, token: &str) -> Result<(), String> {
-    let client = GraphQLClient::new()
-        .await
-        .map_err(|e| e.to_string())?
-        .with_token(token.to_string());
-
-    let response = client
-        .execute_query::<_, ListUsersResponse>("ListUsers", LIST_USERS_QUERY, serde_json::json!({}))
-        .await
-        .map_err(|e| e.to_string())?;
-
-    if let Some(data) = response.data {
-        chat_state.dispatch(ChatAction::SetUsers(data.list_users));
-        Ok(())
-    } else if let Some(errors) = response.errors {
-        Err(errors[0].message.clone())
+/// Logs out when the session has expired, otherwise shows the error.
+fn report_send_error(
+    chat_state: &UseReducerHandle<ChatState>,
+    auth_state: &UseReducerHandle<AuthState>,
+    error: String,
+) {
+    if error.contains("expired") || error.contains("token") {
+        auth_state.dispatch(AuthAction::Logout);
     } else {
-        Ok(())
+        chat_state.dispatch(ChatAction::SetError(error));
     }
 }
@@ -26,4 +26,4 @@ pub fn chat_status(props: &ChatStatusProps) -> Html {
             }
         </>
     }
-} 
\ No newline at end of file
+}
@@ -16,7 +16,7 @@ pub struct ConfirmSignUpProps {
 
 #[function_component(ConfirmSignUp)]
 pub fn confirm_signup(props: &ConfirmSignUpProps) -> Html {
-    let confirmation_code = use_state(|| String::new());
+    let confirmation_code = use_state(String::new);
     let is_loading = use_state(|| false);
     let error = use_state(|| Option::<String>::None);
 
@@ -1,5 +1,7 @@
 use crate::models::conversation::Conversation;
-use crate::models::user::User;
+use crate::models::message::Message;
+use crate::models::user::{User, UserStatus};
+use crate::utils::search;
 use chrono::{Local, TimeZone};
 use web_sys::HtmlInputElement;
 use yew::prelude::*;
@@ -16,41 +18,112 @@ pub struct ConversationListProps {
     #[prop_or_default]
     pub show_mobile: bool,
     pub on_mobile_toggle: Callback<()>,
+    /// Message search hits for the current query, one group per conversation.
+    #[prop_or_default]
+    pub search_results: Vec<SearchResultGroup>,
+    /// Callback with the username and message id of a chosen hit.
+    #[prop_or_default]
+    pub on_select_result: Callback<(String, String)>,
+    /// Emitted with the new chat panel's query.
+    #[prop_or_default]
+    pub on_search_users: Callback<String>,
+    /// Server-side results for that query; `None` filters `users` locally.
+    #[prop_or_default]
+    pub user_directory: Option<UserDirectory>,
+    #[prop_or_default]
+    pub on_more_users: Callback<()>,
+    /// Chats whose notifications are off.
+    #[prop_or_default]
+    pub muted_chats: Vec<String>,
 }
 
+#[derive(Clone, Default, PartialEq)]
+pub struct UserDirectory {
+    pub users: Vec<User>,
+    pub loading: bool,
+    pub has_more: bool,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct SearchResultGroup {
+    pub username: String,
+    pub messages: Vec<Message>,
+}
+
+/// Characters of context shown around a search hit.
+const SNIPPET_CHARS: usize = 80;
+
 #[function_component(ConversationList)]
 pub fn conversation_list(props: &ConversationListProps) -> Html {
-    let search_query = use_state(|| String::new());
+    let search_query = use_state(String::new);
     let show_search = use_state(|| false);
 
-    let filtered_users = {
-        let query = (*search_query).clone().to_lowercase();
-        props
-            .users
-            .iter()
-            .filter(|user| {
-                user.username != props.current_user_id
-                    && (query.is_empty()
-                        || user.username.to_lowercase().contains(&query)
-                        || user.email.to_lowercase().contains(&query))
-            })
-            .cloned()
-            .collect::<Vec<_>>()
+    let (filtered_users, users_loading, has_more_users) = match &props.user_directory {
+        Some(directory) => (
+            directory
+                .users
+                .iter()
+                .filter(|user| user.username != props.current_user_id)
+                .cloned()
+                .collect::<Vec<_>>(),
+            directory.loading && directory.users.is_empty(),
+            directory.has_more,
+        ),
+        None => {
+            let query = (*search_query).clone().to_lowercase();
+            let users = props
+                .users
+                .iter()
+                .filter(|user| {
+                    user.username != props.current_user_id
+                        && (query.is_empty()
+                            || user.username.to_lowercase().contains(&query)
+                            || user.email.to_lowercase().contains(&query))
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            (users, props.is_loading, false)
+        }
     };
 
-    // TODO: Search is not implemented yet.
-    let _on_search_input = {
-        let search_query = search_query.clone();
+    // The query goes to whichever panel is showing
+    let emit_query = {
         let on_search = props.on_search.clone();
+        let on_search_users = props.on_search_users.clone();
+        move |users_panel: bool, query: String| {
+            if users_panel {
+                on_search_users.emit(query);
+            } else {
+                on_search.emit(query);
+            }
+        }
+    };
+
+    let on_search_input = {
+        let search_query = search_query.clone();
+        let show_search = show_search.clone();
+        let emit_query = emit_query.clone();
 
         Callback::from(move |e: InputEvent| {
             let input: HtmlInputElement = e.target_unchecked_into();
             let value = input.value();
             search_query.set(value.clone());
-            on_search.emit(value);
+            emit_query(*show_search, value);
         })
     };
 
+    let on_toggle_panel = {
+        let show_search = show_search.clone();
+        let search_query = search_query.clone();
+        move |_| {
+            let users_panel = !*show_search;
+            show_search.set(users_panel);
+            emit_query(users_panel, (*search_query).clone());
+        }
+    };
+
+    let now = js_sys::Date::now();
+
     let format_last_seen = |timestamp: f64| {
         let now = Local::now();
         let last_seen = Local.timestamp_millis_opt(timestamp as i64).unwrap();
@@ -84,7 +157,7 @@ pub fn conversation_list(props: &ConversationListProps) -> Html {
                     <h2>{"Conversations"}</h2>
                     <button
                         class="new-chat-button"
-                        onclick={let show = show_search.clone(); move |_| show.set(!*show)}
+                        onclick={on_toggle_panel}
                     >
                         if *show_search {
                             {"×"}
@@ -94,9 +167,19 @@ pub fn conversation_list(props: &ConversationListProps) -> Html {
                     </button>
                 </div>
 
+                <div class="search-container">
+                    <input
+                        type="search"
+                        class="search-input"
+                        placeholder={if *show_search { "Search users" } else { "Search messages" }}
+                        value={(*search_query).clone()}
+                        oninput={on_search_input}
+                    />
+                </div>
+
                 <div class="conversations">
                     if *show_search {
-                        if props.is_loading {
+                        if users_loading {
                             <div class="user-loading">
                                 { for (0..3).map(|i| {
                                     html! {
@@ -128,11 +211,7 @@ pub fn conversation_list(props: &ConversationListProps) -> Html {
                                             <div class="conversation-info">
                                                 <div class="conversation-name">
                                                     {&username}
-                                                    if let Some(status) = &user.status {
-                                                        <span class={classes!("user-status", status.to_lowercase())}>
-                                                            {status}
-                                                        </span>
-                                                    }
+                                                    { view_status(user.presence(now)) }
                                                 </div>
                                                 <div class="conversation-preview">
                                                     {&user.email}
@@ -141,8 +220,19 @@ pub fn conversation_list(props: &ConversationListProps) -> Html {
                                         </div>
                                     }
                                 })}
+                                if has_more_users {
+                                    <button
+                                        class="load-more-users"
+                                        disabled={props.user_directory.as_ref().is_some_and(|d| d.loading)}
+                                        onclick={let cb = props.on_more_users.clone(); move |_| cb.emit(())}
+                                    >
+                                        {"More users"}
+                                    </button>
+                                }
                             </div>
                         }
+                    } else if !search_query.trim().is_empty() {
+                        { view_search_results(props, &search_query, format_last_seen) }
                     } else {
                         if props.is_loading {
                             <div class="conversation-loading">
@@ -159,8 +249,7 @@ pub fn conversation_list(props: &ConversationListProps) -> Html {
                         } else {
                             { for props.conversations.iter().map(|conv| {
                                 let is_selected = props.selected_chat_id
-                                    .as_ref()
-                                    .map_or(false, |id| id == &conv.chat_id);
+                                    .as_ref() == Some(&conv.chat_id);
 
                                 let username = conv.other_user.username.clone();
 
@@ -183,10 +272,9 @@ pub fn conversation_list(props: &ConversationListProps) -> Html {
                                         <div class="conversation-info">
                                             <div class="conversation-name">
                                                 {&username}
-                                                if let Some(status) = &conv.other_user.status {
-                                                    <span class={classes!("user-status", status.to_lowercase())}>
-                                                        {status}
-                                                    </span>
+                                                { view_status(conv.other_user.presence(now)) }
+                                                if props.muted_chats.contains(&conv.chat_id) {
+                                                    <span class="muted-icon" title="Muted">{"🔕"}</span>
                                                 }
                                             </div>
                                             if let Some(last_message) = &conv.last_message {
@@ -206,6 +294,11 @@ pub fn conversation_list(props: &ConversationListProps) -> Html {
                                                     {format_last_seen(last_message.timestamp)}
                                                 </div>
                                             }
+                                            if conv.mention_count > 0 {
+                                                <div class="mention-badge" title="Mentions">
+                                                    {format!("@{}", conv.mention_count)}
+                                                </div>
+                                            }
                                             if conv.unread_count > 0 {
                                                 <div class="unread-badge">
                                                     {conv.unread_count}
@@ -222,3 +315,54 @@ pub fn conversation_list(props: &ConversationListProps) -> Html {
         </>
     }
 }
+
+fn view_search_results(
+    props: &ConversationListProps,
+    query: &str,
+    format_time: impl Fn(f64) -> String,
+) -> Html {
+    if props.search_results.is_empty() {
+        return html! { <div class="no-conversations">{"No messages found"}</div> };
+    }
+
+    html! {
+        <div class="search-results">
+            { for props.search_results.iter().map(|group| html! {
+                <div key={group.username.clone()} class="search-result-group">
+                    <div class="search-result-user">{ &group.username }</div>
+                    { for group.messages.iter().map(|msg| {
+                        let onclick = {
+                            let on_select_result = props.on_select_result.clone();
+                            let hit = (group.username.clone(), msg.message_id.clone());
+                            move |_| on_select_result.emit(hit.clone())
+                        };
+                        html! {
+                            <div key={msg.message_id.clone()} class="search-result" {onclick}>
+                                <div class="conversation-preview">
+                                    if msg.sender == props.current_user_id {
+                                        <span class="preview-sender">{"You: "}</span>
+                                    }
+                                    { for search::snippet(&msg.content, query, SNIPPET_CHARS)
+                                        .into_iter()
+                                        .map(|(text, is_match)| if is_match {
+                                            html! { <mark>{ text }</mark> }
+                                        } else {
+                                            html! { { text } }
+                                        }) }
+                                </div>
+                                <div class="conversation-time">{ format_time(msg.timestamp) }</div>
+                            </div>
+                        }
+                    }) }
+                </div>
+            }) }
+        </div>
+    }
+}
+
+fn view_status(status: UserStatus) -> Html {
+    let status = status.to_string();
+    html! {
+        <span class={classes!("user-status", status.clone())}>{ status }</span>
+    }
+}
@@ -0,0 +1,124 @@
+use crate::models::notification_settings::{parse_time, DoNotDisturb};
+use crate::services::notifications::NotificationService;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct DoNotDisturbPanelProps {
+    pub value: Option<DoNotDisturb>,
+    pub on_save: Callback<DoNotDisturb>,
+    pub on_close: Callback<()>,
+}
+
+/// Edits the quiet hours schedule. Nothing is saved until "Save".
+#[function_component(DoNotDisturbPanel)]
+pub fn do_not_disturb_panel(props: &DoNotDisturbPanelProps) -> Html {
+    let draft = {
+        let value = props.value.clone();
+        use_state(move || {
+            value.unwrap_or_else(|| DoNotDisturb {
+                enabled: true,
+                start: "22:00".to_string(),
+                end: "07:00".to_string(),
+                time_zone: NotificationService::browser_time_zone(),
+            })
+        })
+    };
+    let time_zones = use_memo((), |_| supported_time_zones());
+
+    let edit = |apply: fn(&mut DoNotDisturb, &HtmlInputElement)| {
+        let draft = draft.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let mut next = (*draft).clone();
+            apply(&mut next, &input);
+            draft.set(next);
+        })
+    };
+
+    let valid = parse_time(&draft.start).is_some()
+        && parse_time(&draft.end).is_some()
+        && !draft.time_zone.trim().is_empty();
+
+    let on_save = {
+        let draft = draft.clone();
+        let cb = props.on_save.clone();
+        Callback::from(move |_| {
+            cb.emit(DoNotDisturb {
+                time_zone: draft.time_zone.trim().to_string(),
+                ..(*draft).clone()
+            })
+        })
+    };
+
+    html! {
+        <div class="dnd-panel" role="dialog" aria-label="Do not disturb">
+            <label class="dnd-toggle">
+                <input
+                    type="checkbox"
+                    checked={draft.enabled}
+                    onchange={edit(|d, input| d.enabled = input.checked())}
+                />
+                { "Pause notifications on a schedule" }
+            </label>
+            <div class="dnd-times">
+                <label>
+                    { "From" }
+                    <input
+                        type="time"
+                        value={draft.start.clone()}
+                        onchange={edit(|d, input| d.start = input.value())}
+                    />
+                </label>
+                <label>
+                    { "To" }
+                    <input
+                        type="time"
+                        value={draft.end.clone()}
+                        onchange={edit(|d, input| d.end = input.value())}
+                    />
+                </label>
+            </div>
+            <label class="dnd-zone">
+                { "Time zone" }
+                <input
+                    list="dnd-time-zones"
+                    value={draft.time_zone.clone()}
+                    onchange={edit(|d, input| d.time_zone = input.value())}
+                />
+                <datalist id="dnd-time-zones">
+                    { for time_zones.iter().map(|zone| html! { <option value={zone.clone()} /> }) }
+                </datalist>
+            </label>
+            <div class="dnd-actions">
+                <button onclick={let cb = props.on_close.clone(); move |_| cb.emit(())}>
+                    { "Cancel" }
+                </button>
+                <button class="dnd-save" onclick={on_save} disabled={!valid}>
+                    { "Save" }
+                </button>
+            </div>
+        </div>
+    }
+}
+
+/// `Intl.supportedValuesOf("timeZone")`, where the browser has it.
+fn supported_time_zones() -> Vec<String> {
+    let supported = js_sys::Reflect::get(&js_sys::global(), &"Intl".into())
+        .and_then(|intl| js_sys::Reflect::get(&intl, &"supportedValuesOf".into()))
+        .ok()
+        .and_then(|f| f.dyn_into::<js_sys::Function>().ok())
+        .and_then(|f| {
+            f.call1(&wasm_bindgen::JsValue::NULL, &"timeZone".into())
+                .ok()
+        });
+    supported
+        .map(|zones| {
+            js_sys::Array::from(&zones)
+                .iter()
+                .filter_map(|zone| zone.as_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
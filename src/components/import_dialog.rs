@@ -0,0 +1,267 @@
+use crate::models::message::Message;
+use crate::utils::import::{self, ImportFile, ImportSource};
+use chrono::{Local, TimeZone};
+use std::collections::HashMap;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+/// How far an import has got, owned by the parent that runs it.
+#[derive(Clone, PartialEq)]
+pub enum ImportProgress {
+    Running { written: usize, total: usize },
+    Done { written: usize },
+    Failed(String),
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ImportDialogProps {
+    pub chat_id: String,
+    /// The chat's members; foreign authors are mapped onto these.
+    pub participants: Vec<String>,
+    pub progress: Option<ImportProgress>,
+    /// Messages with local senders, ready to write.
+    pub on_import: Callback<Vec<Message>>,
+    pub on_close: Callback<()>,
+}
+
+/// Parses an export, lets the user match its authors to the chat members
+/// and hands the mapped messages to `on_import`.
+#[function_component(ImportDialog)]
+pub fn import_dialog(props: &ImportDialogProps) -> Html {
+    let source = use_state(|| ImportSource::WhatsApp);
+    let parsed = use_state(|| None::<Vec<Message>>);
+    let error = use_state(|| None::<String>);
+    // Author -> local username; authors left out are skipped
+    let mapping = use_state(HashMap::<String, String>::new);
+    let reading = use_state(|| false);
+
+    let on_files = {
+        let source = source.clone();
+        let parsed = parsed.clone();
+        let error = error.clone();
+        let mapping = mapping.clone();
+        let reading = reading.clone();
+        let chat_id = props.chat_id.clone();
+        let participants = props.participants.clone();
+
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let Some(list) = input.files() else {
+                return;
+            };
+            let files: Vec<_> = (0..list.length()).filter_map(|i| list.get(i)).collect();
+            let source = *source;
+            let parsed = parsed.clone();
+            let error = error.clone();
+            let mapping = mapping.clone();
+            let reading = reading.clone();
+            let chat_id = chat_id.clone();
+            let participants = participants.clone();
+            reading.set(true);
+
+            wasm_bindgen_futures::spawn_local(async move {
+                let result = match read_files(files).await {
+                    Ok(files) => import::parse(source, &files, &chat_id),
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(messages) => {
+                        mapping.set(default_mapping(&import::authors(&messages), &participants));
+                        parsed.set(Some(messages));
+                        error.set(None);
+                    }
+                    Err(e) => {
+                        parsed.set(None);
+                        error.set(Some(e));
+                    }
+                }
+                reading.set(false);
+            });
+        })
+    };
+
+    let on_source = {
+        let source = source.clone();
+        let parsed = parsed.clone();
+        let error = error.clone();
+        Callback::from(move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            if let Some(chosen) = ImportSource::ALL
+                .into_iter()
+                .find(|s| s.label() == select.value())
+            {
+                source.set(chosen);
+                parsed.set(None);
+                error.set(None);
+            }
+        })
+    };
+
+    let mapped = parsed
+        .as_ref()
+        .map(|messages| import::apply_mapping(messages, &mapping))
+        .unwrap_or_default();
+    let running = matches!(props.progress, Some(ImportProgress::Running { .. }));
+
+    let on_submit = {
+        let on_import = props.on_import.clone();
+        let mapped = mapped.clone();
+        Callback::from(move |_| on_import.emit(mapped.clone()))
+    };
+
+    html! {
+        <div class="import-overlay">
+            <div class="import-dialog" role="dialog" aria-label="Import chat history">
+                <h3>{ "Import chat history" }</h3>
+                <label class="import-field">
+                    { "Source" }
+                    <select onchange={on_source} disabled={running}>
+                        { for ImportSource::ALL.into_iter().map(|s| html! {
+                            <option value={s.label()} selected={s == *source}>{ s.label() }</option>
+                        }) }
+                    </select>
+                </label>
+                <label class="import-field">
+                    { source.hint() }
+                    <input
+                        type="file"
+                        accept={source.accept()}
+                        multiple={*source == ImportSource::Slack}
+                        onchange={on_files}
+                        disabled={running}
+                    />
+                </label>
+                if *reading {
+                    <p class="import-note">{ "Reading…" }</p>
+                }
+                if let Some(e) = &*error {
+                    <p class="import-error">{ e }</p>
+                }
+                if let Some(messages) = &*parsed {
+                    <p class="import-note">{ summary(messages) }</p>
+                    <table class="import-mapping">
+                        <thead>
+                            <tr><th>{ "Author" }</th><th>{ "Import as" }</th></tr>
+                        </thead>
+                        <tbody>
+                            { for import::authors(messages).into_iter().map(|author| {
+                                let selected = mapping.get(&author).cloned().unwrap_or_default();
+                                let onchange = {
+                                    let mapping = mapping.clone();
+                                    let author = author.clone();
+                                    Callback::from(move |e: Event| {
+                                        let select: HtmlSelectElement = e.target_unchecked_into();
+                                        let mut next = (*mapping).clone();
+                                        match select.value() {
+                                            value if value.is_empty() => next.remove(&author),
+                                            value => next.insert(author.clone(), value),
+                                        };
+                                        mapping.set(next);
+                                    })
+                                };
+                                html! {
+                                    <tr>
+                                        <td>{ &author }</td>
+                                        <td>
+                                            <select {onchange} disabled={running}>
+                                                <option value="" selected={selected.is_empty()}>
+                                                    { "Skip" }
+                                                </option>
+                                                { for props.participants.iter().map(|username| html! {
+                                                    <option
+                                                        value={username.clone()}
+                                                        selected={*username == selected}
+                                                    >
+                                                        { username }
+                                                    </option>
+                                                }) }
+                                            </select>
+                                        </td>
+                                    </tr>
+                                }
+                            }) }
+                        </tbody>
+                    </table>
+                }
+                { match &props.progress {
+                    Some(ImportProgress::Running { written, total }) => html! {
+                        <p class="import-note">
+                            <progress max={total.to_string()} value={written.to_string()} />
+                            { format!(" {} of {}", written, total) }
+                        </p>
+                    },
+                    Some(ImportProgress::Done { written }) => html! {
+                        <p class="import-note">{ format!("Imported {} messages.", written) }</p>
+                    },
+                    Some(ImportProgress::Failed(e)) => html! {
+                        <p class="import-error">{ format!("Import failed: {}", e) }</p>
+                    },
+                    None => html! {},
+                } }
+                <div class="import-actions">
+                    <button
+                        onclick={let cb = props.on_close.clone(); move |_| cb.emit(())}
+                        disabled={running}
+                    >
+                        { "Close" }
+                    </button>
+                    <button
+                        class="import-submit"
+                        onclick={on_submit}
+                        disabled={running || mapped.is_empty()}
+                    >
+                        { format!("Import {} messages", mapped.len()) }
+                    </button>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+async fn read_files(files: Vec<web_sys::File>) -> Result<Vec<ImportFile>, String> {
+    let mut read = Vec::with_capacity(files.len());
+    for file in files {
+        let text = wasm_bindgen_futures::JsFuture::from(file.text())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        read.push(ImportFile {
+            name: file.name(),
+            contents: text.as_string().unwrap_or_default(),
+        });
+    }
+    Ok(read)
+}
+
+/// Authors whose name matches a member's, ignoring case, start out mapped.
+fn default_mapping(authors: &[String], participants: &[String]) -> HashMap<String, String> {
+    authors
+        .iter()
+        .filter_map(|author| {
+            participants
+                .iter()
+                .find(|p| p.eq_ignore_ascii_case(author))
+                .map(|p| (author.clone(), p.clone()))
+        })
+        .collect()
+}
+
+fn summary(messages: &[Message]) -> String {
+    let date = |timestamp: f64| {
+        Local
+            .timestamp_millis_opt(timestamp as i64)
+            .earliest()
+            .map_or_else(
+                || "an unknown date".to_string(),
+                |t| t.format("%B %d, %Y").to_string(),
+            )
+    };
+    match (messages.first(), messages.last()) {
+        (Some(first), Some(last)) => format!(
+            "{} messages from {} to {}",
+            messages.len(),
+            date(first.timestamp),
+            date(last.timestamp)
+        ),
+        _ => "No messages".to_string(),
+    }
+}
@@ -11,8 +11,8 @@ pub struct LoginProps {
 
 #[function_component(Login)]
 pub fn login(props: &LoginProps) -> Html {
-    let username = use_state(|| String::new());
-    let password = use_state(|| String::new());
+    let username = use_state(String::new);
+    let password = use_state(String::new);
     let is_loading = use_state(|| false);
     let username_error = use_state(|| Option::<String>::None);
     let password_error = use_state(|| Option::<String>::None);
@@ -0,0 +1,91 @@
+use crate::utils::highlight::highlight;
+use crate::utils::markdown::{parse_with_mentions, Block, Inline};
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct MarkdownProps {
+    pub content: String,
+    /// Usernames mentioned in the message, highlighted when they appear.
+    #[prop_or_default]
+    pub mentions: Vec<String>,
+    #[prop_or_default]
+    pub current_user: String,
+}
+
+/// Renders message text as a safe Markdown subset. Everything goes through
+/// Yew's text nodes and attributes, so user input is always escaped.
+#[function_component(Markdown)]
+pub fn markdown(props: &MarkdownProps) -> Html {
+    let blocks = use_memo(
+        (props.content.clone(), props.mentions.clone()),
+        |(content, mentions)| parse_with_mentions(content, mentions),
+    );
+    let current_user = props.current_user.as_str();
+
+    html! {
+        <div class="markdown">
+            { for blocks.iter().map(|block| view_block(block, current_user)) }
+        </div>
+    }
+}
+
+fn view_block(block: &Block, current_user: &str) -> Html {
+    let view_inline = |inline| view_inline(inline, current_user);
+    match block {
+        Block::Paragraph(inlines) => html! {
+            <p class="md-paragraph">{ for inlines.iter().map(view_inline) }</p>
+        },
+        Block::CodeBlock { language, code } => {
+            let class = language.as_ref().map(|l| format!("language-{}", l));
+            html! {
+                <pre class="md-code-block">
+                    <code class={class}>
+                        { for highlight(language.as_deref(), code).into_iter().map(|(kind, text)| {
+                            match kind.class() {
+                                Some(class) => html! { <span {class}>{ text }</span> },
+                                None => html! { { text } },
+                            }
+                        })}
+                    </code>
+                </pre>
+            }
+        }
+        Block::List {
+            ordered,
+            start,
+            items,
+        } => {
+            let items = items.iter().map(|item| {
+                html! { <li>{ for item.iter().map(view_inline) }</li> }
+            });
+            if *ordered {
+                html! { <ol class="md-list" start={start.to_string()}>{ for items }</ol> }
+            } else {
+                html! { <ul class="md-list">{ for items }</ul> }
+            }
+        }
+        Block::Quote(blocks) => html! {
+            <blockquote class="md-quote">{ for blocks.iter().map(|block| view_block(block, current_user)) }</blockquote>
+        },
+    }
+}
+
+fn view_inline(inline: &Inline, current_user: &str) -> Html {
+    let view_inline = |inline| view_inline(inline, current_user);
+    match inline {
+        Inline::Text(text) => html! { { text } },
+        Inline::Bold(children) => {
+            html! { <strong>{ for children.iter().map(view_inline) }</strong> }
+        }
+        Inline::Italic(children) => html! { <em>{ for children.iter().map(view_inline) }</em> },
+        Inline::Code(code) => html! { <code class="md-code">{ code }</code> },
+        Inline::Link { href, text } => html! {
+            <a href={href.clone()} target="_blank" rel="noopener noreferrer nofollow">{ text }</a>
+        },
+        Inline::Mention { username, text } => html! {
+            <span class={classes!("mention", (username == current_user).then_some("mention-me"))}>
+                { text }
+            </span>
+        },
+    }
+}
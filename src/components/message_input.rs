@@ -1,40 +1,174 @@
-use crate::models::message::{Message, MessageStatus, MessageType};
+use crate::models::message::{Message, MessageStatus, MessageType, MAX_CONTENT_LENGTH};
+use crate::utils::mentions::{active_query, complete, extract_mentions};
 use uuid::Uuid;
-use web_sys::KeyboardEvent;
+use web_sys::{File, HtmlInputElement, KeyboardEvent};
 use yew::prelude::*;
 
 #[derive(Properties, PartialEq)]
 pub struct MessageInputProps {
     pub on_send: Callback<Message>,
     #[prop_or_default]
+    pub on_attach: Callback<File>,
+    #[prop_or_default]
     pub disabled: bool,
+    /// Usernames offered when typing `@`.
+    #[prop_or_default]
+    pub mention_candidates: Vec<String>,
+    /// `true` while the user is typing (throttled), `false` once they stop.
+    #[prop_or_default]
+    pub on_typing: Callback<bool>,
+    /// A failed message to edit; its text replaces the input's content.
+    #[prop_or_default]
+    pub edit: Option<Message>,
+}
+
+const MAX_SUGGESTIONS: usize = 6;
+/// Minimum gap between "still typing" events. Must stay below the
+/// receiving side's expiry so the indicator doesn't flicker.
+const TYPING_THROTTLE_MS: f64 = 3000.0;
+
+/// The `@` being completed: its char index, the caret, and matching usernames.
+#[derive(Clone, PartialEq)]
+struct MentionQuery {
+    at: usize,
+    caret: usize,
+    suggestions: Vec<String>,
+}
+
+fn mention_query(input: &HtmlInputElement, candidates: &[String]) -> Option<MentionQuery> {
+    let value = input.value();
+    // selectionStart is in UTF-16 units; convert to a char index
+    let caret_utf16 = input.selection_start().ok().flatten()? as usize;
+    let mut units = 0;
+    let caret = value
+        .chars()
+        .take_while(|c| {
+            units += c.len_utf16();
+            units <= caret_utf16
+        })
+        .count();
+
+    let (at, prefix) = active_query(&value, caret)?;
+    let prefix = prefix.to_lowercase();
+    let suggestions: Vec<String> = candidates
+        .iter()
+        .filter(|u| u.to_lowercase().starts_with(&prefix))
+        .take(MAX_SUGGESTIONS)
+        .cloned()
+        .collect();
+
+    (!suggestions.is_empty()).then_some(MentionQuery {
+        at,
+        caret,
+        suggestions,
+    })
 }
 
 #[function_component(MessageInput)]
 pub fn message_input(props: &MessageInputProps) -> Html {
-    let content = use_state(|| String::new());
+    let content = use_state(String::new);
+    let mention = use_state(|| None::<MentionQuery>);
+    let selected_suggestion = use_state(|| 0usize);
+    let text_input_ref = use_node_ref();
+    // When we last told the chat we're typing; None while idle
+    let typing_since = use_mut_ref(|| None::<f64>);
+
+    {
+        let content = content.clone();
+        let text_input_ref = text_input_ref.clone();
+        let edit = props.edit.clone();
+        use_effect_with(
+            props.edit.as_ref().map(|m| m.message_id.clone()),
+            move |_| {
+                if let Some(msg) = edit {
+                    content.set(msg.content);
+                    if let Some(input) = text_input_ref.cast::<HtmlInputElement>() {
+                        let _ = input.focus();
+                    }
+                }
+                || ()
+            },
+        );
+    }
 
     let oninput = {
         let content = content.clone();
+        let mention = mention.clone();
+        let selected_suggestion = selected_suggestion.clone();
+        let candidates = props.mention_candidates.clone();
+        let typing_since = typing_since.clone();
+        let on_typing = props.on_typing.clone();
         Callback::from(move |e: InputEvent| {
             let input: web_sys::HtmlInputElement = e.target_unchecked_into();
-            content.set(input.value());
+            let value = input.value();
+
+            let mut last = typing_since.borrow_mut();
+            if value.is_empty() {
+                if last.take().is_some() {
+                    on_typing.emit(false);
+                }
+            } else {
+                let now = js_sys::Date::now();
+                if last.is_none_or(|t| now - t >= TYPING_THROTTLE_MS) {
+                    *last = Some(now);
+                    on_typing.emit(true);
+                }
+            }
+
+            content.set(value);
+            mention.set(mention_query(&input, &candidates));
+            selected_suggestion.set(0);
+        })
+    };
+
+    let insert_mention = {
+        let content = content.clone();
+        let mention = mention.clone();
+        let text_input_ref = text_input_ref.clone();
+        Callback::from(move |username: String| {
+            let Some(query) = (*mention).clone() else {
+                return;
+            };
+            let (text, caret) = complete(&content, query.at, query.caret, &username);
+            let caret_utf16: usize = text.chars().take(caret).map(char::len_utf16).sum();
+            if let Some(input) = text_input_ref.cast::<HtmlInputElement>() {
+                input.set_value(&text);
+                let _ = input.focus();
+                let _ = input.set_selection_range(caret_utf16 as u32, caret_utf16 as u32);
+            }
+            content.set(text);
+            mention.set(None);
         })
     };
 
     let send_message = {
         let content = content.clone();
+        let mention = mention.clone();
         let on_send = props.on_send.clone();
+        let candidates = props.mention_candidates.clone();
+        let typing_since = typing_since.clone();
+        let on_typing = props.on_typing.clone();
         move || {
             if !content.is_empty() {
+                mention.set(None);
+                if typing_since.borrow_mut().take().is_some() {
+                    on_typing.emit(false);
+                }
+                let message_id = Uuid::new_v4().to_string();
                 let message = Message {
-                    message_id: Uuid::new_v4().to_string(),
+                    client_message_id: Some(message_id.clone()),
+                    message_id,
                     content: (*content).clone(),
                     sender: String::new(),
                     status: MessageStatus::Sending,
                     message_type: MessageType::Text,
                     timestamp: js_sys::Date::now(),
+                    sequence: None,
                     chat_id: String::new(),
+                    attachment: None,
+                    mentions: extract_mentions(&content, &candidates),
+                    imported_by: None,
+                    upload_progress: None,
                 };
                 on_send.emit(message);
                 content.set(String::new());
@@ -42,10 +176,42 @@ pub fn message_input(props: &MessageInputProps) -> Html {
         }
     };
 
+    // Navigation keys don't produce keypress events, so the suggestion list
+    // is driven from keydown. Enter/Tab pick a suggestion instead of sending.
+    let onkeydown = {
+        let mention = mention.clone();
+        let selected_suggestion = selected_suggestion.clone();
+        let insert_mention = insert_mention.clone();
+        Callback::from(move |e: KeyboardEvent| {
+            let Some(query) = (*mention).clone() else {
+                return;
+            };
+            let count = query.suggestions.len();
+            match e.key().as_str() {
+                "ArrowDown" => {
+                    e.prevent_default();
+                    selected_suggestion.set((*selected_suggestion + 1) % count);
+                }
+                "ArrowUp" => {
+                    e.prevent_default();
+                    selected_suggestion.set((*selected_suggestion + count - 1) % count);
+                }
+                "Enter" | "Tab" => {
+                    e.prevent_default();
+                    let index = (*selected_suggestion).min(count - 1);
+                    insert_mention.emit(query.suggestions[index].clone());
+                }
+                "Escape" => mention.set(None),
+                _ => {}
+            }
+        })
+    };
+
     let onkeypress = {
         let send = send_message.clone();
+        let suggesting = mention.is_some();
         Callback::from(move |e: KeyboardEvent| {
-            if e.key() == "Enter" && !e.shift_key() {
+            if e.key() == "Enter" && !e.shift_key() && !suggesting {
                 e.prevent_default();
                 send();
             }
@@ -57,13 +223,89 @@ pub fn message_input(props: &MessageInputProps) -> Html {
         Callback::from(move |_| send())
     };
 
+    let file_input_ref = use_node_ref();
+
+    let open_file_picker = {
+        let file_input_ref = file_input_ref.clone();
+        Callback::from(move |_| {
+            if let Some(input) = file_input_ref.cast::<HtmlInputElement>() {
+                input.click();
+            }
+        })
+    };
+
+    let on_file_change = {
+        let on_attach = props.on_attach.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            if let Some(files) = input.files() {
+                for i in 0..files.length() {
+                    if let Some(file) = files.get(i) {
+                        on_attach.emit(file);
+                    }
+                }
+            }
+            // Allow picking the same file again
+            input.set_value("");
+        })
+    };
+
     html! {
         <div class="message-input-container">
             <input
+                ref={file_input_ref}
+                type="file"
+                class="file-input"
+                multiple=true
+                onchange={on_file_change}
+                disabled={props.disabled}
+            />
+            <button
+                class="attach-button"
+                onclick={open_file_picker}
+                disabled={props.disabled}
+                title="Attach files"
+            >
+                { "📎" }
+            </button>
+            if let Some(query) = &*mention {
+                <ul class="mention-suggestions">
+                    { for query.suggestions.iter().enumerate().map(|(i, username)| {
+                        let onmousedown = {
+                            let insert_mention = insert_mention.clone();
+                            let username = username.clone();
+                            // mousedown fires before the input loses focus
+                            Callback::from(move |e: MouseEvent| {
+                                e.prevent_default();
+                                insert_mention.emit(username.clone());
+                            })
+                        };
+                        html! {
+                            <li
+                                key={username.clone()}
+                                class={classes!(
+                                    "mention-suggestion",
+                                    (i == *selected_suggestion).then_some("selected")
+                                )}
+                                {onmousedown}
+                            >
+                                <span class="mention-suggestion-avatar">
+                                    { username.chars().next().unwrap_or('?').to_uppercase().to_string() }
+                                </span>
+                                { username }
+                            </li>
+                        }
+                    })}
+                </ul>
+            }
+            <input
+                ref={text_input_ref}
                 type="text"
                 class="message-input"
+                maxlength={MAX_CONTENT_LENGTH.to_string()}
                 value={(*content).clone()}
                 {oninput}
+                {onkeydown}
                 {onkeypress}
                 placeholder={
                     if props.disabled {
@@ -1,49 +1,355 @@
+use crate::components::markdown::Markdown;
+use crate::models::attachment::Attachment;
 use crate::models::message::{Message, MessageStatus, MessageType};
-use chrono::{Local, TimeZone};
+use crate::state::message_store::ChatMessages;
+use crate::utils::format::{format_date, format_time, should_show_sender};
+use crate::utils::image_processing::placeholder_data_url;
+use crate::utils::virtual_list::{RowLayout, ESTIMATED_ROW_HEIGHT};
+use gloo::events::EventListener;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::JsCast;
 use web_sys::HtmlElement;
 use yew::prelude::*;
 
+/// What the user chose for one of their messages that failed to send.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailedAction {
+    Retry,
+    /// Put the text back in the input and drop the failed copy.
+    Edit,
+    Discard,
+}
+
+/// A chat's messages as the list receives them. Compared by pointer: the
+/// reducer replaces a chat's `Rc` whenever it changes it, so this avoids
+/// comparing the whole history on every parent render.
+#[derive(Clone, Default)]
+pub struct ListMessages(pub Rc<ChatMessages>);
+
+impl PartialEq for ListMessages {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl std::ops::Deref for ListMessages {
+    type Target = [Message];
+
+    fn deref(&self) -> &[Message] {
+        self.0.messages()
+    }
+}
+
 #[derive(Properties, PartialEq)]
 pub struct MessageListProps {
-    pub messages: Vec<Message>,
+    pub messages: ListMessages,
     pub current_user_id: String,
     pub is_loading: bool,
     pub on_scroll: Callback<(f64, f64, f64)>,
     pub show_scroll_button: bool,
     pub on_scroll_to_bottom: Callback<MouseEvent>,
+    /// Emitted with the timestamp of the newest received message on screen.
+    #[prop_or_default]
+    pub on_read: Callback<f64>,
+    /// Whether older messages can still be fetched.
+    #[prop_or_default]
+    pub has_older: bool,
+    #[prop_or_default]
+    pub loading_older: bool,
+    #[prop_or_default]
+    pub on_load_older: Callback<()>,
+    /// Message to scroll to and highlight, e.g. a search hit.
+    #[prop_or_default]
+    pub focus_message_id: Option<String>,
+    /// Emitted with the message id when the user acts on a failed message.
+    #[prop_or_default]
+    pub on_failed_action: Callback<(String, FailedAction)>,
+}
+
+/// Distance from the top of the list at which the next older page is requested.
+const LOAD_OLDER_THRESHOLD_PX: f64 = 200.0;
+
+/// Finds the newest message from someone else that is at least partly inside
+/// the list's viewport. Hidden tabs never count as having read anything.
+fn newest_visible_received(
+    list: &HtmlElement,
+    messages: &[Message],
+    current_user: &str,
+) -> Option<f64> {
+    let document = web_sys::window()?.document()?;
+    if document.hidden() {
+        return None;
+    }
+    let viewport = list.get_bounding_client_rect();
+
+    messages
+        .iter()
+        .rev()
+        .filter(|m| m.sender != current_user && m.message_type != MessageType::System)
+        .find(|m| {
+            list.query_selector(&format!("[data-message-id=\"{}\"]", m.message_id))
+                .ok()
+                .flatten()
+                .is_some_and(|el| {
+                    let rect = el.get_bounding_client_rect();
+                    rect.bottom() > viewport.top() && rect.top() < viewport.bottom()
+                })
+        })
+        .map(|m| m.timestamp)
+}
+
+/// Extra height rendered above and below the viewport.
+const OVERSCAN_PX: f64 = 600.0;
+
+/// Stable across the optimistic copy being swapped for the stored message.
+fn row_key(message: &Message) -> &str {
+    message
+        .client_message_id
+        .as_deref()
+        .unwrap_or(&message.message_id)
+}
+
+/// Row keys and positions for the messages last rendered. Rebuilt only when
+/// the messages change, and then only extended when they were appended to;
+/// measurements update single rows.
+#[derive(Default)]
+struct Rows {
+    source: Option<Rc<ChatMessages>>,
+    keys: Vec<String>,
+    index_of: HashMap<String, usize>,
+    layout: RowLayout,
+}
+
+impl Rows {
+    fn sync(&mut self, messages: &Rc<ChatMessages>, heights: &HashMap<String, f64>) {
+        if self
+            .source
+            .as_ref()
+            .is_some_and(|s| Rc::ptr_eq(s, messages))
+        {
+            return;
+        }
+        let height = |key: &str| heights.get(key).copied().unwrap_or(ESTIMATED_ROW_HEIGHT);
+        let messages_slice = messages.messages();
+        let appended = self.keys.len() <= messages_slice.len()
+            && self
+                .keys
+                .iter()
+                .zip(messages_slice)
+                .all(|(key, msg)| key == row_key(msg));
+        if !appended {
+            self.keys.clear();
+            self.index_of.clear();
+            self.layout = RowLayout::default();
+        }
+        for msg in &messages_slice[self.keys.len()..] {
+            let key = row_key(msg).to_string();
+            self.layout.push(height(&key));
+            self.index_of.insert(key.clone(), self.keys.len());
+            self.keys.push(key);
+        }
+        self.source = Some(messages.clone());
+    }
+
+    fn set_height(&mut self, key: &str, height: f64) {
+        if let Some(&index) = self.index_of.get(key) {
+            self.layout.set_height(index, height);
+        }
+    }
+}
+
+/// Top of the rows container within the list's scrollable content.
+fn rows_top(list: &HtmlElement, rows: &HtmlElement) -> f64 {
+    rows.get_bounding_client_rect().top() - list.get_bounding_client_rect().top()
+        + list.scroll_top() as f64
 }
 
 #[function_component(MessageList)]
 pub fn message_list(props: &MessageListProps) -> Html {
     let list_ref = use_node_ref();
+    let rows_ref = use_node_ref();
     let new_messages = use_state(|| 0);
     let auto_scroll = use_state(|| true);
+    let scroll_top = use_state_eq(|| 0.0);
+    let viewport_height = use_state_eq(|| 800.0);
+    // Measured row heights by row key; bumping `layout_version` re-renders
+    // after new measurements
+    let heights = use_mut_ref(HashMap::<String, f64>::new);
+    let layout_version = use_state_eq(|| 0u32);
+    // Row under the top of the viewport and how far into it we're scrolled
+    let anchor = use_mut_ref(|| None::<(String, f64)>);
+    let older_requested = use_mut_ref(|| false);
+    // Focus target we've already scrolled to, so later renders don't pull
+    // the list back to it
+    let focused = use_mut_ref(|| None::<String>);
+    let row_cache = use_mut_ref(Rows::default);
+    row_cache
+        .borrow_mut()
+        .sync(&props.messages.0, &heights.borrow());
 
-    // Scroll handling
+    let window = row_cache
+        .borrow()
+        .layout
+        .window(*scroll_top, *viewport_height, OVERSCAN_PX);
+    let pending_focus = props
+        .focus_message_id
+        .as_ref()
+        .filter(|id| focused.borrow().as_ref() != Some(*id))
+        .and_then(|id| props.messages.iter().position(|m| m.message_id == *id));
+
+    // Keyed on the newest message so older pages being prepended don't count
+    // as new messages
     {
-        let list_ref = list_ref.clone();
         let auto_scroll = auto_scroll.clone();
-        let messages_len = props.messages.len();
+        let newest = row_cache.borrow().keys.last().cloned();
         let new_messages = new_messages.clone();
 
-        use_effect_with(messages_len, move |_| {
-            if let Some(list) = list_ref.cast::<HtmlElement>() {
-                if *auto_scroll {
-                    list.scroll_to_with_x_and_y(0.0, list.scroll_height() as f64);
-                } else {
-                    new_messages.set(*new_messages + 1);
+        use_effect_with(newest, move |_| {
+            if !*auto_scroll {
+                new_messages.set(*new_messages + 1);
+            }
+            || ()
+        });
+    }
+
+    // After every render: measure the rendered rows, then either stick to the
+    // bottom or keep the anchored row where it was (prepends, late images and
+    // corrected estimates all shift the content above it)
+    {
+        let list_ref = list_ref.clone();
+        let rows_ref = rows_ref.clone();
+        let heights = heights.clone();
+        let layout_version = layout_version.clone();
+        let viewport_height = viewport_height.clone();
+        let auto_scroll_state = auto_scroll.clone();
+        let auto_scroll = *auto_scroll;
+        let anchor = anchor.clone();
+        let row_cache = row_cache.clone();
+        let focused = focused.clone();
+        let focus_message_id = props.focus_message_id.clone();
+
+        use_effect(move || {
+            let (Some(list), Some(rows)) = (
+                list_ref.cast::<HtmlElement>(),
+                rows_ref.cast::<HtmlElement>(),
+            ) else {
+                return;
+            };
+            viewport_height.set(list.client_height() as f64);
+
+            let mut changed = false;
+            let mut cache = row_cache.borrow_mut();
+            if let Ok(rendered) = rows.query_selector_all("[data-row-key]") {
+                let mut heights = heights.borrow_mut();
+                for i in 0..rendered.length() {
+                    let Some(row) = rendered
+                        .item(i)
+                        .and_then(|node| node.dyn_into::<HtmlElement>().ok())
+                    else {
+                        continue;
+                    };
+                    let Some(key) = row.get_attribute("data-row-key") else {
+                        continue;
+                    };
+                    let height = row.get_bounding_client_rect().height();
+                    if heights
+                        .get(&key)
+                        .is_none_or(|old| (old - height).abs() > 0.5)
+                    {
+                        cache.set_height(&key, height);
+                        heights.insert(key, height);
+                        changed = true;
+                    }
                 }
             }
+
+            // Park the focused row a third of the way down the viewport
+            if let Some(index) = pending_focus {
+                *focused.borrow_mut() = focus_message_id;
+                *anchor.borrow_mut() = Some((
+                    cache.keys[index].clone(),
+                    -(list.client_height() as f64) / 3.0,
+                ));
+                auto_scroll_state.set(false);
+            } else if focus_message_id.is_none() {
+                *focused.borrow_mut() = None;
+            }
+
+            if auto_scroll && pending_focus.is_none() {
+                list.set_scroll_top(list.scroll_height());
+            } else if let Some((key, delta)) = anchor.borrow().clone() {
+                if let Some(&index) = cache.index_of.get(&key) {
+                    let desired = rows_top(&list, &rows) + cache.layout.offset(index) + delta;
+                    if (list.scroll_top() as f64 - desired).abs() > 1.0 {
+                        list.set_scroll_top(desired.round() as i32);
+                    }
+                }
+            }
+
+            if changed {
+                layout_version.set(*layout_version + 1);
+            }
+        });
+    }
+
+    {
+        let older_requested = older_requested.clone();
+        use_effect_with(props.loading_older, move |loading| {
+            if !*loading {
+                *older_requested.borrow_mut() = false;
+            }
             || ()
         });
     }
 
+    let report_read = {
+        let list_ref = list_ref.clone();
+        let messages = props.messages[window.clone()].to_vec();
+        let current_user = props.current_user_id.clone();
+        let on_read = props.on_read.clone();
+        Rc::new(move || {
+            if let Some(list) = list_ref.cast::<HtmlElement>() {
+                if let Some(timestamp) = newest_visible_received(&list, &messages, &current_user) {
+                    on_read.emit(timestamp);
+                }
+            }
+        })
+    };
+
+    // New messages may already be on screen; tabs may come back into view
+    {
+        let report_read = report_read.clone();
+        let newest = (
+            props.messages.len(),
+            props.messages.last().map(|m| m.message_id.clone()),
+        );
+        use_effect_with(newest, move |_| {
+            report_read();
+            let listener = web_sys::window()
+                .and_then(|w| w.document())
+                .map(|document| {
+                    EventListener::new(&document, "visibilitychange", move |_| report_read())
+                });
+            move || drop(listener)
+        });
+    }
+
     // Scroll event listener
     let onscroll = {
+        let report_read = report_read.clone();
         let list_ref = list_ref.clone();
+        let rows_ref = rows_ref.clone();
         let auto_scroll = auto_scroll.clone();
         let on_scroll = props.on_scroll.clone();
         let new_messages = new_messages.clone();
+        let scroll_top_state = scroll_top.clone();
+        let viewport_height = viewport_height.clone();
+        let anchor = anchor.clone();
+        let row_cache = row_cache.clone();
+        let older_requested = older_requested.clone();
+        let can_load_older = props.has_older && !props.loading_older;
+        let on_load_older = props.on_load_older.clone();
 
         Callback::from(move |_| {
             if let Some(list) = list_ref.cast::<HtmlElement>() {
@@ -59,41 +365,35 @@ pub fn message_list(props: &MessageListProps) -> Html {
                     new_messages.set(0);
                 }
 
+                let content_top = rows_ref
+                    .cast::<HtmlElement>()
+                    .map_or(0.0, |rows| rows_top(&list, &rows));
+                let y = (scroll_top - content_top).max(0.0);
+                let cache = row_cache.borrow();
+                *anchor.borrow_mut() = (!cache.layout.is_empty()).then(|| {
+                    let index = cache.layout.index_at(y);
+                    (cache.keys[index].clone(), y - cache.layout.offset(index))
+                });
+                scroll_top_state.set(y);
+                viewport_height.set(client_height);
+
+                if can_load_older
+                    && scroll_top < LOAD_OLDER_THRESHOLD_PX
+                    && !*older_requested.borrow()
+                {
+                    *older_requested.borrow_mut() = true;
+                    on_load_older.emit(());
+                }
+
                 auto_scroll.set(at_bottom);
                 on_scroll.emit((scroll_top, scroll_height, client_height));
+                report_read();
             }
         })
     };
 
-    // Formatting functions
-    fn format_date(timestamp: f64) -> String {
-        let now = Local::now();
-        let message_date = Local.timestamp_millis_opt(timestamp as i64).unwrap();
-
-        if message_date.date_naive() == now.date_naive() {
-            "Today".to_string()
-        } else if message_date.date_naive() == now.date_naive().pred_opt().unwrap() {
-            "Yesterday".to_string()
-        } else {
-            message_date.format("%B %d, %Y").to_string()
-        }
-    }
-
-    fn format_time(timestamp: f64) -> String {
-        let datetime = Local.timestamp_millis_opt(timestamp as i64).unwrap();
-        datetime.format("%I:%M %p").to_string()
-    }
-
-    fn should_show_sender(messages: &[Message], index: usize) -> bool {
-        if index == 0 {
-            return true;
-        }
-        let current = &messages[index];
-        let previous = &messages[index - 1];
-
-        current.sender != previous.sender || (current.timestamp - previous.timestamp) > 300000.0
-        // 5 minutes gap
-    }
+    let cache = row_cache.borrow();
+    let (keys, layout) = (&cache.keys, &cache.layout);
 
     html! {
         <div class="message-list-container">
@@ -116,67 +416,44 @@ pub fn message_list(props: &MessageListProps) -> Html {
                     } else {
                         html! {
                             <div class="message-groups">
-                                {
-                                   {
-                                    let mut current_date = String::new();
-                                    props.messages.iter().enumerate().map(|(index, msg)| {
-                                        let mut elements = Vec::new();
-                                        let msg_date = format_date(msg.timestamp);
-
-                                        if msg_date != current_date {
-                                            current_date = msg_date.clone();
-                                            elements.push(html! {
-                                                <div class="date-separator">
-                                                    <span class="date-text">{ msg_date }</span>
-                                                </div>
+                                if props.loading_older {
+                                    <div class="message-history-loading">{ "Loading earlier messages…" }</div>
+                                } else if !props.has_older && !props.messages.is_empty() {
+                                    <div class="message-history-start">{ "Beginning of conversation" }</div>
+                                }
+                                <div ref={rows_ref.clone()} class="message-rows">
+                                    <div style={format!("height: {}px", layout.offset(window.start))} />
+                                    { for window.clone().map(|index| {
+                                        let msg = &props.messages[index];
+                                        let date = format_date(msg.timestamp);
+                                        let new_date = index == 0
+                                            || format_date(props.messages[index - 1].timestamp) != date;
+                                        // Only failed rows get a callback, so the rest stay memoized
+                                        let on_failed_action = (msg.status == MessageStatus::Failed
+                                            && msg.sender == props.current_user_id)
+                                            .then(|| {
+                                                let cb = props.on_failed_action.clone();
+                                                let id = msg.message_id.clone();
+                                                cb.reform(move |action| (id.clone(), action))
                                             });
+                                        html! {
+                                            <MessageRow
+                                                key={keys[index].clone()}
+                                                row_key={keys[index].clone()}
+                                                message={msg.clone()}
+                                                current_user={props.current_user_id.clone()}
+                                                show_sender={should_show_sender(&props.messages, index)}
+                                                date_label={new_date.then_some(date)}
+                                                focused={props.focus_message_id.as_ref() == Some(&msg.message_id)}
+                                                {on_failed_action}
+                                            />
                                         }
-
-                                        let show_sender = should_show_sender(&props.messages, index);
-                                        let message_class = classes!(
-                                            "message-item",
-                                            match msg.message_type {
-                                                MessageType::System => "system",
-                                                MessageType::Text => {
-                                                    if msg.sender == props.current_user_id {
-                                                        "sent"
-                                                    } else {
-                                                        "received"
-                                                    }
-                                                },
-                                                MessageType::Error => "error",
-                                            }
-                                        );
-
-                                        elements.push(html! {
-                                            <div class="message-wrapper" key={msg.message_id.clone()}>
-                                                if show_sender && msg.message_type != MessageType::System {
-                                                    <div class="message-sender">
-                                                        { &msg.sender }
-                                                    </div>
-                                                }
-                                                <div class={message_class}>
-                                                    <div class="message-content">
-                                                        { &msg.content }
-                                                    </div>
-                                                    <div class="message-meta">
-                                                        <span class="message-time">
-                                                            { format_time(msg.timestamp) }
-                                                        </span>
-                                                        if msg.message_type == MessageType::Text {
-                                                            <span class={classes!("message-status", msg.status.to_string().to_lowercase())}>
-                                                                { get_status_icon(&msg.status) }
-                                                            </span>
-                                                        }
-                                                    </div>
-                                                </div>
-                                            </div>
-                                        });
-
-                                        elements
-                                    }).collect::<Vec<_>>().into_iter().flatten().collect::<Html>()
-                                    }
-                                }
+                                    })}
+                                    <div style={format!(
+                                        "height: {}px",
+                                        layout.total_height() - layout.offset(window.end)
+                                    )} />
+                                </div>
                             </div>
                         }
                     }
@@ -210,10 +487,217 @@ pub fn message_list(props: &MessageListProps) -> Html {
     }
 }
 
+#[derive(Properties, PartialEq)]
+struct MessageRowProps {
+    row_key: String,
+    message: Message,
+    current_user: String,
+    show_sender: bool,
+    date_label: Option<String>,
+    focused: bool,
+    on_failed_action: Option<Callback<FailedAction>>,
+}
+
+/// One measured row: an optional date separator and the message. Rows only
+/// re-render when their own props change.
+#[function_component(MessageRow)]
+fn message_row(props: &MessageRowProps) -> Html {
+    let msg = &props.message;
+    let actions_open = use_state(|| false);
+    let message_class = classes!(
+        "message-item",
+        match msg.message_type {
+            MessageType::System => "system",
+            MessageType::Text | MessageType::Image | MessageType::File => {
+                if msg.sender == props.current_user {
+                    "sent"
+                } else {
+                    "received"
+                }
+            }
+            MessageType::Error => "error",
+        },
+        (msg.sender != props.current_user && msg.mentions_user(&props.current_user))
+            .then_some("mentions-me"),
+        msg.imported_by.is_some().then_some("imported")
+    );
+
+    html! {
+        <div
+            class={classes!("message-row", props.focused.then_some("focused"))}
+            data-row-key={props.row_key.clone()}
+        >
+            if let Some(date) = &props.date_label {
+                <div class="date-separator">
+                    <span class="date-text">{ date }</span>
+                </div>
+            }
+            <div class="message-wrapper" data-message-id={msg.message_id.clone()}>
+                if props.show_sender && msg.message_type != MessageType::System {
+                    <div class="message-sender">
+                        { &msg.sender }
+                    </div>
+                }
+                <div class={message_class}>
+                    if let Some(attachment) = &msg.attachment {
+                        { view_attachment(attachment, msg.upload_progress) }
+                    } else if msg.message_type == MessageType::Text {
+                        <div class="message-content">
+                            <Markdown
+                                content={msg.content.clone()}
+                                mentions={msg.mentions.iter().map(|m| m.username.clone()).collect::<Vec<_>>()}
+                                current_user={props.current_user.clone()}
+                            />
+                        </div>
+                    } else {
+                        <div class="message-content">
+                            { &msg.content }
+                        </div>
+                    }
+                    <div class="message-meta">
+                        // Anyone in the chat can import, so say who vouched for it
+                        if let Some(importer) = &msg.imported_by {
+                            <span
+                                class="message-imported"
+                                title={format!("Imported from an export by {}", importer)}
+                            >
+                                { "Imported" }
+                            </span>
+                        }
+                        <span class="message-time">
+                            { format_time(msg.timestamp) }
+                        </span>
+                        if let Some(on_action) = &props.on_failed_action {
+                            <button
+                                class="message-status failed"
+                                title="Not sent"
+                                aria-expanded={actions_open.to_string()}
+                                onclick={let open = actions_open.clone(); move |_| open.set(!*open)}
+                            >
+                                { get_status_icon(&msg.status) }
+                            </button>
+                            if *actions_open {
+                                { view_failed_actions(msg, on_action, &actions_open) }
+                            }
+                        } else if matches!(msg.message_type, MessageType::Text | MessageType::Image | MessageType::File) {
+                            <span class={classes!("message-status", msg.status.to_string().to_lowercase())}>
+                                { get_status_icon(&msg.status) }
+                            </span>
+                        }
+                    </div>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+fn view_failed_actions(
+    msg: &Message,
+    on_action: &Callback<FailedAction>,
+    open: &UseStateHandle<bool>,
+) -> Html {
+    let item = |action: FailedAction, label: &'static str| {
+        let on_action = on_action.clone();
+        let open = open.clone();
+        html! {
+            <button
+                role="menuitem"
+                onclick={move |_| {
+                    open.set(false);
+                    on_action.emit(action);
+                }}
+            >
+                { label }
+            </button>
+        }
+    };
+
+    html! {
+        <div class="failed-actions" role="menu">
+            { item(FailedAction::Retry, "Retry") }
+            // Attachments can't be edited, only sent again
+            if msg.message_type == MessageType::Text {
+                { item(FailedAction::Edit, "Edit and resend") }
+            }
+            { item(FailedAction::Discard, "Discard") }
+        </div>
+    }
+}
+
 fn get_status_icon(status: &MessageStatus) -> &'static str {
     match status {
         MessageStatus::Sending => "⋯",
         MessageStatus::Sent => "✓",
+        MessageStatus::Delivered => "✓✓",
+        MessageStatus::Read => "✓✓",
         MessageStatus::Failed => "!",
     }
 }
+
+fn view_attachment(attachment: &Attachment, upload_progress: Option<f64>) -> Html {
+    let progress = upload_progress.map(|progress| {
+        html! {
+            <div class="upload-progress">
+                <div
+                    class="upload-progress-bar"
+                    style={format!("width: {:.0}%", progress * 100.0)}
+                />
+            </div>
+        }
+    });
+
+    let placeholder_style = match (&attachment.placeholder, attachment.width, attachment.height) {
+        (Some(hash), Some(width), Some(height)) => {
+            placeholder_data_url(hash, width, height).map(|data_url| {
+                format!(
+                    "background-image: url({}); aspect-ratio: {} / {};",
+                    data_url, width, height
+                )
+            })
+        }
+        _ => None,
+    };
+
+    let body = match (&attachment.url, attachment.is_image()) {
+        (Some(url), true) => html! {
+            <a class="message-image-link" href={url.clone()} target="_blank" rel="noopener">
+                <img
+                    class="message-image"
+                    style={placeholder_style}
+                    src={url.clone()}
+                    alt={attachment.file_name.clone()}
+                    width={attachment.width.map(|w| w.to_string())}
+                    height={attachment.height.map(|h| h.to_string())}
+                    loading="lazy"
+                />
+            </a>
+        },
+        (None, true) if placeholder_style.is_some() => html! {
+            <div
+                class="message-image message-image-placeholder"
+                style={placeholder_style}
+                title={attachment.file_name.clone()}
+            />
+        },
+        (url, _) => html! {
+            <a
+                class="message-file"
+                href={url.clone()}
+                download={attachment.file_name.clone()}
+                target="_blank"
+                rel="noopener"
+            >
+                <span class="message-file-icon">{ "📎" }</span>
+                <span class="message-file-name">{ &attachment.file_name }</span>
+                <span class="message-file-size">{ attachment.display_size() }</span>
+            </a>
+        },
+    };
+
+    html! {
+        <div class="message-attachment">
+            { body }
+            { for progress }
+        </div>
+    }
+}
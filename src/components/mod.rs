@@ -3,7 +3,10 @@ pub mod chat;
 pub mod chat_status;
 pub mod confirm_signup;
 pub mod conversation_list;
+pub mod do_not_disturb;
+pub mod import_dialog;
 pub mod login;
+pub mod markdown;
 pub mod message_input;
 pub mod message_list;
 pub mod signup;
@@ -12,10 +12,10 @@ pub struct SignUpProps {
 
 #[function_component(SignUp)]
 pub fn sign_up(props: &SignUpProps) -> Html {
-    let username = use_state(|| String::new());
-    let email = use_state(|| String::new());
-    let password = use_state(|| String::new());
-    let confirm_password = use_state(|| String::new());
+    let username = use_state(String::new);
+    let email = use_state(String::new);
+    let password = use_state(String::new);
+    let confirm_password = use_state(String::new);
     let is_loading = use_state(|| false);
 
     // Validation states
@@ -1,4 +1,4 @@
 pub mod mutations;
 pub mod queries;
 pub mod subscriptions;
-pub mod types;
\ No newline at end of file
+pub mod types;
@@ -1,23 +1,70 @@
-use crate::graphql::types::MessageData;
+use crate::graphql::types::{ImportResult, MessageData, UploadTarget, UserStatusData};
+use crate::models::attachment::Attachment;
+use crate::models::mention::Mention;
+use crate::models::message::MessageType;
+use crate::models::receipt::ReadReceipt;
 use crate::models::user::User;
 use serde::{Deserialize, Serialize};
 
 pub const CREATE_MESSAGE_MUTATION: &str = r#"
-    mutation CreateMessage($content: String!, $receiverUsername: String!) {
-        createMessage(content: $content, receiverUsername: $receiverUsername) {
+    mutation CreateMessage(
+        $content: String!
+        $receiverUsername: String!
+        $messageType: MessageType
+        $attachment: AttachmentInput
+        $mentions: [MentionInput!]
+        $clientMessageId: ID
+    ) {
+        createMessage(
+            content: $content
+            receiverUsername: $receiverUsername
+            messageType: $messageType
+            attachment: $attachment
+            mentions: $mentions
+            clientMessageId: $clientMessageId
+        ) {
             messageId
+            clientMessageId
             content
             sender
+            receiver
             timestamp
+            sequence
             chatId
             status
+            messageType
+            attachment {
+                key
+                fileName
+                mimeType
+                size
+                width
+                height
+                placeholder
+                url
+            }
+            mentions {
+                username
+                offset
+                length
+            }
+        }
+    }
+"#;
+
+pub const CREATE_UPLOAD_URL_MUTATION: &str = r#"
+    mutation CreateUploadUrl($fileName: String!, $mimeType: String!, $size: Int!) {
+        createUploadUrl(fileName: $fileName, mimeType: $mimeType, size: $size) {
+            key
+            uploadUrl
+            expiresAt
         }
     }
 "#;
 
 pub const UPDATE_USER_STATUS_MUTATION: &str = r#"
-    mutation UpdateUserStatus($username: String!, $status: String!) {
-        updateUserStatus(username: $username, status: $status) {
+    mutation UpdateUserStatus($status: String!) {
+        updateUserStatus(status: $status) {
             username
             status
             lastSeen
@@ -25,6 +72,106 @@ pub const UPDATE_USER_STATUS_MUTATION: &str = r#"
     }
 "#;
 
+pub const UPDATE_LAST_SEEN_MUTATION: &str = r#"
+    mutation UpdateLastSeen($chatId: String!, $deliveredUpTo: Float, $readUpTo: Float) {
+        updateLastSeen(chatId: $chatId, deliveredUpTo: $deliveredUpTo, readUpTo: $readUpTo) {
+            chatId
+            username
+            deliveredUpTo
+            readUpTo
+        }
+    }
+"#;
+
+pub const SEND_TYPING_MUTATION: &str = r#"
+    mutation SendTyping($chatId: String!, $isTyping: Boolean!) {
+        sendTyping(chatId: $chatId, isTyping: $isTyping) {
+            chatId
+            username
+            isTyping
+            timestamp
+        }
+    }
+"#;
+
+pub const MARK_CONVERSATION_READ_MUTATION: &str = r#"
+    mutation MarkConversationRead($chatId: String!) {
+        markConversationRead(chatId: $chatId) {
+            chatId
+            unreadCount
+            mentionCount
+        }
+    }
+"#;
+
+pub const IMPORT_MESSAGES_MUTATION: &str = r#"
+    mutation ImportMessages($chatId: String!, $messages: [ImportMessageInput!]!) {
+        importMessages(chatId: $chatId, messages: $messages) {
+            imported
+            unprocessed
+        }
+    }
+"#;
+
+pub const REGISTER_PUSH_SUBSCRIPTION_MUTATION: &str = r#"
+    mutation RegisterPushSubscription(
+        $deviceId: ID!
+        $endpoint: String!
+        $p256dh: String!
+        $auth: String!
+    ) {
+        registerPushSubscription(
+            deviceId: $deviceId
+            endpoint: $endpoint
+            p256dh: $p256dh
+            auth: $auth
+        ) {
+            deviceId
+        }
+    }
+"#;
+
+pub const UNREGISTER_PUSH_SUBSCRIPTION_MUTATION: &str = r#"
+    mutation UnregisterPushSubscription($deviceId: ID!) {
+        unregisterPushSubscription(deviceId: $deviceId) {
+            deviceId
+        }
+    }
+"#;
+
+/// Variables are a `ChatNotificationSetting`.
+pub const SET_CHAT_NOTIFICATION_SETTING_MUTATION: &str = r#"
+    mutation SetChatNotificationSetting(
+        $chatId: String!
+        $mentionsOnly: Boolean!
+        $mute: MuteKind
+        $mutedUntil: Float
+    ) {
+        setChatNotificationSetting(
+            chatId: $chatId
+            mentionsOnly: $mentionsOnly
+            mute: $mute
+            mutedUntil: $mutedUntil
+        ) {
+            chatId
+        }
+    }
+"#;
+
+/// Variables are a `DoNotDisturb`.
+pub const SET_DO_NOT_DISTURB_MUTATION: &str = r#"
+    mutation SetDoNotDisturb(
+        $enabled: Boolean!
+        $start: String!
+        $end: String!
+        $timeZone: String!
+    ) {
+        setDoNotDisturb(enabled: $enabled, start: $start, end: $end, timeZone: $timeZone) {
+            enabled
+        }
+    }
+"#;
+
 pub const CREATE_USER_MUTATION: &str = r#"
     mutation CreateUser($username: String!, $email: String!) {
         createUser(username: $username, email: $email) {
@@ -42,30 +189,103 @@ pub struct CreateMessageVariables {
     pub content: String,
     #[serde(rename = "receiverUsername")]
     pub receiver_username: String,
+    #[serde(rename = "messageType", skip_serializing_if = "Option::is_none")]
+    pub message_type: Option<MessageType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<Attachment>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mentions: Vec<Mention>,
+    #[serde(rename = "clientMessageId", skip_serializing_if = "Option::is_none")]
+    pub client_message_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CreateUploadUrlVariables {
+    #[serde(rename = "fileName")]
+    pub file_name: String,
+    #[serde(rename = "mimeType")]
+    pub mime_type: String,
+    pub size: u64,
 }
 
+/// The user is taken from the caller's identity, never from the variables.
 #[derive(Serialize)]
 pub struct UpdateUserStatusVariables {
-    pub username: String,
     pub status: String,
 }
 
+#[derive(Serialize)]
+pub struct UpdateLastSeenVariables {
+    #[serde(rename = "chatId")]
+    pub chat_id: String,
+    #[serde(rename = "deliveredUpTo", skip_serializing_if = "Option::is_none")]
+    pub delivered_up_to: Option<f64>,
+    #[serde(rename = "readUpTo", skip_serializing_if = "Option::is_none")]
+    pub read_up_to: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct SendTypingVariables {
+    #[serde(rename = "chatId")]
+    pub chat_id: String,
+    #[serde(rename = "isTyping")]
+    pub is_typing: bool,
+}
+
 #[derive(Serialize)]
 pub struct CreateUserVariables {
     pub username: String,
     pub email: String,
 }
 
+#[derive(Serialize)]
+pub struct ImportMessagesVariables {
+    #[serde(rename = "chatId")]
+    pub chat_id: String,
+    pub messages: Vec<ImportMessageInput>,
+}
+
+#[derive(Serialize)]
+pub struct ImportMessageInput {
+    #[serde(rename = "messageId")]
+    pub message_id: String,
+    pub sender: String,
+    pub content: String,
+    pub timestamp: f64,
+}
+
+#[derive(Serialize)]
+pub struct RegisterPushSubscriptionVariables {
+    #[serde(rename = "deviceId")]
+    pub device_id: String,
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+}
+
 #[derive(Deserialize)]
 pub struct CreateMessageResponse {
     #[serde(rename = "createMessage")]
     pub create_message: MessageData,
 }
 
+#[derive(Deserialize)]
+pub struct CreateUploadUrlResponse {
+    #[serde(rename = "createUploadUrl")]
+    pub create_upload_url: UploadTarget,
+}
+
 #[derive(Deserialize)]
 pub struct UpdateUserStatusResponse {
     #[serde(rename = "updateUserStatus")]
-    pub update_user_status: User,
+    pub update_user_status: Option<UserStatusData>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateLastSeenResponse {
+    /// Null when the cursor was already at or past the requested position.
+    #[serde(rename = "updateLastSeen")]
+    pub update_last_seen: Option<ReadReceipt>,
 }
 
 #[derive(Deserialize)]
@@ -73,3 +293,9 @@ pub struct CreateUserResponse {
     #[serde(rename = "createUser")]
     pub create_user: User,
 }
+
+#[derive(Deserialize)]
+pub struct ImportMessagesResponse {
+    #[serde(rename = "importMessages")]
+    pub import_messages: ImportResult,
+}
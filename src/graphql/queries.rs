@@ -1,9 +1,9 @@
-use crate::graphql::types::MessageData;
+use crate::graphql::types::{ConversationPageData, MessageData, MessagePageData, UserPageData};
+use crate::models::notification_settings::NotificationSettings;
+use crate::models::receipt::ReadReceipt;
 use crate::models::user::User;
 use serde::Deserialize;
 
-use super::types::ConversationData;
-
 pub const LIST_MESSAGES_QUERY: &str = r#"
     query ListMessages {
         listMessages {
@@ -40,47 +40,132 @@ pub const GET_USER_BY_EMAIL_QUERY: &str = r#"
 "#;
 
 pub const GET_CONVERSATION_QUERY: &str = r#"
-    query GetConversation($otherUsername: String!) {
-        getConversation(otherUsername: $otherUsername) {
-            messageId
-            content
-            sender
-            timestamp
+    query GetConversation($otherUsername: String!, $limit: Int, $nextToken: String) {
+        getConversation(otherUsername: $otherUsername, limit: $limit, nextToken: $nextToken) {
+            items {
+                messageId
+                content
+                sender
+                timestamp
+                sequence
+                chatId
+                status
+                messageType
+                attachment {
+                    key
+                    fileName
+                    mimeType
+                    size
+                    width
+                    height
+                    placeholder
+                    url
+                }
+                mentions {
+                    username
+                    offset
+                    length
+                }
+                importedBy
+            }
+            nextToken
+        }
+    }
+"#;
+
+pub const SEARCH_MESSAGES_QUERY: &str = r#"
+    query SearchMessages($query: String!, $chatId: String, $limit: Int, $nextToken: String) {
+        searchMessages(query: $query, chatId: $chatId, limit: $limit, nextToken: $nextToken) {
+            items {
+                messageId
+                content
+                sender
+                timestamp
+                sequence
+                chatId
+                status
+                messageType
+                mentions {
+                    username
+                    offset
+                    length
+                }
+            }
+            nextToken
+        }
+    }
+"#;
+
+pub const GET_READ_RECEIPTS_QUERY: &str = r#"
+    query GetReadReceipts($chatId: String!) {
+        getReadReceipts(chatId: $chatId) {
             chatId
-            status
+            username
+            deliveredUpTo
+            readUpTo
         }
     }
 "#;
 
 pub const LIST_CONVERSATIONS_QUERY: &str = r#"
-    query ListConversations {
-        listConversations {
-            chatId
-            otherUser {
+    query ListConversations($limit: Int, $nextToken: String) {
+        listConversations(limit: $limit, nextToken: $nextToken) {
+            items {
+                chatId
+                otherUser {
+                    username
+                    email
+                    createdAt
+                    status
+                    lastSeen
+                }
+                lastMessage {
+                    messageId
+                    content
+                    sender
+                    timestamp
+                    sequence
+                    chatId
+                    messageType
+                }
+                unreadCount
+                mentionCount
+            }
+            nextToken
+        }
+    }
+"#;
+
+pub const SEARCH_USERS_QUERY: &str = r#"
+    query SearchUsers($prefix: String!, $limit: Int, $nextToken: String) {
+        searchUsers(prefix: $prefix, limit: $limit, nextToken: $nextToken) {
+            items {
                 username
                 email
+                createdAt
                 status
                 lastSeen
             }
-            lastMessage {
-                messageId
-                content
-                sender
-                timestamp
-            }
-            unreadCount
+            nextToken
         }
     }
 "#;
 
-pub const LIST_USERS_QUERY: &str = r#"
-    query ListUsers {
-        listUsers {
-            username
-            email
-            createdAt
-            status
-            lastSeen
+pub const GET_NOTIFICATION_SETTINGS_QUERY: &str = r#"
+    query GetNotificationSettings {
+        getNotificationSettings {
+            chats {
+                chatId
+                mentionsOnly
+                mute
+                mutedUntil
+            }
+            doNotDisturb {
+                enabled
+                start
+                end
+                timeZone
+            }
         }
     }
 "#;
@@ -104,16 +189,35 @@ pub struct GetUserByEmailResponse {
 #[derive(Debug, Deserialize)]
 pub struct GetConversationResponse {
     #[serde(rename = "getConversation")]
-    pub get_conversation: Vec<MessageData>,
+    pub get_conversation: MessagePageData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchMessagesResponse {
+    #[serde(rename = "searchMessages")]
+    pub search_messages: MessagePageData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetReadReceiptsResponse {
+    #[serde(rename = "getReadReceipts")]
+    pub get_read_receipts: Vec<ReadReceipt>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ListConversationsResponse {
-    pub list_conversations: Vec<ConversationData>,
+    #[serde(rename = "listConversations")]
+    pub list_conversations: ConversationPageData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchUsersResponse {
+    #[serde(rename = "searchUsers")]
+    pub search_users: UserPageData,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct ListUsersResponse {
-    #[serde(rename = "listUsers")]
-    pub list_users: Vec<User>,
+pub struct GetNotificationSettingsResponse {
+    #[serde(rename = "getNotificationSettings")]
+    pub get_notification_settings: NotificationSettings,
 }
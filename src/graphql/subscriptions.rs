@@ -1,16 +1,100 @@
 use serde::Deserialize;
 
-use super::types::MessageData;
+use super::types::{MessageData, UserStatusData};
+use crate::models::receipt::ReadReceipt;
+use crate::models::typing::TypingEvent;
 
 pub const ON_CREATE_MESSAGE_SUBSCRIPTION: &str = r#"
     subscription OnCreateMessage($chatId: String!) {
         onCreateMessage(chatId: $chatId) {
             messageId
+            clientMessageId
             content
             sender
             timestamp
+            sequence
             chatId
             status
+            messageType
+            attachment {
+                key
+                fileName
+                mimeType
+                size
+                width
+                height
+                placeholder
+                url
+            }
+            mentions {
+                username
+                offset
+                length
+            }
+        }
+    }
+"#;
+
+pub const ON_NEW_MESSAGE_SUBSCRIPTION: &str = r#"
+    subscription OnNewMessage {
+        onNewMessage {
+            messageId
+            clientMessageId
+            content
+            sender
+            receiver
+            timestamp
+            sequence
+            chatId
+            status
+            messageType
+            attachment {
+                key
+                fileName
+                mimeType
+                size
+                width
+                height
+                placeholder
+                url
+            }
+            mentions {
+                username
+                offset
+                length
+            }
+        }
+    }
+"#;
+
+pub const ON_READ_RECEIPT_SUBSCRIPTION: &str = r#"
+    subscription OnReadReceipt($chatId: String!) {
+        onReadReceipt(chatId: $chatId) {
+            chatId
+            username
+            deliveredUpTo
+            readUpTo
+        }
+    }
+"#;
+
+pub const ON_TYPING_SUBSCRIPTION: &str = r#"
+    subscription OnTyping($chatId: String!) {
+        onTyping(chatId: $chatId) {
+            chatId
+            username
+            isTyping
+            timestamp
+        }
+    }
+"#;
+
+pub const ON_USER_STATUS_CHANGE_SUBSCRIPTION: &str = r#"
+    subscription OnUserStatusChange($username: String!) {
+        onUserStatusChange(username: $username) {
+            username
+            status
+            lastSeen
         }
     }
 "#;
@@ -25,3 +109,47 @@ pub struct SubscriptionData {
     #[serde(rename = "onCreateMessage")]
     pub on_create_message: MessageData,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct ReadReceiptPayload {
+    pub data: ReadReceiptData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReadReceiptData {
+    #[serde(rename = "onReadReceipt")]
+    pub on_read_receipt: Option<ReadReceipt>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TypingPayload {
+    pub data: TypingData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TypingData {
+    #[serde(rename = "onTyping")]
+    pub on_typing: Option<TypingEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserStatusPayload {
+    pub data: UserStatusChangeData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserStatusChangeData {
+    #[serde(rename = "onUserStatusChange")]
+    pub on_user_status_change: Option<UserStatusData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewMessagePayload {
+    pub data: NewMessageData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewMessageData {
+    #[serde(rename = "onNewMessage")]
+    pub on_new_message: Option<MessageData>,
+}
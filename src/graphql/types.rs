@@ -1,14 +1,72 @@
+use crate::models::attachment::Attachment;
+use crate::models::mention::Mention;
+use crate::models::message::MessageType;
+use crate::models::user::User;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageData {
     #[serde(rename = "messageId")]
     pub message_id: String,
+    #[serde(rename = "clientMessageId", default)]
+    pub client_message_id: Option<String>,
     pub content: String,
     pub sender: String,
+    #[serde(default)]
+    pub receiver: Option<String>,
     pub timestamp: f64,
+    #[serde(default)]
+    pub sequence: Option<u64>,
     #[serde(rename = "chatId")]
     pub chat_id: String,
+    #[serde(rename = "messageType", default)]
+    pub message_type: Option<MessageType>,
+    #[serde(default)]
+    pub attachment: Option<Attachment>,
+    #[serde(default)]
+    pub mentions: Option<Vec<Mention>>,
+    #[serde(rename = "importedBy", default)]
+    pub imported_by: Option<String>,
+}
+
+/// One page of a conversation, newest message first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessagePageData {
+    pub items: Vec<MessageData>,
+    #[serde(rename = "nextToken", default)]
+    pub next_token: Option<String>,
+}
+
+/// One page of the caller's conversations, in chat id order.
+#[derive(Debug, Deserialize)]
+pub struct ConversationPageData {
+    pub items: Vec<ConversationData>,
+    #[serde(rename = "nextToken", default)]
+    pub next_token: Option<String>,
+}
+
+/// One page of the user directory, in username order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserPageData {
+    pub items: Vec<User>,
+    #[serde(rename = "nextToken", default)]
+    pub next_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub imported: u32,
+    /// Throttled message ids to send again.
+    pub unprocessed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadTarget {
+    pub key: String,
+    #[serde(rename = "uploadUrl")]
+    pub upload_url: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +79,8 @@ pub struct ConversationData {
     pub last_message: Option<MessageData>,
     #[serde(rename = "unreadCount")]
     pub unread_count: i32,
+    #[serde(rename = "mentionCount", default)]
+    pub mention_count: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +94,16 @@ pub struct UserData {
     pub status: Option<String>,
 }
 
+/// The fields of `User` that `updateUserStatus` returns and
+/// `onUserStatusChange` pushes.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserStatusData {
+    pub username: String,
+    pub status: Option<String>,
+    #[serde(rename = "lastSeen")]
+    pub last_seen: Option<f64>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct GraphQLRequest<T> {
     pub query: String,
@@ -52,6 +122,31 @@ pub struct GraphQLError {
     pub message: String,
     pub locations: Option<Vec<ErrorLocation>>,
     pub path: Option<Vec<String>>,
+    /// AppSync's error class, e.g. "ValidationError" from `$util.error` or
+    /// "DynamoDB:ProvisionedThroughputExceededException".
+    #[serde(rename = "errorType", default)]
+    pub error_type: Option<String>,
+}
+
+/// Error types worth trying again: throttling and service-side hiccups.
+const TRANSIENT_ERROR_TYPES: [&str; 6] = [
+    "ProvisionedThroughputExceeded",
+    "Throttl",
+    "RequestLimitExceeded",
+    "InternalFailure",
+    "ServiceUnavailable",
+    // createMessage racing another message in the same chat
+    "TransactionConflict",
+];
+
+impl GraphQLError {
+    /// Whether the same request may succeed later. Anything unrecognised,
+    /// validation and authorization errors included, is treated as final.
+    pub fn is_transient(&self) -> bool {
+        self.error_type
+            .as_deref()
+            .is_some_and(|t| TRANSIENT_ERROR_TYPES.iter().any(|p| t.contains(p)))
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -59,3 +154,24 @@ pub struct ErrorLocation {
     pub line: i32,
     pub column: i32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_throttling_and_service_errors_are_transient() {
+        let error = |error_type: Option<&str>| GraphQLError {
+            message: String::new(),
+            locations: None,
+            path: None,
+            error_type: error_type.map(str::to_string),
+        };
+        assert!(error(Some("DynamoDB:ProvisionedThroughputExceededException")).is_transient());
+        assert!(error(Some("ThrottlingException")).is_transient());
+        assert!(error(Some("TransactionConflict")).is_transient());
+        assert!(!error(Some("ValidationError")).is_transient());
+        assert!(!error(Some("Unauthorized")).is_transient());
+        assert!(!error(None).is_transient());
+    }
+}
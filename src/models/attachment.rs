@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub key: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size: u64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Blurhash shown while the full image loads.
+    #[serde(default)]
+    pub placeholder: Option<String>,
+    /// Download URL. Presigned by the server, or a local object URL while
+    /// the upload is still in flight. Never sent back to the API.
+    #[serde(default, skip_serializing)]
+    pub url: Option<String>,
+}
+
+impl Attachment {
+    pub fn is_image(&self) -> bool {
+        self.mime_type.starts_with("image/")
+    }
+
+    pub fn display_size(&self) -> String {
+        let size = self.size as f64;
+        if size < 1024.0 {
+            format!("{} B", self.size)
+        } else if size < 1024.0 * 1024.0 {
+            format!("{:.1} KB", size / 1024.0)
+        } else {
+            format!("{:.1} MB", size / 1024.0 / 1024.0)
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use super::message::Message;
 use super::user::User;
+use crate::graphql::types::ConversationData;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -8,19 +9,40 @@ pub struct Conversation {
     pub other_user: User,
     pub last_message: Option<Message>,
     pub unread_count: i32,
+    #[serde(default)]
+    pub mention_count: i32,
 }
 
 impl Conversation {
-    pub fn new(current_user: &str, other_user: User) -> Self {
-        let mut users = vec![current_user.to_string(), other_user.username.clone()];
+    /// `CHAT#<a>#<b>` with the two usernames in sorted order.
+    pub fn chat_id_for(user: &str, other_user: &str) -> String {
+        let mut users = [user, other_user];
         users.sort();
-        let chat_id = format!("CHAT#{}#{}", users[0], users[1]);
+        format!("CHAT#{}#{}", users[0], users[1])
+    }
 
+    pub fn new(current_user: &str, other_user: User) -> Self {
         Self {
-            chat_id,
+            chat_id: Self::chat_id_for(current_user, &other_user.username),
             other_user,
             last_message: None,
             unread_count: 0,
+            mention_count: 0,
         }
     }
+
+    pub fn from_conversation_data(data: ConversationData) -> Self {
+        Self {
+            chat_id: data.chat_id,
+            other_user: User::from(data.other_user),
+            last_message: data.last_message.map(Message::from_message_data),
+            unread_count: data.unread_count,
+            mention_count: data.mention_count.unwrap_or_default(),
+        }
+    }
+
+    /// When the conversation last saw activity, for newest-first ordering.
+    pub fn last_activity(&self) -> f64 {
+        self.last_message.as_ref().map_or(0.0, |m| m.timestamp)
+    }
 }
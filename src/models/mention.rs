@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+/// An `@username` span inside a message. `offset` and `length` count Unicode
+/// characters (not bytes) and cover the leading `@`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Mention {
+    pub username: String,
+    pub offset: u32,
+    pub length: u32,
+}
@@ -1,20 +1,35 @@
 use crate::graphql::types::MessageData;
+use crate::models::attachment::Attachment;
+use crate::models::conversation::Conversation;
+use crate::models::mention::Mention;
+use crate::models::receipt::ReadReceipt;
 use serde::{Deserialize, Serialize};
 use strum_macros::Display;
 
+/// Longest message the server accepts, in UTF-16 code units (what both the
+/// resolver and `<input maxlength>` count).
+pub const MAX_CONTENT_LENGTH: usize = 10_000;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Display)]
 pub enum MessageStatus {
     #[strum(serialize = "sending")]
     Sending,
     #[strum(serialize = "sent")]
     Sent,
+    #[strum(serialize = "delivered")]
+    Delivered,
+    #[strum(serialize = "read")]
+    Read,
     #[strum(serialize = "failed")]
     Failed,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
 pub enum MessageType {
     Text,
+    Image,
+    File,
     System,
     Error,
 }
@@ -22,52 +37,244 @@ pub enum MessageType {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Message {
     pub message_id: String,
+    /// Id generated when the message was composed; the server echoes it back
+    /// so the optimistic copy can be matched with its stored version.
+    #[serde(default)]
+    pub client_message_id: Option<String>,
     pub content: String,
     pub sender: String,
     pub timestamp: f64,
+    /// Server-assigned position within the chat; `None` until the send is acknowledged.
+    #[serde(default)]
+    pub sequence: Option<u64>,
     pub status: MessageStatus,
     pub message_type: MessageType,
     pub chat_id: String,
+    #[serde(default)]
+    pub attachment: Option<Attachment>,
+    #[serde(default)]
+    pub mentions: Vec<Mention>,
+    /// Who brought the message in from an export, for imported history.
+    #[serde(default)]
+    pub imported_by: Option<String>,
+    /// Fraction of the attachment uploaded so far, while sending.
+    #[serde(skip)]
+    pub upload_progress: Option<f64>,
 }
 
 impl Message {
     pub fn new_text(content: String, sender: String, receiver: String) -> Self {
-        let mut users = vec![sender.clone(), receiver];
-        users.sort();
-        let chat_id = format!("CHAT#{}#{}", users[0], users[1]);
+        let chat_id = Conversation::chat_id_for(&sender, &receiver);
 
+        let message_id = uuid::Uuid::new_v4().to_string();
         Self {
-            message_id: uuid::Uuid::new_v4().to_string(),
+            client_message_id: Some(message_id.clone()),
+            message_id,
             content,
             sender,
             timestamp: js_sys::Date::now(),
+            sequence: None,
             status: MessageStatus::Sending,
             message_type: MessageType::Text,
             chat_id,
+            attachment: None,
+            mentions: Vec::new(),
+            imported_by: None,
+            upload_progress: None,
         }
     }
 
+    pub fn new_attachment(sender: String, receiver: String, attachment: Attachment) -> Self {
+        let message_type = if attachment.is_image() {
+            MessageType::Image
+        } else {
+            MessageType::File
+        };
+        let mut message = Self::new_text(attachment.file_name.clone(), sender, receiver);
+        message.message_type = message_type;
+        message.attachment = Some(attachment);
+        message.upload_progress = Some(0.0);
+        message
+    }
+
     pub fn new_system(content: String) -> Self {
         Self {
             message_id: uuid::Uuid::new_v4().to_string(),
+            client_message_id: None,
             content,
             sender: "system".to_string(),
             timestamp: js_sys::Date::now(),
+            sequence: None,
             status: MessageStatus::Sent,
             message_type: MessageType::System,
             chat_id: "SYSTEM".to_string(),
+            attachment: None,
+            mentions: Vec::new(),
+            imported_by: None,
+            upload_progress: None,
         }
     }
 
+    /// Applies a receipt from the other chat member. Statuses only move forward.
+    pub fn apply_receipt(&mut self, receipt: &ReadReceipt) {
+        if self.chat_id != receipt.chat_id || self.sender == receipt.username {
+            return;
+        }
+        match (receipt.status_for(self.timestamp), &self.status) {
+            (Some(MessageStatus::Read), MessageStatus::Sent | MessageStatus::Delivered) => {
+                self.status = MessageStatus::Read
+            }
+            (Some(MessageStatus::Delivered), MessageStatus::Sent) => {
+                self.status = MessageStatus::Delivered
+            }
+            _ => {}
+        }
+    }
+
+    /// Not yet acknowledged by the server.
+    pub fn is_pending(&self) -> bool {
+        matches!(self.status, MessageStatus::Sending | MessageStatus::Failed)
+    }
+
+    /// Puts one chat's messages in display order. Stored messages with a
+    /// server sequence go by sequence, since concurrent sends can get
+    /// timestamps out of order. Stored messages without one (imported
+    /// history) go by timestamp, just before the first sequenced message that
+    /// is newer. Pending messages come last, by local timestamp.
+    pub fn sort_chat(messages: &mut Vec<Message>) {
+        let by_timestamp = |a: &Message, b: &Message| {
+            a.timestamp
+                .total_cmp(&b.timestamp)
+                .then_with(|| a.message_id.cmp(&b.message_id))
+        };
+        let (mut pending, stored): (Vec<_>, Vec<_>) =
+            messages.drain(..).partition(Message::is_pending);
+        let (mut sequenced, mut unsequenced): (Vec<_>, Vec<_>) =
+            stored.into_iter().partition(|m| m.sequence.is_some());
+        sequenced.sort_by(|a, b| {
+            a.sequence
+                .cmp(&b.sequence)
+                .then_with(|| a.message_id.cmp(&b.message_id))
+        });
+        unsequenced.sort_by(by_timestamp);
+        pending.sort_by(by_timestamp);
+
+        let mut unsequenced = unsequenced.into_iter().peekable();
+        for msg in sequenced {
+            while let Some(older) = unsequenced.next_if(|u| u.timestamp < msg.timestamp) {
+                messages.push(older);
+            }
+            messages.push(msg);
+        }
+        messages.extend(unsequenced);
+        messages.extend(pending);
+    }
+
+    /// Whether both values describe the same stored message, whichever path
+    /// (optimistic insert, send response, subscription) each came from.
+    pub fn is_same_message(&self, other: &Message) -> bool {
+        self.message_id == other.message_id
+            || (self.client_message_id.is_some()
+                && self.client_message_id == other.client_message_id)
+            || (self.chat_id == other.chat_id
+                && self.sequence.is_some()
+                && self.sequence == other.sequence)
+    }
+
+    pub fn mentions_user(&self, username: &str) -> bool {
+        self.mentions.iter().any(|m| m.username == username)
+    }
+
     pub fn from_message_data(data: MessageData) -> Self {
         Self {
             message_id: data.message_id,
+            client_message_id: data.client_message_id,
             content: data.content,
             sender: data.sender,
             timestamp: data.timestamp,
+            sequence: data.sequence,
             status: MessageStatus::Sent,
-            message_type: MessageType::Text,
+            message_type: data.message_type.unwrap_or(MessageType::Text),
             chat_id: data.chat_id,
+            attachment: data.attachment,
+            mentions: data.mentions.unwrap_or_default(),
+            imported_by: data.imported_by,
+            upload_progress: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, timestamp: f64, sequence: Option<u64>) -> Message {
+        Message {
+            message_id: id.to_string(),
+            client_message_id: None,
+            content: String::new(),
+            sender: "alice".to_string(),
+            timestamp,
+            sequence,
+            status: MessageStatus::Sent,
+            message_type: MessageType::Text,
+            chat_id: "CHAT#alice#bob".to_string(),
+            attachment: None,
+            mentions: Vec::new(),
+            imported_by: None,
+            upload_progress: None,
+        }
+    }
+
+    #[test]
+    fn chat_order_prefers_sequence_over_clock() {
+        let pending = |id: &str, timestamp: f64| Message {
+            status: MessageStatus::Sending,
+            ..message(id, timestamp, None)
+        };
+        let messages = [
+            pending("pending", 50.0),
+            message("b", 100.0, Some(2)),
+            message("a", 200.0, Some(1)),
+            message("c", 100.0, Some(3)),
+            pending("same-ms-2", 10.0),
+            pending("same-ms-1", 10.0),
+            message("imported", 20.0, None),
+            message("imported-mid", 150.0, None),
+            message("imported-new", 300.0, None),
+        ];
+
+        // Every arrival order ends up the same
+        for rotation in 0..messages.len() {
+            let mut sorted = messages.to_vec();
+            sorted.rotate_left(rotation);
+            Message::sort_chat(&mut sorted);
+            let ids: Vec<_> = sorted.iter().map(|m| m.message_id.as_str()).collect();
+            assert_eq!(
+                ids,
+                [
+                    "imported",
+                    "imported-mid",
+                    "a",
+                    "b",
+                    "c",
+                    "imported-new",
+                    "same-ms-1",
+                    "same-ms-2",
+                    "pending"
+                ]
+            );
         }
     }
+
+    #[test]
+    fn echo_matches_optimistic_copy_by_client_id() {
+        let mut optimistic = message("local", 10.0, None);
+        optimistic.client_message_id = Some("local".to_string());
+        let mut stored = message("server", 12.0, Some(7));
+        stored.client_message_id = Some("local".to_string());
+        assert!(optimistic.is_same_message(&stored));
+        assert!(!message("other", 10.0, None).is_same_message(&stored));
+        assert!(message("other", 10.0, Some(7)).is_same_message(&stored));
+    }
 }
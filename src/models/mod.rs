@@ -1,3 +1,8 @@
+pub mod attachment;
 pub mod conversation;
+pub mod mention;
 pub mod message;
+pub mod notification_settings;
+pub mod receipt;
+pub mod typing;
 pub mod user;
@@ -0,0 +1,265 @@
+use crate::models::message::Message;
+use serde::{Deserialize, Serialize};
+
+const HOUR_MS: f64 = 60.0 * 60.0 * 1000.0;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MuteKind {
+    /// Ends at `muted_until`.
+    Timed,
+    UntilTurnedOff,
+    /// Like `UntilTurnedOff`, and the chat is also left out of the unread
+    /// badge.
+    Forever,
+}
+
+/// What the mute menu offers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MuteDuration {
+    OneHour,
+    EightHours,
+    UntilTurnedOff,
+    Forever,
+}
+
+impl MuteDuration {
+    pub const ALL: [MuteDuration; 4] = [
+        MuteDuration::OneHour,
+        MuteDuration::EightHours,
+        MuteDuration::UntilTurnedOff,
+        MuteDuration::Forever,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MuteDuration::OneHour => "For 1 hour",
+            MuteDuration::EightHours => "For 8 hours",
+            MuteDuration::UntilTurnedOff => "Until I turn it back on",
+            MuteDuration::Forever => "Forever",
+        }
+    }
+
+    pub fn key(self) -> &'static str {
+        match self {
+            MuteDuration::OneHour => "1h",
+            MuteDuration::EightHours => "8h",
+            MuteDuration::UntilTurnedOff => "off",
+            MuteDuration::Forever => "forever",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|d| d.key() == key)
+    }
+
+    /// The stored mute and its end, for a mute starting at `now`.
+    pub fn mute(self, now: f64) -> (MuteKind, Option<f64>) {
+        match self {
+            MuteDuration::OneHour => (MuteKind::Timed, Some(now + HOUR_MS)),
+            MuteDuration::EightHours => (MuteKind::Timed, Some(now + 8.0 * HOUR_MS)),
+            MuteDuration::UntilTurnedOff => (MuteKind::UntilTurnedOff, None),
+            MuteDuration::Forever => (MuteKind::Forever, None),
+        }
+    }
+}
+
+/// One chat's notification preferences. Chats without one notify for
+/// every message.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatNotificationSetting {
+    pub chat_id: String,
+    #[serde(default)]
+    pub mentions_only: bool,
+    #[serde(default)]
+    pub mute: Option<MuteKind>,
+    #[serde(default)]
+    pub muted_until: Option<f64>,
+}
+
+impl ChatNotificationSetting {
+    pub fn new(chat_id: &str) -> Self {
+        Self {
+            chat_id: chat_id.to_string(),
+            mentions_only: false,
+            mute: None,
+            muted_until: None,
+        }
+    }
+
+    pub fn is_muted(&self, now: f64) -> bool {
+        match self.mute {
+            None => false,
+            Some(MuteKind::Timed) => self.muted_until.is_some_and(|until| now < until),
+            Some(_) => true,
+        }
+    }
+}
+
+/// Quiet hours, as "HH:MM" wall-clock times in an IANA time zone. A start
+/// after the end spans midnight.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DoNotDisturb {
+    pub enabled: bool,
+    pub start: String,
+    pub end: String,
+    pub time_zone: String,
+}
+
+impl DoNotDisturb {
+    /// Whether `minutes` after midnight, in `time_zone`, falls in the quiet
+    /// hours.
+    pub fn is_active_at(&self, minutes: u32) -> bool {
+        let (Some(start), Some(end)) = (parse_time(&self.start), parse_time(&self.end)) else {
+            return false;
+        };
+        if !self.enabled || start == end {
+            return false;
+        }
+        if start < end {
+            (start..end).contains(&minutes)
+        } else {
+            minutes >= start || minutes < end
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationSettings {
+    #[serde(default)]
+    pub chats: Vec<ChatNotificationSetting>,
+    #[serde(default)]
+    pub do_not_disturb: Option<DoNotDisturb>,
+}
+
+impl NotificationSettings {
+    pub fn chat(&self, chat_id: &str) -> ChatNotificationSetting {
+        self.chats
+            .iter()
+            .find(|c| c.chat_id == chat_id)
+            .cloned()
+            .unwrap_or_else(|| ChatNotificationSetting::new(chat_id))
+    }
+
+    pub fn set_chat(&mut self, setting: ChatNotificationSetting) {
+        self.chats.retain(|c| c.chat_id != setting.chat_id);
+        self.chats.push(setting);
+    }
+
+    pub fn muted_chats(&self, now: f64) -> Vec<String> {
+        self.chats
+            .iter()
+            .filter(|c| c.is_muted(now))
+            .map(|c| c.chat_id.clone())
+            .collect()
+    }
+
+    /// Whether `msg` may notify `username`. `local_minutes` is the time of day
+    /// in the do-not-disturb time zone, if there's a schedule.
+    pub fn allows(
+        &self,
+        msg: &Message,
+        username: &str,
+        now: f64,
+        local_minutes: Option<u32>,
+    ) -> bool {
+        let chat = self.chat(&msg.chat_id);
+        if chat.is_muted(now) || (chat.mentions_only && !msg.mentions_user(username)) {
+            return false;
+        }
+        !matches!(
+            (&self.do_not_disturb, local_minutes),
+            (Some(dnd), Some(minutes)) if dnd.is_active_at(minutes)
+        )
+    }
+}
+
+/// Minutes after midnight for "HH:MM".
+pub fn parse_time(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::mention::Mention;
+    use crate::models::message::{MessageStatus, MessageType};
+
+    fn dnd(start: &str, end: &str) -> DoNotDisturb {
+        DoNotDisturb {
+            enabled: true,
+            start: start.to_string(),
+            end: end.to_string(),
+            time_zone: "Europe/Berlin".to_string(),
+        }
+    }
+
+    #[test]
+    fn quiet_hours_can_span_midnight() {
+        let night = dnd("22:00", "07:30");
+        assert!(night.is_active_at(23 * 60));
+        assert!(night.is_active_at(7 * 60 + 29));
+        assert!(!night.is_active_at(7 * 60 + 30));
+        assert!(!night.is_active_at(12 * 60));
+
+        let lunch = dnd("12:00", "13:00");
+        assert!(lunch.is_active_at(12 * 60 + 30));
+        assert!(!lunch.is_active_at(13 * 60));
+        assert!(!DoNotDisturb {
+            enabled: false,
+            ..lunch
+        }
+        .is_active_at(12 * 60 + 30));
+    }
+
+    #[test]
+    fn mutes_mentions_only_and_quiet_hours_suppress_notifications() {
+        let mut msg = Message {
+            message_id: "m1".to_string(),
+            client_message_id: None,
+            content: "Lunch?".to_string(),
+            sender: "alice".to_string(),
+            timestamp: 0.0,
+            sequence: None,
+            status: MessageStatus::Sent,
+            message_type: MessageType::Text,
+            chat_id: "CHAT#alice#bob".to_string(),
+            attachment: None,
+            mentions: Vec::new(),
+            imported_by: None,
+            upload_progress: None,
+        };
+        let mut settings = NotificationSettings::default();
+        assert!(settings.allows(&msg, "bob", 0.0, None));
+
+        let (mute, muted_until) = MuteDuration::OneHour.mute(0.0);
+        settings.set_chat(ChatNotificationSetting {
+            mute: Some(mute),
+            muted_until,
+            ..settings.chat(&msg.chat_id)
+        });
+        assert!(!settings.allows(&msg, "bob", 1_000.0, None));
+        assert!(settings.allows(&msg, "bob", HOUR_MS, None));
+
+        settings.set_chat(ChatNotificationSetting {
+            mentions_only: true,
+            ..ChatNotificationSetting::new(&msg.chat_id)
+        });
+        assert!(!settings.allows(&msg, "bob", 0.0, None));
+        msg.mentions.push(Mention {
+            username: "bob".to_string(),
+            offset: 0,
+            length: 4,
+        });
+        assert!(settings.allows(&msg, "bob", 0.0, None));
+
+        settings.do_not_disturb = Some(dnd("22:00", "07:00"));
+        assert!(!settings.allows(&msg, "bob", 0.0, Some(23 * 60)));
+        assert!(settings.allows(&msg, "bob", 0.0, Some(9 * 60)));
+    }
+}
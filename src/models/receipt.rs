@@ -0,0 +1,68 @@
+use crate::models::message::MessageStatus;
+use serde::{Deserialize, Serialize};
+
+/// How far a chat member has received and read a chat. Cursors are message
+/// timestamps: everything at or before them counts as delivered/read.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadReceipt {
+    pub chat_id: String,
+    pub username: String,
+    #[serde(default)]
+    pub delivered_up_to: Option<f64>,
+    #[serde(default)]
+    pub read_up_to: Option<f64>,
+}
+
+impl ReadReceipt {
+    /// The status this receipt implies for a message sent at `timestamp`.
+    pub fn status_for(&self, timestamp: f64) -> Option<MessageStatus> {
+        if self.read_up_to.is_some_and(|t| timestamp <= t) {
+            Some(MessageStatus::Read)
+        } else if self
+            .delivered_up_to
+            .or(self.read_up_to)
+            .is_some_and(|t| timestamp <= t)
+        {
+            Some(MessageStatus::Delivered)
+        } else {
+            None
+        }
+    }
+
+    /// Combines two receipts for the same member, keeping the furthest cursors.
+    pub fn merge(&mut self, other: &ReadReceipt) {
+        let max = |a: Option<f64>, b: Option<f64>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        self.delivered_up_to = max(self.delivered_up_to, other.delivered_up_to);
+        self.read_up_to = max(self.read_up_to, other.read_up_to);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_only_move_forward() {
+        let mut receipt = ReadReceipt {
+            chat_id: "CHAT#a#b".to_string(),
+            username: "b".to_string(),
+            delivered_up_to: Some(200.0),
+            read_up_to: Some(100.0),
+        };
+        receipt.merge(&ReadReceipt {
+            delivered_up_to: Some(150.0),
+            read_up_to: Some(120.0),
+            ..receipt.clone()
+        });
+
+        assert_eq!(receipt.delivered_up_to, Some(200.0));
+        assert_eq!(receipt.read_up_to, Some(120.0));
+        assert_eq!(receipt.status_for(120.0), Some(MessageStatus::Read));
+        assert_eq!(receipt.status_for(180.0), Some(MessageStatus::Delivered));
+        assert_eq!(receipt.status_for(250.0), None);
+    }
+}
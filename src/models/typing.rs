@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+/// Someone started or stopped typing in a chat. These only travel over the
+/// realtime connection and are never persisted.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TypingEvent {
+    pub chat_id: String,
+    pub username: String,
+    pub is_typing: bool,
+    pub timestamp: f64,
+}
+
+/// A chat member currently typing, until `expires_at` (local clock, ms).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypingUser {
+    pub chat_id: String,
+    pub username: String,
+    pub expires_at: f64,
+}
@@ -1,4 +1,20 @@
+use crate::graphql::types::UserData;
 use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+/// Clients refresh `lastSeen` with a heartbeat; anyone silent for longer than
+/// this is shown as offline regardless of their stored status.
+pub const PRESENCE_STALE_MS: f64 = 3.0 * 60.0 * 1000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+pub enum UserStatus {
+    #[strum(serialize = "online")]
+    Online,
+    #[strum(serialize = "away")]
+    Away,
+    #[strum(serialize = "offline")]
+    Offline,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct User {
@@ -21,4 +37,27 @@ impl User {
             status: None,
         }
     }
+
+    /// Status to display, treating stale heartbeats as offline.
+    pub fn presence(&self, now: f64) -> UserStatus {
+        let stale = self.last_seen.is_none_or(|t| now - t > PRESENCE_STALE_MS);
+        match self.status.as_deref() {
+            _ if stale => UserStatus::Offline,
+            Some("online") => UserStatus::Online,
+            Some("away") => UserStatus::Away,
+            _ => UserStatus::Offline,
+        }
+    }
+}
+
+impl From<UserData> for User {
+    fn from(data: UserData) -> Self {
+        Self {
+            username: data.username,
+            email: data.email,
+            created_at: data.created_at,
+            last_seen: data.last_seen,
+            status: data.status,
+        }
+    }
 }
@@ -94,6 +94,12 @@ struct ResendConfirmationCodeRequest {
 
 pub struct AuthService;
 
+impl Default for AuthService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AuthService {
     pub fn new() -> Self {
         Self
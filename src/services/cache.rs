@@ -0,0 +1,412 @@
+use futures::channel::oneshot;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{
+    IdbDatabase, IdbObjectStore, IdbOpenDbRequest, IdbRequest, IdbTransaction, IdbTransactionMode,
+    IdbVersionChangeEvent,
+};
+
+use crate::models::conversation::Conversation;
+use crate::models::message::Message;
+use crate::models::user::User;
+use crate::state::chat_state::ChatState;
+use crate::state::message_store::ChatMessages;
+
+const DB_PREFIX: &str = "rusty-chat-sync";
+/// Must equal `MIGRATIONS.len()`.
+const DB_VERSION: u32 = 1;
+
+const CONVERSATIONS: &str = "conversations";
+const USERS: &str = "users";
+const CHATS: &str = "chats";
+
+type Migration = fn(&IdbDatabase) -> Result<(), JsValue>;
+
+/// `MIGRATIONS[n]` upgrades a database from version `n` to `n + 1`. Append
+/// new steps instead of editing old ones; browsers may hold any version.
+const MIGRATIONS: &[Migration] = &[create_stores];
+
+fn create_stores(db: &IdbDatabase) -> Result<(), JsValue> {
+    for store in [CONVERSATIONS, USERS, CHATS] {
+        db.create_object_store(store)?;
+    }
+    Ok(())
+}
+
+/// How many of a chat's newest messages are kept offline.
+pub const CACHED_MESSAGES_PER_CHAT: usize = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedChat {
+    pub chat_id: String,
+    pub messages: Vec<Message>,
+    pub history_cursor: Option<String>,
+}
+
+impl CachedChat {
+    /// Keeps the newest messages that made it to the server; in-flight sends
+    /// are owned by the session that started them.
+    pub fn new(chat_id: &str, messages: &[Message], history_cursor: Option<String>) -> Self {
+        let stored: Vec<Message> = messages
+            .iter()
+            .filter(|m| !m.is_pending())
+            .cloned()
+            .collect();
+        let skip = stored.len().saturating_sub(CACHED_MESSAGES_PER_CHAT);
+        // Older messages were dropped, so paging has to start over from the
+        // oldest one still cached
+        let history_cursor = if skip > 0 { None } else { history_cursor };
+        Self {
+            chat_id: chat_id.to_string(),
+            messages: stored.into_iter().skip(skip).collect(),
+            history_cursor,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CachedState {
+    pub conversations: Vec<Conversation>,
+    pub users: Vec<User>,
+    pub chats: Vec<CachedChat>,
+}
+
+/// What was last written to the offline cache, so unchanged parts are skipped.
+#[derive(Default)]
+pub struct CacheSnapshot {
+    conversations: Vec<Conversation>,
+    users: Vec<User>,
+    chats: HashMap<String, Rc<ChatMessages>>,
+}
+
+/// Per-user IndexedDB copy of conversations, users and recent messages.
+/// Values are stored as JSON strings keyed by chat id or username.
+pub struct CacheService;
+
+impl CacheService {
+    pub async fn load(username: &str) -> Result<CachedState, String> {
+        let db = open(username).await?;
+        let tx = transaction(
+            &db,
+            &[CONVERSATIONS, USERS, CHATS],
+            IdbTransactionMode::Readonly,
+        )?;
+        // All requests go out before awaiting so the transaction can't
+        // auto-commit in between
+        let conversations = get_all(&store(&tx, CONVERSATIONS)?)?;
+        let users = get_all(&store(&tx, USERS)?)?;
+        let chats = get_all(&store(&tx, CHATS)?)?;
+        Ok(CachedState {
+            conversations: read_all(&conversations).await?,
+            users: read_all(&users).await?,
+            chats: read_all(&chats).await?,
+        })
+    }
+
+    pub async fn save_conversations(
+        username: &str,
+        conversations: &[Conversation],
+    ) -> Result<(), String> {
+        let db = open(username).await?;
+        let tx = transaction(&db, &[CONVERSATIONS], IdbTransactionMode::Readwrite)?;
+        replace_all(
+            &store(&tx, CONVERSATIONS)?,
+            conversations.iter().map(|c| (c.chat_id.as_str(), c)),
+        )?;
+        wait_for_transaction(&tx).await
+    }
+
+    pub async fn save_users(username: &str, users: &[User]) -> Result<(), String> {
+        let db = open(username).await?;
+        let tx = transaction(&db, &[USERS], IdbTransactionMode::Readwrite)?;
+        replace_all(
+            &store(&tx, USERS)?,
+            users.iter().map(|u| (u.username.as_str(), u)),
+        )?;
+        wait_for_transaction(&tx).await
+    }
+
+    pub async fn save_chats(username: &str, chats: &[CachedChat]) -> Result<(), String> {
+        let db = open(username).await?;
+        let tx = transaction(&db, &[CHATS], IdbTransactionMode::Readwrite)?;
+        let store = store(&tx, CHATS)?;
+        for chat in chats {
+            put(&store, &chat.chat_id, chat)?;
+        }
+        wait_for_transaction(&tx).await
+    }
+
+    /// Writes the parts of `state` that changed since the last call to the
+    /// cache.
+    pub async fn save_changes(
+        username: &str,
+        snapshot: &RefCell<CacheSnapshot>,
+        state: &ChatState,
+    ) -> Result<(), String> {
+        let (conversations, users, chats) = {
+            let snapshot = snapshot.borrow();
+            let conversations = (!state.conversations.is_empty()
+                && state.conversations != snapshot.conversations)
+                .then(|| state.conversations.clone());
+            let users = (!state.users.is_empty() && state.users != snapshot.users)
+                .then(|| state.users.clone());
+            let chats: Vec<(String, Rc<ChatMessages>)> = state
+                .chats
+                .iter()
+                .filter(|(chat_id, chat)| {
+                    !chat.messages().is_empty()
+                        && snapshot
+                            .chats
+                            .get(*chat_id)
+                            .is_none_or(|saved| !Rc::ptr_eq(saved, chat))
+                })
+                .map(|(chat_id, chat)| (chat_id.clone(), chat.clone()))
+                .collect();
+            (conversations, users, chats)
+        };
+
+        if let Some(conversations) = conversations {
+            Self::save_conversations(username, &conversations).await?;
+            snapshot.borrow_mut().conversations = conversations;
+        }
+        if let Some(users) = users {
+            Self::save_users(username, &users).await?;
+            snapshot.borrow_mut().users = users;
+        }
+        if !chats.is_empty() {
+            let cached: Vec<CachedChat> = chats
+                .iter()
+                .map(|(chat_id, chat)| {
+                    CachedChat::new(chat_id, chat.messages(), chat.history_cursor.clone())
+                })
+                .collect();
+            Self::save_chats(username, &cached).await?;
+            snapshot.borrow_mut().chats.extend(chats);
+        }
+        Ok(())
+    }
+
+    /// Deletes the user's cache, e.g. on logout.
+    pub async fn clear(username: &str) -> Result<(), String> {
+        let factory = web_sys::window()
+            .ok_or("No window")?
+            .indexed_db()
+            .map_err(|e| format!("{:?}", e))?
+            .ok_or("IndexedDB is not available")?;
+        let request = factory
+            .delete_database(&db_name(username))
+            .map_err(|e| format!("{:?}", e))?;
+        wait_for_request(&request).await.map(|_| ())
+    }
+}
+
+fn db_name(username: &str) -> String {
+    format!("{}:{}", DB_PREFIX, username)
+}
+
+async fn open(username: &str) -> Result<IdbDatabase, String> {
+    let factory = web_sys::window()
+        .ok_or("No window")?
+        .indexed_db()
+        .map_err(|e| format!("{:?}", e))?
+        .ok_or("IndexedDB is not available")?;
+    let request = factory
+        .open_with_u32(&db_name(username), DB_VERSION)
+        .map_err(|e| format!("{:?}", e))?;
+
+    let onupgradeneeded =
+        Closure::<dyn FnMut(IdbVersionChangeEvent)>::new(move |e: IdbVersionChangeEvent| {
+            let Some(db) = e
+                .target()
+                .and_then(|t| t.dyn_into::<IdbOpenDbRequest>().ok())
+                .and_then(|r| r.result().ok())
+                .and_then(|r| r.dyn_into::<IdbDatabase>().ok())
+            else {
+                return;
+            };
+            let from = (e.old_version() as usize).min(MIGRATIONS.len());
+            for migrate in &MIGRATIONS[from..] {
+                if let Err(e) = migrate(&db) {
+                    web_sys::console::error_1(&e);
+                }
+            }
+        });
+    request.set_onupgradeneeded(Some(onupgradeneeded.as_ref().unchecked_ref()));
+
+    let result = wait_for_request(&request).await;
+    request.set_onupgradeneeded(None);
+    result?
+        .dyn_into::<IdbDatabase>()
+        .map_err(|_| "Unexpected IndexedDB open result".to_string())
+}
+
+/// Also closes the connection; that waits for the transaction, and an open
+/// connection would block upgrades and `clear`.
+fn transaction(
+    db: &IdbDatabase,
+    stores: &[&str],
+    mode: IdbTransactionMode,
+) -> Result<IdbTransaction, String> {
+    let names: js_sys::Array = stores.iter().map(|s| JsValue::from_str(s)).collect();
+    let tx = db
+        .transaction_with_str_sequence_and_mode(&names, mode)
+        .map_err(|e| format!("{:?}", e));
+    db.close();
+    tx
+}
+
+fn store(tx: &IdbTransaction, name: &str) -> Result<IdbObjectStore, String> {
+    tx.object_store(name).map_err(|e| format!("{:?}", e))
+}
+
+fn put<T: Serialize>(store: &IdbObjectStore, key: &str, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string(value).map_err(|e| e.to_string())?;
+    store
+        .put_with_key(&JsValue::from_str(&json), &JsValue::from_str(key))
+        .map_err(|e| format!("{:?}", e))?;
+    Ok(())
+}
+
+fn replace_all<'a, T: Serialize + 'a>(
+    store: &IdbObjectStore,
+    items: impl IntoIterator<Item = (&'a str, &'a T)>,
+) -> Result<(), String> {
+    store.clear().map_err(|e| format!("{:?}", e))?;
+    for (key, value) in items {
+        put(store, key, value)?;
+    }
+    Ok(())
+}
+
+fn get_all(store: &IdbObjectStore) -> Result<IdbRequest, String> {
+    store.get_all().map_err(|e| format!("{:?}", e))
+}
+
+/// Entries that no longer deserialize (e.g. written by an older build) are
+/// skipped rather than failing the whole load.
+async fn read_all<T: DeserializeOwned>(request: &IdbRequest) -> Result<Vec<T>, String> {
+    let values: js_sys::Array = wait_for_request(request)
+        .await?
+        .dyn_into()
+        .map_err(|_| "Unexpected IndexedDB result".to_string())?;
+    Ok(values
+        .iter()
+        .filter_map(|v| v.as_string())
+        .filter_map(|json| serde_json::from_str(&json).ok())
+        .collect())
+}
+
+async fn wait_for_request(request: &IdbRequest) -> Result<JsValue, String> {
+    let (tx, rx) = oneshot::channel::<Result<JsValue, String>>();
+    let tx = Rc::new(RefCell::new(Some(tx)));
+
+    let onsuccess = {
+        let tx = tx.clone();
+        let request = request.clone();
+        Closure::<dyn FnMut()>::new(move || {
+            if let Some(tx) = tx.borrow_mut().take() {
+                let _ = tx.send(request.result().map_err(|e| format!("{:?}", e)));
+            }
+        })
+    };
+    let onerror = {
+        let request = request.clone();
+        Closure::<dyn FnMut()>::new(move || {
+            if let Some(tx) = tx.borrow_mut().take() {
+                let message = request
+                    .error()
+                    .ok()
+                    .flatten()
+                    .map(|e| e.message())
+                    .unwrap_or_else(|| "IndexedDB request failed".to_string());
+                let _ = tx.send(Err(message));
+            }
+        })
+    };
+    request.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
+    request.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+
+    let result = rx
+        .await
+        .unwrap_or_else(|_| Err("IndexedDB request was dropped".to_string()));
+    request.set_onsuccess(None);
+    request.set_onerror(None);
+    result
+}
+
+async fn wait_for_transaction(transaction: &IdbTransaction) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel::<Result<(), String>>();
+    let tx = Rc::new(RefCell::new(Some(tx)));
+
+    let oncomplete = {
+        let tx = tx.clone();
+        Closure::<dyn FnMut()>::new(move || {
+            if let Some(tx) = tx.borrow_mut().take() {
+                let _ = tx.send(Ok(()));
+            }
+        })
+    };
+    let onfailed = {
+        let transaction = transaction.clone();
+        Closure::<dyn FnMut()>::new(move || {
+            if let Some(tx) = tx.borrow_mut().take() {
+                let message = transaction
+                    .error()
+                    .map(|e| e.message())
+                    .unwrap_or_else(|| "IndexedDB transaction failed".to_string());
+                let _ = tx.send(Err(message));
+            }
+        })
+    };
+    transaction.set_oncomplete(Some(oncomplete.as_ref().unchecked_ref()));
+    transaction.set_onerror(Some(onfailed.as_ref().unchecked_ref()));
+    transaction.set_onabort(Some(onfailed.as_ref().unchecked_ref()));
+
+    rx.await
+        .unwrap_or_else(|_| Err("IndexedDB transaction was dropped".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::{MessageStatus, MessageType};
+
+    fn message(id: usize, status: MessageStatus) -> Message {
+        Message {
+            message_id: id.to_string(),
+            client_message_id: None,
+            content: String::new(),
+            sender: "alice".to_string(),
+            timestamp: id as f64,
+            sequence: Some(id as u64),
+            status,
+            message_type: MessageType::Text,
+            chat_id: "CHAT#alice#bob".to_string(),
+            attachment: None,
+            mentions: Vec::new(),
+            imported_by: None,
+            upload_progress: None,
+        }
+    }
+
+    #[test]
+    fn cached_chat_keeps_newest_sent_messages() {
+        let mut messages: Vec<_> = (0..CACHED_MESSAGES_PER_CHAT + 5)
+            .map(|i| message(i, MessageStatus::Sent))
+            .collect();
+        messages.push(message(999, MessageStatus::Sending));
+
+        let cached = CachedChat::new("CHAT#alice#bob", &messages, Some("cursor".to_string()));
+        assert_eq!(cached.messages.len(), CACHED_MESSAGES_PER_CHAT);
+        assert_eq!(cached.messages[0].message_id, "5");
+        assert!(cached.history_cursor.is_none());
+
+        let cached = CachedChat::new("CHAT#alice#bob", &messages[..3], Some("cursor".to_string()));
+        assert_eq!(cached.history_cursor.as_deref(), Some("cursor"));
+    }
+}
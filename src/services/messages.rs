@@ -0,0 +1,427 @@
+use std::future::Future;
+
+use crate::graphql::mutations::{
+    CreateMessageResponse, CreateMessageVariables, ImportMessageInput, ImportMessagesResponse,
+    ImportMessagesVariables, SendTypingVariables, UpdateLastSeenResponse, UpdateLastSeenVariables,
+    CREATE_MESSAGE_MUTATION, IMPORT_MESSAGES_MUTATION, MARK_CONVERSATION_READ_MUTATION,
+    SEND_TYPING_MUTATION, UPDATE_LAST_SEEN_MUTATION,
+};
+use crate::graphql::queries::{
+    GetConversationResponse, GetReadReceiptsResponse, ListConversationsResponse,
+    SearchMessagesResponse, GET_CONVERSATION_QUERY, GET_READ_RECEIPTS_QUERY,
+    LIST_CONVERSATIONS_QUERY, SEARCH_MESSAGES_QUERY,
+};
+use crate::graphql::types::GraphQLError;
+use crate::models::conversation::Conversation;
+use crate::models::message::Message;
+use crate::models::receipt::ReadReceipt;
+use crate::utils::graphql_client::{self, GraphQLClient};
+
+/// Messages per history request.
+const HISTORY_PAGE_SIZE: u32 = 50;
+/// The most getConversation returns at once.
+const EXPORT_PAGE_SIZE: u32 = 100;
+/// The most listConversations returns at once.
+const CONVERSATION_PAGE_SIZE: u32 = 100;
+/// The most importMessages accepts per call (DynamoDB's batch write limit).
+pub const IMPORT_BATCH_SIZE: usize = 25;
+/// Rounds of resending throttled items before giving up.
+const IMPORT_RETRIES: u32 = 3;
+/// Resends after a transient failure before giving up.
+const SEND_RETRIES: u32 = 3;
+/// Delay before the first resend; doubled for each one after.
+const SEND_RETRY_BASE_MS: u32 = 1000;
+
+/// Why a send didn't go through. Only transient failures (network,
+/// throttling, 5xx) are retried; a rejected message would fail again.
+pub enum SendError {
+    Transient(String),
+    Rejected(String),
+}
+
+impl SendError {
+    /// Classifies an `execute_query` failure.
+    pub fn from_request(error: Box<dyn std::error::Error>) -> Self {
+        if graphql_client::is_transient(&*error) {
+            Self::Transient(error.to_string())
+        } else {
+            Self::Rejected(error.to_string())
+        }
+    }
+
+    /// Classifies an error AppSync returned for the operation.
+    pub fn from_graphql(error: GraphQLError) -> Self {
+        if error.is_transient() {
+            Self::Transient(error.message)
+        } else {
+            Self::Rejected(error.message)
+        }
+    }
+}
+
+/// Runs `attempt` until it succeeds, retrying transient failures with
+/// backoff. Gives up with the last error after `SEND_RETRIES` resends.
+pub async fn retry_transient<T, F, Fut>(mut attempt: F) -> Result<T, String>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, SendError>>,
+{
+    let mut retries = 0;
+    loop {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(SendError::Transient(_)) if retries < SEND_RETRIES => {
+                gloo_timers::future::TimeoutFuture::new(SEND_RETRY_BASE_MS * 2u32.pow(retries))
+                    .await;
+                retries += 1;
+            }
+            Err(SendError::Transient(e) | SendError::Rejected(e)) => return Err(e),
+        }
+    }
+}
+
+pub struct MessageService;
+
+impl MessageService {
+    /// Sends `msg`, retrying transient failures. The client message id makes
+    /// resends safe. Returns the message as stored by the server.
+    pub async fn send(
+        msg: &Message,
+        receiver_username: &str,
+        token: &str,
+    ) -> Result<Message, String> {
+        retry_transient(|| Self::create_message(msg, receiver_username, token)).await
+    }
+
+    async fn create_message(
+        msg: &Message,
+        receiver_username: &str,
+        token: &str,
+    ) -> Result<Message, SendError> {
+        let client = GraphQLClient::new()
+            .await
+            .map_err(|e| SendError::Rejected(e.to_string()))?
+            .with_token(token.to_string());
+
+        let variables = CreateMessageVariables {
+            content: msg.content.clone(),
+            receiver_username: receiver_username.to_string(),
+            message_type: msg.attachment.as_ref().map(|_| msg.message_type.clone()),
+            attachment: msg.attachment.clone(),
+            mentions: msg.mentions.clone(),
+            client_message_id: msg.client_message_id.clone(),
+        };
+
+        let response = client
+            .execute_query::<_, CreateMessageResponse>(
+                "CreateMessage",
+                CREATE_MESSAGE_MUTATION,
+                variables,
+            )
+            .await
+            .map_err(SendError::from_request)?;
+
+        if let Some(data) = response.data {
+            Ok(Message::from_message_data(data.create_message))
+        } else if let Some(error) = response.errors.and_then(|errors| errors.into_iter().next()) {
+            Err(SendError::from_graphql(error))
+        } else {
+            Err(SendError::Rejected("Unknown error occurred".to_string()))
+        }
+    }
+
+    /// Fetches one page of a conversation, starting at the newest message when
+    /// `next_token` is `None`. Returns the messages and the cursor to older ones.
+    pub async fn fetch_page(
+        other_username: &str,
+        next_token: Option<String>,
+        token: &str,
+    ) -> Result<(Vec<Message>, Option<String>), String> {
+        Self::fetch_page_of(other_username, next_token, HISTORY_PAGE_SIZE, token).await
+    }
+
+    /// Pages through a conversation's whole history, oldest message first.
+    pub async fn fetch_full_history(
+        other_username: &str,
+        token: &str,
+    ) -> Result<Vec<Message>, String> {
+        let mut messages = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (page, next_token) =
+                Self::fetch_page_of(other_username, cursor, EXPORT_PAGE_SIZE, token).await?;
+            messages.extend(page);
+            match next_token {
+                Some(token) => cursor = Some(token),
+                None => break,
+            }
+        }
+        Message::sort_chat(&mut messages);
+        Ok(messages)
+    }
+
+    async fn fetch_page_of(
+        other_username: &str,
+        next_token: Option<String>,
+        limit: u32,
+        token: &str,
+    ) -> Result<(Vec<Message>, Option<String>), String> {
+        let client = GraphQLClient::new()
+            .await
+            .map_err(|e| e.to_string())?
+            .with_token(token.to_string());
+
+        let variables = serde_json::json!({
+            "otherUsername": other_username,
+            "limit": limit,
+            "nextToken": next_token,
+        });
+
+        let response = client
+            .execute_query::<_, GetConversationResponse>(
+                "GetConversation",
+                GET_CONVERSATION_QUERY,
+                variables,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(data) = response.data {
+            let page = data.get_conversation;
+            let messages = page
+                .items
+                .into_iter()
+                .map(Message::from_message_data)
+                .collect();
+            Ok((messages, page.next_token))
+        } else if let Some(errors) = response.errors {
+            Err(errors[0].message.clone())
+        } else {
+            Ok((Vec::new(), None))
+        }
+    }
+
+    /// Full-text search on the server, across every chat we're in.
+    pub async fn search(query: &str, limit: usize, token: &str) -> Result<Vec<Message>, String> {
+        let client = GraphQLClient::new()
+            .await
+            .map_err(|e| e.to_string())?
+            .with_token(token.to_string());
+
+        let variables = serde_json::json!({
+            "query": query,
+            "limit": limit,
+        });
+
+        let response = client
+            .execute_query::<_, SearchMessagesResponse>(
+                "SearchMessages",
+                SEARCH_MESSAGES_QUERY,
+                variables,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(data) = response.data {
+            Ok(data
+                .search_messages
+                .items
+                .into_iter()
+                .map(Message::from_message_data)
+                .collect())
+        } else if let Some(errors) = response.errors {
+            Err(errors[0].message.clone())
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Writes one batch of imported messages, resending throttled items.
+    pub async fn import_batch(chat_id: &str, batch: &[Message], token: &str) -> Result<(), String> {
+        let client = GraphQLClient::new()
+            .await
+            .map_err(|e| e.to_string())?
+            .with_token(token.to_string());
+
+        let mut pending: Vec<&Message> = batch.iter().collect();
+        for attempt in 0..=IMPORT_RETRIES {
+            if attempt > 0 {
+                gloo_timers::future::TimeoutFuture::new(500 * 2u32.pow(attempt - 1)).await;
+            }
+            let variables = ImportMessagesVariables {
+                chat_id: chat_id.to_string(),
+                messages: pending
+                    .iter()
+                    .map(|msg| ImportMessageInput {
+                        message_id: msg.message_id.clone(),
+                        sender: msg.sender.clone(),
+                        content: msg.content.clone(),
+                        timestamp: msg.timestamp,
+                    })
+                    .collect(),
+            };
+            let response = client
+                .execute_query::<_, ImportMessagesResponse>(
+                    "ImportMessages",
+                    IMPORT_MESSAGES_MUTATION,
+                    variables,
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let result = match (response.data, response.errors) {
+                (Some(data), _) => data.import_messages,
+                (None, Some(errors)) => return Err(errors[0].message.clone()),
+                (None, None) => return Err("Unknown error occurred".to_string()),
+            };
+            pending.retain(|msg| result.unprocessed.contains(&msg.message_id));
+            if pending.is_empty() {
+                return Ok(());
+            }
+        }
+        Err(format!("{} messages were throttled", pending.len()))
+    }
+
+    /// Every conversation the user is in, fetched a page at a time.
+    pub async fn list_conversations(token: &str) -> Result<Vec<Conversation>, String> {
+        let client = GraphQLClient::new()
+            .await
+            .map_err(|e| e.to_string())?
+            .with_token(token.to_string());
+
+        let mut conversations = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let variables = serde_json::json!({
+                "limit": CONVERSATION_PAGE_SIZE,
+                "nextToken": cursor,
+            });
+            let response = client
+                .execute_query::<_, ListConversationsResponse>(
+                    "ListConversations",
+                    LIST_CONVERSATIONS_QUERY,
+                    variables,
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let page = match (response.data, response.errors) {
+                (Some(data), _) => data.list_conversations,
+                (None, Some(errors)) => return Err(errors[0].message.clone()),
+                (None, None) => return Err("Unknown error occurred".to_string()),
+            };
+            conversations.extend(
+                page.items
+                    .into_iter()
+                    .map(Conversation::from_conversation_data),
+            );
+            match page.next_token {
+                Some(token) => cursor = Some(token),
+                None => return Ok(conversations),
+            }
+        }
+    }
+
+    /// Advances our delivery and/or read cursor for a chat.
+    pub async fn update_last_seen(
+        chat_id: String,
+        delivered_up_to: Option<f64>,
+        read_up_to: Option<f64>,
+        token: &str,
+    ) -> Result<Option<ReadReceipt>, String> {
+        let client = GraphQLClient::new()
+            .await
+            .map_err(|e| e.to_string())?
+            .with_token(token.to_string());
+
+        let variables = UpdateLastSeenVariables {
+            chat_id,
+            delivered_up_to,
+            read_up_to,
+        };
+
+        let response = client
+            .execute_query::<_, UpdateLastSeenResponse>(
+                "UpdateLastSeen",
+                UPDATE_LAST_SEEN_MUTATION,
+                variables,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(data) = response.data {
+            Ok(data.update_last_seen)
+        } else if let Some(errors) = response.errors {
+            Err(errors[0].message.clone())
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn mark_conversation_read(chat_id: String, token: &str) -> Result<(), String> {
+        let client = GraphQLClient::new()
+            .await
+            .map_err(|e| e.to_string())?
+            .with_token(token.to_string());
+
+        let response = client
+            .execute_query::<_, serde_json::Value>(
+                "MarkConversationRead",
+                MARK_CONVERSATION_READ_MUTATION,
+                serde_json::json!({ "chatId": chat_id }),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        match response.errors.and_then(|errors| errors.into_iter().next()) {
+            Some(error) => Err(error.message),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn fetch_read_receipts(
+        chat_id: &str,
+        token: &str,
+    ) -> Result<Vec<ReadReceipt>, String> {
+        let client = GraphQLClient::new()
+            .await
+            .map_err(|e| e.to_string())?
+            .with_token(token.to_string());
+
+        let response = client
+            .execute_query::<_, GetReadReceiptsResponse>(
+                "GetReadReceipts",
+                GET_READ_RECEIPTS_QUERY,
+                serde_json::json!({ "chatId": chat_id }),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(data) = response.data {
+            Ok(data.get_read_receipts)
+        } else if let Some(errors) = response.errors {
+            Err(errors[0].message.clone())
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    pub async fn send_typing(chat_id: String, is_typing: bool, token: &str) -> Result<(), String> {
+        let client = GraphQLClient::new()
+            .await
+            .map_err(|e| e.to_string())?
+            .with_token(token.to_string());
+
+        let variables = SendTypingVariables { chat_id, is_typing };
+
+        let response = client
+            .execute_query::<_, serde_json::Value>("SendTyping", SEND_TYPING_MUTATION, variables)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        match response.errors.and_then(|errors| errors.into_iter().next()) {
+            Some(error) => Err(error.message),
+            None => Ok(()),
+        }
+    }
+}
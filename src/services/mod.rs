@@ -1 +1,9 @@
 pub mod auth;
+pub mod cache;
+pub mod messages;
+pub mod notifications;
+pub mod presence;
+pub mod push;
+pub mod service_worker;
+pub mod upload;
+pub mod users;
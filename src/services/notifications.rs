@@ -0,0 +1,270 @@
+use gloo::storage::{LocalStorage, Storage};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{HtmlLinkElement, Notification, NotificationOptions, NotificationPermission};
+
+use crate::graphql::mutations::{
+    SET_CHAT_NOTIFICATION_SETTING_MUTATION, SET_DO_NOT_DISTURB_MUTATION,
+};
+use crate::graphql::queries::{GetNotificationSettingsResponse, GET_NOTIFICATION_SETTINGS_QUERY};
+use crate::models::message::Message;
+use crate::models::notification_settings::{
+    parse_time, ChatNotificationSetting, DoNotDisturb, NotificationSettings,
+};
+use crate::utils::graphql_client::GraphQLClient;
+
+/// Longest message preview shown in a notification, in characters.
+const PREVIEW_CHARS: usize = 100;
+/// Set once the user has turned down our permission prompt.
+const PROMPT_DISMISSED_KEY: &str = "notifications_prompt_dismissed";
+const APP_TITLE: &str = "Rusty Chat Sync";
+const APP_ICON: &str = "public/aws.svg";
+
+pub struct NotificationService;
+
+impl NotificationService {
+    pub fn is_supported() -> bool {
+        web_sys::window().is_some_and(|w| {
+            js_sys::Reflect::has(&w, &JsValue::from_str("Notification")).unwrap_or(false)
+        })
+    }
+
+    pub fn permission() -> NotificationPermission {
+        if Self::is_supported() {
+            Notification::permission()
+        } else {
+            NotificationPermission::Denied
+        }
+    }
+
+    /// Whether to offer the permission prompt: the browser hasn't been asked
+    /// yet and the user hasn't said "not now".
+    pub fn should_prompt() -> bool {
+        Self::permission() == NotificationPermission::Default
+            && LocalStorage::get::<bool>(PROMPT_DISMISSED_KEY).is_err()
+    }
+
+    pub fn dismiss_prompt() {
+        let _ = LocalStorage::set(PROMPT_DISMISSED_KEY, true);
+    }
+
+    /// Must run from a user gesture, or browsers ignore it.
+    pub async fn request_permission() -> Result<NotificationPermission, String> {
+        let promise = Notification::request_permission().map_err(|e| format!("{:?}", e))?;
+        let result = wasm_bindgen_futures::JsFuture::from(promise)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        Ok(
+            NotificationPermission::from_js_value(&result)
+                .unwrap_or(NotificationPermission::Denied),
+        )
+    }
+
+    /// Shows `msg` while the tab is hidden, if `settings` allow it.
+    /// Messages from one chat replace each other's notification. Clicking
+    /// focuses the tab and calls `on_click`.
+    pub fn notify(
+        msg: &Message,
+        username: &str,
+        settings: &NotificationSettings,
+        on_click: impl FnOnce() + 'static,
+    ) {
+        let hidden = web_sys::window()
+            .and_then(|w| w.document())
+            .is_some_and(|d| d.hidden());
+        if !hidden
+            || msg.sender == username
+            || Self::permission() != NotificationPermission::Granted
+            || !settings.allows(
+                msg,
+                username,
+                js_sys::Date::now(),
+                settings
+                    .do_not_disturb
+                    .as_ref()
+                    .and_then(|dnd| local_minutes(&dnd.time_zone)),
+            )
+        {
+            return;
+        }
+
+        let options = NotificationOptions::new();
+        options.set_body(&preview(msg));
+        options.set_tag(&msg.chat_id);
+        options.set_icon(APP_ICON);
+        let Ok(notification) = Notification::new_with_options(&msg.sender, &options) else {
+            return;
+        };
+
+        let target = notification.clone();
+        let onclick = Closure::once_into_js(move || {
+            if let Some(window) = web_sys::window() {
+                let _ = window.focus();
+            }
+            target.close();
+            on_click();
+        });
+        notification.set_onclick(Some(onclick.unchecked_ref()));
+    }
+
+    pub async fn load_settings(token: &str) -> Result<NotificationSettings, String> {
+        let response = client(token)
+            .await?
+            .execute_query::<_, GetNotificationSettingsResponse>(
+                "GetNotificationSettings",
+                GET_NOTIFICATION_SETTINGS_QUERY,
+                serde_json::json!({}),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        match (response.data, response.errors) {
+            (Some(data), _) => Ok(data.get_notification_settings),
+            (None, Some(errors)) => Err(errors[0].message.clone()),
+            (None, None) => Ok(NotificationSettings::default()),
+        }
+    }
+
+    pub async fn save_chat_setting(
+        token: &str,
+        setting: &ChatNotificationSetting,
+    ) -> Result<(), String> {
+        Self::mutate(
+            token,
+            "SetChatNotificationSetting",
+            SET_CHAT_NOTIFICATION_SETTING_MUTATION,
+            setting,
+        )
+        .await
+    }
+
+    pub async fn save_do_not_disturb(token: &str, dnd: &DoNotDisturb) -> Result<(), String> {
+        Self::mutate(token, "SetDoNotDisturb", SET_DO_NOT_DISTURB_MUTATION, dnd).await
+    }
+
+    async fn mutate(
+        token: &str,
+        operation: &str,
+        query: &str,
+        variables: impl serde::Serialize,
+    ) -> Result<(), String> {
+        let response = client(token)
+            .await?
+            .execute_query::<_, serde_json::Value>(operation, query, variables)
+            .await
+            .map_err(|e| e.to_string())?;
+        match response.errors {
+            Some(errors) => Err(errors[0].message.clone()),
+            None => Ok(()),
+        }
+    }
+
+    /// This browser's IANA time zone, the default for quiet hours.
+    pub fn browser_time_zone() -> String {
+        let format =
+            js_sys::Intl::DateTimeFormat::new(&js_sys::Array::new(), &js_sys::Object::new());
+        js_sys::Reflect::get(&format.resolved_options(), &"timeZone".into())
+            .ok()
+            .and_then(|tz| tz.as_string())
+            .unwrap_or_else(|| "UTC".to_string())
+    }
+
+    /// Puts the unread count in the tab title and favicon.
+    pub fn set_unread_badge(count: i32) {
+        let Some(document) = web_sys::window().and_then(|w| w.document()) else {
+            return;
+        };
+        if count > 0 {
+            document.set_title(&format!("({}) {}", count, APP_TITLE));
+        } else {
+            document.set_title(APP_TITLE);
+        }
+
+        let Some(link) = document
+            .query_selector("link[rel~='icon']")
+            .ok()
+            .flatten()
+            .and_then(|e| e.dyn_into::<HtmlLinkElement>().ok())
+        else {
+            return;
+        };
+        if count > 0 {
+            link.set_href(&badge_icon(count));
+        } else {
+            link.set_href(APP_ICON);
+        }
+    }
+}
+
+async fn client(token: &str) -> Result<GraphQLClient, String> {
+    Ok(GraphQLClient::new()
+        .await
+        .map_err(|e| e.to_string())?
+        .with_token(token.to_string()))
+}
+
+/// Minutes after midnight right now in `time_zone`, or `None` if the browser
+/// doesn't know the zone.
+fn local_minutes(time_zone: &str) -> Option<u32> {
+    let options = js_sys::Object::new();
+    for (key, value) in [
+        ("timeZone", time_zone),
+        ("hour", "2-digit"),
+        ("minute", "2-digit"),
+        ("hourCycle", "h23"),
+    ] {
+        js_sys::Reflect::set(&options, &key.into(), &value.into()).ok()?;
+    }
+    // Constructed through Reflect: unknown zones throw a RangeError
+    let intl = js_sys::Reflect::get(&js_sys::global(), &"Intl".into()).ok()?;
+    let constructor: js_sys::Function = js_sys::Reflect::get(&intl, &"DateTimeFormat".into())
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    let format =
+        js_sys::Reflect::construct(&constructor, &js_sys::Array::of2(&"en-GB".into(), &options))
+            .ok()?;
+    let time = js_sys::Reflect::get(&format, &"format".into())
+        .ok()?
+        .dyn_into::<js_sys::Function>()
+        .ok()?
+        .call1(&format, &js_sys::Date::new_0())
+        .ok()?
+        .as_string()?;
+    parse_time(&time)
+}
+
+fn preview(msg: &Message) -> String {
+    if let Some(attachment) = &msg.attachment {
+        return format!("📎 {}", attachment.file_name);
+    }
+    let mut preview: String = msg.content.chars().take(PREVIEW_CHARS).collect();
+    if msg.content.chars().nth(PREVIEW_CHARS).is_some() {
+        preview.push('…');
+    }
+    preview
+}
+
+/// A round badge with the count, as an SVG data URL.
+fn badge_icon(count: i32) -> String {
+    let label = if count > 99 {
+        "99+".to_string()
+    } else {
+        count.to_string()
+    };
+    let font_size = match label.len() {
+        1 => 40,
+        2 => 32,
+        _ => 24,
+    };
+    let svg = format!(
+        "<svg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 64 64'>\
+         <circle cx='32' cy='32' r='30' fill='#ae445a'/>\
+         <text x='32' y='32' dy='.35em' text-anchor='middle' font-family='sans-serif' \
+         font-weight='bold' font-size='{}' fill='#fff'>{}</text></svg>",
+        font_size, label
+    );
+    format!(
+        "data:image/svg+xml,{}",
+        String::from(js_sys::encode_uri_component(&svg))
+    )
+}
@@ -0,0 +1,88 @@
+use wasm_bindgen::JsValue;
+use web_sys::{Headers, Request, RequestInit};
+
+use crate::graphql::mutations::{
+    UpdateUserStatusResponse, UpdateUserStatusVariables, UPDATE_USER_STATUS_MUTATION,
+};
+use crate::graphql::types::{GraphQLRequest, UserStatusData};
+use crate::models::user::UserStatus;
+use crate::utils::config::CONFIG;
+use crate::utils::graphql_client::GraphQLClient;
+
+/// Mark the user away after this long without input.
+pub const IDLE_TIMEOUT_MS: f64 = 5.0 * 60.0 * 1000.0;
+/// How often the current status is re-sent to keep `lastSeen` fresh. Must be
+/// well below `PRESENCE_STALE_MS`.
+pub const HEARTBEAT_MS: u32 = 60 * 1000;
+
+pub struct PresenceService;
+
+impl PresenceService {
+    pub async fn set_status(status: UserStatus, token: &str) -> Result<UserStatusData, String> {
+        let client = GraphQLClient::new()
+            .await
+            .map_err(|e| e.to_string())?
+            .with_token(token.to_string());
+
+        let variables = UpdateUserStatusVariables {
+            status: status.to_string(),
+        };
+
+        let response = client
+            .execute_query::<_, UpdateUserStatusResponse>(
+                "UpdateUserStatus",
+                UPDATE_USER_STATUS_MUTATION,
+                variables,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(data) = response.data.and_then(|d| d.update_user_status) {
+            Ok(data)
+        } else if let Some(errors) = response.errors {
+            Err(errors[0].message.clone())
+        } else {
+            Err("Unknown error occurred".to_string())
+        }
+    }
+
+    /// Sends "offline" in a way that survives the page going away.
+    ///
+    /// `navigator.sendBeacon` can't carry the Authorization header AppSync
+    /// needs, so this uses a `keepalive` fetch, which the browser also lets
+    /// outlive the page. Fire and forget: there is nobody left to handle the
+    /// response.
+    pub fn send_offline_beacon(token: &str) {
+        let Some(window) = web_sys::window() else {
+            return;
+        };
+
+        let body = GraphQLRequest {
+            query: UPDATE_USER_STATUS_MUTATION.to_string(),
+            variables: UpdateUserStatusVariables {
+                status: UserStatus::Offline.to_string(),
+            },
+            operation_name: "UpdateUserStatus".to_string(),
+        };
+        let Ok(body) = serde_json::to_string(&body) else {
+            return;
+        };
+
+        let Ok(headers) = Headers::new() else {
+            return;
+        };
+        let _ = headers.set("Content-Type", "application/json");
+        let _ = headers.set("Authorization", token);
+
+        let init = RequestInit::new();
+        init.set_method("POST");
+        init.set_headers(&headers);
+        init.set_body(&JsValue::from_str(&body));
+        // Not exposed by this web-sys version
+        let _ = js_sys::Reflect::set(&init, &"keepalive".into(), &JsValue::TRUE);
+
+        if let Ok(request) = Request::new_with_str_and_init(&CONFIG.graphql_endpoint, &init) {
+            let _ = window.fetch_with_request(&request);
+        }
+    }
+}
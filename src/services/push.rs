@@ -0,0 +1,162 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use gloo::storage::{LocalStorage, Storage};
+use serde::Deserialize;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{PushSubscription, PushSubscriptionOptionsInit};
+
+use crate::graphql::mutations::{
+    RegisterPushSubscriptionVariables, REGISTER_PUSH_SUBSCRIPTION_MUTATION,
+    UNREGISTER_PUSH_SUBSCRIPTION_MUTATION,
+};
+use crate::services::service_worker::ServiceWorkerService;
+use crate::utils::config::CONFIG;
+use crate::utils::graphql_client::GraphQLClient;
+
+/// Identifies this browser among the user's subscriptions.
+const DEVICE_ID_KEY: &str = "push_device_id";
+
+/// `PushSubscription.toJSON()`.
+#[derive(Deserialize)]
+struct SubscriptionJson {
+    endpoint: String,
+    keys: SubscriptionKeys,
+}
+
+#[derive(Deserialize)]
+struct SubscriptionKeys {
+    p256dh: String,
+    auth: String,
+}
+
+pub struct PushService;
+
+impl PushService {
+    pub fn is_supported() -> bool {
+        !CONFIG.vapid_public_key.is_empty()
+            && ServiceWorkerService::is_supported()
+            && web_sys::window()
+                .is_some_and(|w| js_sys::Reflect::has(&w, &"PushManager".into()).unwrap_or(false))
+    }
+
+    /// Subscribes this browser and stores the subscription for the user, so
+    /// messages reach them with no tab open. Needs notification permission.
+    pub async fn enable(token: &str) -> Result<(), String> {
+        if !Self::is_supported() {
+            return Ok(());
+        }
+        let registration = ServiceWorkerService::register().await?;
+        let push_manager = registration
+            .push_manager()
+            .map_err(|e| format!("{:?}", e))?;
+
+        let existing = JsFuture::from(
+            push_manager
+                .get_subscription()
+                .map_err(|e| format!("{:?}", e))?,
+        )
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+        let subscription: PushSubscription = if existing.is_null() {
+            let key = URL_SAFE_NO_PAD
+                .decode(&CONFIG.vapid_public_key)
+                .map_err(|e| e.to_string())?;
+            let options = PushSubscriptionOptionsInit::new();
+            options.set_user_visible_only(true);
+            options.set_application_server_key(&js_sys::Uint8Array::from(key.as_slice()));
+            JsFuture::from(
+                push_manager
+                    .subscribe_with_options(&options)
+                    .map_err(|e| format!("{:?}", e))?,
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?
+            .unchecked_into()
+        } else {
+            existing.unchecked_into()
+        };
+
+        let json: String = js_sys::JSON::stringify(&subscription)
+            .map_err(|e| format!("{:?}", e))?
+            .into();
+        let subscription: SubscriptionJson =
+            serde_json::from_str(&json).map_err(|e| e.to_string())?;
+
+        let variables = RegisterPushSubscriptionVariables {
+            device_id: device_id(),
+            endpoint: subscription.endpoint,
+            p256dh: subscription.keys.p256dh,
+            auth: subscription.keys.auth,
+        };
+        Self::mutate(
+            token,
+            "RegisterPushSubscription",
+            REGISTER_PUSH_SUBSCRIPTION_MUTATION,
+            variables,
+        )
+        .await
+    }
+
+    /// Stops pushes to this browser, e.g. on logout so the next user of the
+    /// device doesn't get them.
+    pub async fn disable(token: &str) -> Result<(), String> {
+        if !Self::is_supported() {
+            return Ok(());
+        }
+        Self::mutate(
+            token,
+            "UnregisterPushSubscription",
+            UNREGISTER_PUSH_SUBSCRIPTION_MUTATION,
+            serde_json::json!({ "deviceId": device_id() }),
+        )
+        .await?;
+
+        let registration = ServiceWorkerService::register().await?;
+        let push_manager = registration
+            .push_manager()
+            .map_err(|e| format!("{:?}", e))?;
+        let existing = JsFuture::from(
+            push_manager
+                .get_subscription()
+                .map_err(|e| format!("{:?}", e))?,
+        )
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+        if let Some(subscription) = existing.dyn_ref::<PushSubscription>() {
+            let promise = subscription.unsubscribe().map_err(|e| format!("{:?}", e))?;
+            JsFuture::from(promise)
+                .await
+                .map_err(|e| format!("{:?}", e))?;
+        }
+        Ok(())
+    }
+
+    async fn mutate(
+        token: &str,
+        operation: &str,
+        query: &str,
+        variables: impl serde::Serialize,
+    ) -> Result<(), String> {
+        let client = GraphQLClient::new()
+            .await
+            .map_err(|e| e.to_string())?
+            .with_token(token.to_string());
+        let response = client
+            .execute_query::<_, serde_json::Value>(operation, query, variables)
+            .await
+            .map_err(|e| e.to_string())?;
+        match response.errors {
+            Some(errors) => Err(errors[0].message.clone()),
+            None => Ok(()),
+        }
+    }
+}
+
+fn device_id() -> String {
+    LocalStorage::get(DEVICE_ID_KEY).unwrap_or_else(|_| {
+        let id = uuid::Uuid::new_v4().to_string();
+        let _ = LocalStorage::set(DEVICE_ID_KEY, &id);
+        id
+    })
+}
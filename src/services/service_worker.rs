@@ -0,0 +1,91 @@
+use gloo::events::EventListener;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{ServiceWorker, ServiceWorkerRegistration, ServiceWorkerState};
+use yew::Callback;
+
+const SERVICE_WORKER_URL: &str = "sw.js";
+/// How often a long-lived tab asks whether a new deployment is out.
+pub const UPDATE_CHECK_MS: u32 = 30 * 60 * 1000;
+
+pub struct ServiceWorkerService;
+
+impl ServiceWorkerService {
+    pub fn is_supported() -> bool {
+        web_sys::window().is_some_and(|w| {
+            js_sys::Reflect::has(&w.navigator(), &"serviceWorker".into()).unwrap_or(false)
+        })
+    }
+
+    /// Registers `sw.js`, or returns the existing registration.
+    pub async fn register() -> Result<ServiceWorkerRegistration, String> {
+        let container = web_sys::window()
+            .ok_or("No window")?
+            .navigator()
+            .service_worker();
+        JsFuture::from(container.register(SERVICE_WORKER_URL))
+            .await
+            .map(JsValue::unchecked_into)
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    /// Calls `on_update` once a new deployment's worker has precached its
+    /// assets and is waiting to take over. The listeners live as long as the
+    /// returned handle.
+    pub fn watch_for_updates(
+        registration: &ServiceWorkerRegistration,
+        on_update: Callback<()>,
+    ) -> EventListener {
+        // Installed by an earlier visit, and still waiting
+        if registration.waiting().is_some() {
+            on_update.emit(());
+        }
+        let watched = registration.clone();
+        EventListener::new(registration, "updatefound", move |_| {
+            let Some(worker) = watched.installing() else {
+                return;
+            };
+            // The first install isn't an update: there's nothing to replace
+            if watched.active().is_none() {
+                return;
+            }
+            let on_update = on_update.clone();
+            let installing = worker.clone();
+            EventListener::new(&worker, "statechange", move |_| {
+                if installing.state() == ServiceWorkerState::Installed {
+                    on_update.emit(());
+                }
+            })
+            .forget();
+        })
+    }
+
+    /// Asks the browser to look for a new `sw.js`.
+    pub fn check_for_update(registration: &ServiceWorkerRegistration) {
+        if let Ok(promise) = registration.update() {
+            wasm_bindgen_futures::spawn_local(async move {
+                let _ = JsFuture::from(promise).await;
+            });
+        }
+    }
+
+    /// Lets the waiting worker take over and reloads once it has, so the
+    /// page runs on the new deployment's assets.
+    pub fn apply_update(registration: &ServiceWorkerRegistration) {
+        let (Some(window), Some(waiting)) = (web_sys::window(), registration.waiting()) else {
+            return;
+        };
+        let container = window.navigator().service_worker();
+        EventListener::once(&container, "controllerchange", move |_| {
+            let _ = window.location().reload();
+        })
+        .forget();
+        post_skip_waiting(&waiting);
+    }
+}
+
+fn post_skip_waiting(worker: &ServiceWorker) {
+    let message = js_sys::Object::new();
+    let _ = js_sys::Reflect::set(&message, &"type".into(), &"skip-waiting".into());
+    let _ = worker.post_message(&message);
+}
@@ -0,0 +1,278 @@
+use futures::channel::oneshot;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Blob, BlobPropertyBag, File, HtmlImageElement, ProgressEvent, Url, XmlHttpRequest};
+
+use crate::graphql::mutations::{
+    CreateUploadUrlResponse, CreateUploadUrlVariables, CREATE_UPLOAD_URL_MUTATION,
+};
+use crate::graphql::types::UploadTarget;
+use crate::models::attachment::Attachment;
+use crate::models::message::Message;
+use crate::services::messages::{retry_transient, MessageService, SendError};
+use crate::utils::graphql_client::GraphQLClient;
+use crate::utils::image_processing;
+
+pub const MAX_UPLOAD_BYTES: u64 = 25 * 1024 * 1024;
+
+/// An attachment whose message hasn't been sent yet.
+#[derive(Clone)]
+pub enum PendingUpload {
+    /// Not uploaded yet; a retry processes and uploads the file.
+    File(File),
+    /// Already uploaded; a retry only resends the message.
+    Uploaded(Attachment),
+}
+
+pub struct UploadService;
+
+impl UploadService {
+    /// Uploads the message's pending file unless an earlier attempt already
+    /// did, then sends the message and returns the stored copy. The uploaded
+    /// attachment is kept in `pending` until the send succeeds, so a retry
+    /// only repeats the step that failed.
+    pub async fn send_attachment(
+        pending: &RefCell<HashMap<String, PendingUpload>>,
+        mut msg: Message,
+        receiver_username: &str,
+        token: &str,
+        on_progress: impl Fn(f64) + Clone + 'static,
+    ) -> Result<Message, String> {
+        let preview_url = msg.attachment.as_ref().and_then(|a| a.url.clone());
+
+        let upload = pending.borrow().get(&msg.message_id).cloned();
+        let attachment = match (upload, msg.attachment.take()) {
+            (Some(PendingUpload::Uploaded(attachment)), _) => attachment,
+            (Some(PendingUpload::File(file)), Some(mut attachment)) => {
+                Self::upload_attachment(&mut attachment, &file, token, on_progress).await?;
+                // Unless the message was discarded in the meantime
+                if let Some(upload) = pending.borrow_mut().get_mut(&msg.message_id) {
+                    *upload = PendingUpload::Uploaded(attachment.clone());
+                }
+                attachment
+            }
+            _ => return Err("The file is no longer available. Attach it again.".to_string()),
+        };
+        msg.attachment = Some(attachment);
+
+        let stored = MessageService::send(&msg, receiver_username, token).await?;
+        pending.borrow_mut().remove(&msg.message_id);
+        if let Some(url) = preview_url {
+            let _ = Url::revoke_object_url(&url);
+        }
+        Ok(stored)
+    }
+
+    /// Downscales processable images, fills in the attachment's final name,
+    /// size and dimensions, and uploads it, setting `attachment.key`. The
+    /// upload URL request and the upload itself are retried on transient
+    /// failures.
+    pub async fn upload_attachment(
+        attachment: &mut Attachment,
+        file: &File,
+        token: &str,
+        on_progress: impl Fn(f64) + Clone + 'static,
+    ) -> Result<(), String> {
+        let body: Blob = if image_processing::is_processable(&attachment.mime_type) {
+            let bytes = Self::read_bytes(file).await?;
+            let processed = image_processing::process_image(&bytes)
+                .map_err(|e| format!("Could not process {}: {}", attachment.file_name, e))?;
+
+            attachment.file_name =
+                image_processing::with_extension(&attachment.file_name, processed.extension);
+            attachment.mime_type = processed.mime_type.to_string();
+            attachment.size = processed.bytes.len() as u64;
+            attachment.width = Some(processed.width);
+            attachment.height = Some(processed.height);
+            attachment.placeholder = Some(processed.placeholder);
+
+            Self::blob_from_bytes(&processed.bytes, processed.mime_type)?
+        } else {
+            if let Some(url) = attachment.url.as_ref().filter(|_| attachment.is_image()) {
+                if let Some((width, height)) = Self::image_dimensions(url).await {
+                    attachment.width = Some(width);
+                    attachment.height = Some(height);
+                }
+            }
+            file.clone().into()
+        };
+
+        if attachment.size > MAX_UPLOAD_BYTES {
+            return Err(format!(
+                "{} is larger than {} MB",
+                attachment.file_name,
+                MAX_UPLOAD_BYTES / 1024 / 1024
+            ));
+        }
+
+        let target = retry_transient(|| {
+            Self::request_upload_target(
+                token,
+                &attachment.file_name,
+                &attachment.mime_type,
+                attachment.size,
+            )
+        })
+        .await?;
+
+        retry_transient(|| {
+            Self::upload(&target, &body, &attachment.mime_type, on_progress.clone())
+        })
+        .await?;
+        attachment.key = target.key;
+        Ok(())
+    }
+
+    pub async fn request_upload_target(
+        token: &str,
+        file_name: &str,
+        mime_type: &str,
+        size: u64,
+    ) -> Result<UploadTarget, SendError> {
+        let client = GraphQLClient::new()
+            .await
+            .map_err(|e| SendError::Rejected(e.to_string()))?
+            .with_token(token.to_string());
+
+        let variables = CreateUploadUrlVariables {
+            file_name: file_name.to_string(),
+            mime_type: mime_type.to_string(),
+            size,
+        };
+
+        let response = client
+            .execute_query::<_, CreateUploadUrlResponse>(
+                "CreateUploadUrl",
+                CREATE_UPLOAD_URL_MUTATION,
+                variables,
+            )
+            .await
+            .map_err(SendError::from_request)?;
+
+        if let Some(data) = response.data {
+            Ok(data.create_upload_url)
+        } else if let Some(error) = response.errors.and_then(|errors| errors.into_iter().next()) {
+            Err(SendError::from_graphql(error))
+        } else {
+            Err(SendError::Rejected("Unknown error occurred".to_string()))
+        }
+    }
+
+    /// PUTs the body to a presigned URL, reporting progress as a 0.0..=1.0 fraction.
+    /// Uses XHR rather than fetch because fetch has no upload progress events.
+    pub async fn upload(
+        target: &UploadTarget,
+        body: &Blob,
+        mime_type: &str,
+        on_progress: impl Fn(f64) + 'static,
+    ) -> Result<(), SendError> {
+        let setup_error = |e: JsValue| SendError::Rejected(format!("{:?}", e));
+        let xhr = XmlHttpRequest::new().map_err(setup_error)?;
+        xhr.open("PUT", &target.upload_url).map_err(setup_error)?;
+        xhr.set_request_header("Content-Type", mime_type)
+            .map_err(setup_error)?;
+
+        let (tx, rx) = oneshot::channel::<Result<(), SendError>>();
+        let tx = Rc::new(RefCell::new(Some(tx)));
+
+        let onprogress = Closure::<dyn FnMut(ProgressEvent)>::new(move |e: ProgressEvent| {
+            if e.length_computable() && e.total() > 0.0 {
+                on_progress(e.loaded() / e.total());
+            }
+        });
+
+        let onload = {
+            let tx = tx.clone();
+            let xhr = xhr.clone();
+            Closure::<dyn FnMut()>::new(move || {
+                let status = xhr.status().unwrap_or(0);
+                let error = format!("Upload failed with status {}", status);
+                let result = match status {
+                    200..=299 => Ok(()),
+                    408 | 429 | 500..=599 => Err(SendError::Transient(error)),
+                    _ => Err(SendError::Rejected(error)),
+                };
+                if let Some(tx) = tx.borrow_mut().take() {
+                    let _ = tx.send(result);
+                }
+            })
+        };
+
+        let onerror = {
+            let tx = tx.clone();
+            Closure::<dyn FnMut()>::new(move || {
+                if let Some(tx) = tx.borrow_mut().take() {
+                    let _ = tx.send(Err(SendError::Transient(
+                        "Upload failed: network error".to_string(),
+                    )));
+                }
+            })
+        };
+
+        let upload = xhr.upload().map_err(setup_error)?;
+        upload.set_onprogress(Some(onprogress.as_ref().unchecked_ref()));
+        xhr.set_onload(Some(onload.as_ref().unchecked_ref()));
+        xhr.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+
+        xhr.send_with_opt_blob(Some(body)).map_err(setup_error)?;
+
+        // The closures must stay alive until the request settles.
+        let result = rx
+            .await
+            .unwrap_or_else(|_| Err(SendError::Rejected("Upload was cancelled".to_string())));
+        drop((onprogress, onload, onerror));
+        result
+    }
+
+    /// Reads the natural size of an image by loading it from a local object URL.
+    pub async fn image_dimensions(object_url: &str) -> Option<(u32, u32)> {
+        let image = HtmlImageElement::new().ok()?;
+        let (tx, rx) = oneshot::channel::<bool>();
+        let tx = Rc::new(RefCell::new(Some(tx)));
+
+        let onload = {
+            let tx = tx.clone();
+            Closure::<dyn FnMut()>::new(move || {
+                if let Some(tx) = tx.borrow_mut().take() {
+                    let _ = tx.send(true);
+                }
+            })
+        };
+        let onerror = Closure::<dyn FnMut()>::new(move || {
+            if let Some(tx) = tx.borrow_mut().take() {
+                let _ = tx.send(false);
+            }
+        });
+
+        image.set_onload(Some(onload.as_ref().unchecked_ref()));
+        image.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+        image.set_src(object_url);
+
+        let loaded = rx.await.unwrap_or(false);
+        drop((onload, onerror));
+
+        loaded.then(|| (image.natural_width(), image.natural_height()))
+    }
+
+    pub fn create_object_url(file: &File) -> Option<String> {
+        Url::create_object_url_with_blob(file).ok()
+    }
+
+    pub async fn read_bytes(file: &File) -> Result<Vec<u8>, String> {
+        let buffer = wasm_bindgen_futures::JsFuture::from(file.array_buffer())
+            .await
+            .map_err(|e| format!("Failed to read {}: {:?}", file.name(), e))?;
+        Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+    }
+
+    pub fn blob_from_bytes(bytes: &[u8], mime_type: &str) -> Result<Blob, String> {
+        let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+        let options = BlobPropertyBag::new();
+        options.set_type(mime_type);
+        Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+            .map_err(|e| format!("{:?}", e))
+    }
+}
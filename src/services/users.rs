@@ -0,0 +1,42 @@
+use crate::graphql::queries::{SearchUsersResponse, SEARCH_USERS_QUERY};
+use crate::models::user::User;
+use crate::utils::graphql_client::GraphQLClient;
+
+const USER_PAGE_SIZE: u32 = 20;
+
+pub struct UserService;
+
+impl UserService {
+    /// Case-insensitive username prefix search. Returns the users and the
+    /// cursor to the next page.
+    pub async fn search(
+        prefix: &str,
+        next_token: Option<String>,
+        token: &str,
+    ) -> Result<(Vec<User>, Option<String>), String> {
+        let client = GraphQLClient::new()
+            .await
+            .map_err(|e| e.to_string())?
+            .with_token(token.to_string());
+
+        let variables = serde_json::json!({
+            "prefix": prefix,
+            "limit": USER_PAGE_SIZE,
+            "nextToken": next_token,
+        });
+
+        let response = client
+            .execute_query::<_, SearchUsersResponse>("SearchUsers", SEARCH_USERS_QUERY, variables)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(data) = response.data {
+            let page = data.search_users;
+            Ok((page.items, page.next_token))
+        } else if let Some(errors) = response.errors {
+            Err(errors[0].message.clone())
+        } else {
+            Ok((Vec::new(), None))
+        }
+    }
+}
@@ -1,30 +1,228 @@
+use crate::graphql::types::UserStatusData;
 use crate::models::conversation::Conversation;
 use crate::models::message::{Message, MessageStatus};
+use crate::models::receipt::ReadReceipt;
+use crate::models::typing::{TypingEvent, TypingUser};
 use crate::models::user::User;
+use crate::services::cache::CachedState;
+use crate::state::message_store::ChatMessages;
+use std::collections::HashMap;
 use std::rc::Rc;
 use yew::prelude::*;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ChatState {
-    pub messages: Vec<Message>,
+    /// Messages per chat id. Shared until modified, so reducing an action
+    /// doesn't copy every chat.
+    pub chats: HashMap<String, Rc<ChatMessages>>,
     pub conversations: Vec<Conversation>,
     pub is_loading: bool,
     pub error: Option<String>,
     pub current_chat_id: Option<String>,
     pub users: Vec<User>,
+    pub current_user: Option<String>,
+    /// Latest delivery/read cursors of other chat members.
+    pub receipts: Vec<ReadReceipt>,
+    pub typing: Vec<TypingUser>,
+}
+
+/// How long a typing indicator lasts without a fresh event.
+pub const TYPING_TIMEOUT_MS: f64 = 5000.0;
+
+/// The member of a `CHAT#<a>#<b>` chat who isn't `me`.
+fn other_member(chat_id: &str, me: &str) -> Option<String> {
+    let (a, b) = chat_id.strip_prefix("CHAT#")?.split_once('#')?;
+    Some(if a == me { b } else { a }.to_string())
+}
+
+impl ChatState {
+    pub fn current_chat(&self) -> Option<&ChatMessages> {
+        self.chats
+            .get(self.current_chat_id.as_ref()?)
+            .map(Rc::as_ref)
+    }
+
+    /// The open chat's store, shared rather than copied.
+    pub fn current_chat_rc(&self) -> Option<Rc<ChatMessages>> {
+        self.chats.get(self.current_chat_id.as_ref()?).cloned()
+    }
+
+    pub fn current_messages(&self) -> &[Message] {
+        self.current_chat().map_or(&[], ChatMessages::messages)
+    }
+
+    fn chat_mut(&mut self, chat_id: &str) -> &mut ChatMessages {
+        Rc::make_mut(self.chats.entry(chat_id.to_string()).or_default())
+    }
+
+    /// Applies `update` to the message with this id, in whichever chat it is.
+    fn update_message(&mut self, message_id: &str, update: impl FnOnce(&mut Message)) {
+        let Some(chat) = self
+            .chats
+            .values_mut()
+            .find(|chat| chat.find(|m| m.message_id == message_id).is_some())
+        else {
+            return;
+        };
+        if let Some(msg) = Rc::make_mut(chat).find_mut(message_id) {
+            update(msg);
+        }
+    }
+
+    /// Adds an empty conversation for a chat we haven't seen before, e.g. the
+    /// first message from someone new.
+    fn ensure_conversation(&mut self, chat_id: &str) {
+        if self.conversations.iter().any(|c| c.chat_id == chat_id) {
+            return;
+        }
+        let Some(other) = self
+            .current_user
+            .as_deref()
+            .and_then(|me| other_member(chat_id, me))
+        else {
+            return;
+        };
+        let other_user = self
+            .users
+            .iter()
+            .find(|u| u.username == other)
+            .cloned()
+            .unwrap_or(User {
+                username: other,
+                email: String::new(),
+                created_at: 0.0,
+                last_seen: None,
+                status: None,
+            });
+        self.conversations.push(Conversation {
+            chat_id: chat_id.to_string(),
+            other_user,
+            last_message: None,
+            unread_count: 0,
+            mention_count: 0,
+        });
+    }
+
+    /// Makes `msg` the conversation's last message if it's newer, and keeps
+    /// the list ordered by most recent activity.
+    fn touch_conversation(&mut self, msg: &Message) {
+        if let Some(conv) = self
+            .conversations
+            .iter_mut()
+            .find(|c| c.chat_id == msg.chat_id)
+        {
+            let newer = match &conv.last_message {
+                Some(last) if last.sequence.is_some() && msg.sequence.is_some() => {
+                    last.sequence <= msg.sequence
+                }
+                _ => conv.last_activity() <= msg.timestamp,
+            };
+            if newer {
+                conv.last_message = Some(msg.clone());
+            }
+        }
+        self.sort_conversations();
+    }
+
+    /// Swaps the optimistic copy `local_id` for the stored message. The echo
+    /// may already have replaced it, so the client id is matched too.
+    fn reconcile_sent(&mut self, local_id: &str, server: Message) {
+        let is_local = |m: &Message| m.message_id == local_id || m.is_same_message(&server);
+        // The optimistic copy may carry a later client-side timestamp
+        for conv in self.conversations.iter_mut() {
+            if conv.last_message.as_ref().is_some_and(is_local) {
+                conv.last_message = None;
+            }
+        }
+        self.touch_conversation(&server);
+        let chat_id = server.chat_id.clone();
+        let has_local = self
+            .chats
+            .get(&chat_id)
+            .is_some_and(|chat| chat.find(is_local).is_some());
+        if has_local {
+            let local_id = local_id.to_string();
+            let client_id = server.client_message_id.clone();
+            let server = Message {
+                status: MessageStatus::Sent,
+                ..server
+            };
+            self.chat_mut(&chat_id).replace(
+                |m| {
+                    m.message_id == local_id
+                        || (client_id.is_some() && m.client_message_id == client_id)
+                },
+                server,
+            );
+            self.apply_receipts(&chat_id);
+        }
+    }
+
+    fn add_users(&mut self, users: Vec<User>) {
+        for user in users {
+            match self.users.iter_mut().find(|u| u.username == user.username) {
+                Some(known) => *known = user,
+                None => self.users.push(user),
+            }
+        }
+        self.users.sort_by(|a, b| a.username.cmp(&b.username));
+    }
+
+    fn sort_conversations(&mut self) {
+        self.conversations
+            .sort_by(|a, b| b.last_activity().total_cmp(&a.last_activity()));
+    }
+
+    fn apply_receipts(&mut self, chat_id: &str) {
+        let receipts: Vec<_> = self
+            .receipts
+            .iter()
+            .filter(|r| r.chat_id == chat_id)
+            .cloned()
+            .collect();
+        if receipts.is_empty() || !self.chats.contains_key(chat_id) {
+            return;
+        }
+        let chat = self.chat_mut(chat_id);
+        for receipt in &receipts {
+            chat.apply_receipt(receipt);
+        }
+    }
 }
 
 pub enum ChatAction {
-    SetMessages(Vec<Message>),
+    /// A chat's newest page and the cursor to older messages.
+    SetMessages(String, Vec<Message>, Option<String>),
+    /// An older page for the given chat and the cursor after it.
+    PrependMessages(String, Vec<Message>, Option<String>),
+    SetLoadingHistory(String, bool),
+    /// Older history changed on the server, e.g. after an import: the next
+    /// `SetMessages` for the chat replaces its history cursor.
+    InvalidateHistory(String),
+    SetChatLoading(String, bool),
+    SetChatError(String, String),
     SetConversations(Vec<Conversation>),
     AddMessage(Message),
     UpdateMessage(String, Message),
     UpdateMessageStatus(String, MessageStatus),
+    /// Drops a message that was never sent, e.g. a failed one the user gave up on.
+    DiscardMessage(String),
+    SetUploadProgress(String, f64),
     SetLoading(bool),
     SetError(String),
     ClearError,
     SetCurrentChatId(Option<String>),
-    SetUsers(Vec<User>),
+    /// Users we've learned about (e.g. from a directory search), replacing
+    /// older copies of the same users.
+    AddUsers(Vec<User>),
+    ApplyReceipt(ReadReceipt),
+    SetUserStatus(UserStatusData),
+    /// A typing event and the local time it arrived.
+    SetTyping(TypingEvent, f64),
+    /// Drops typing indicators that expired before the given time.
+    ExpireTyping(f64),
+    /// State saved by a previous session. Anything already fetched wins.
+    HydrateFromCache(CachedState),
 }
 
 impl Reducible for ChatState {
@@ -35,56 +233,224 @@ impl Reducible for ChatState {
 
         match action {
             ChatAction::AddMessage(msg) => {
-                let exists = next_state
-                    .messages
-                    .iter()
-                    .any(|m| m.message_id == msg.message_id);
+                // Messages can arrive from the send response, the open chat's
+                // subscription and the inbox subscription
+                let chat = next_state.chats.get(&msg.chat_id);
+                let pending = chat
+                    .and_then(|chat| chat.find(|m| m.is_pending() && m.is_same_message(&msg)))
+                    .map(|m| m.message_id.clone());
+                if let Some(local_id) = pending {
+                    next_state.reconcile_sent(&local_id, msg);
+                    return Rc::new(next_state);
+                }
 
-                if !exists {
-                    next_state.messages.push(msg);
-                    next_state
-                        .messages
-                        .sort_by(|a, b| a.timestamp.partial_cmp(&b.timestamp).unwrap());
+                let same = |m: &Message| m.is_same_message(&msg);
+                let seen = chat.is_some_and(|chat| chat.find(same).is_some())
+                    || next_state
+                        .conversations
+                        .iter()
+                        .any(|c| c.last_message.as_ref().is_some_and(same));
+                if seen {
+                    return self;
+                }
+
+                next_state.ensure_conversation(&msg.chat_id);
+
+                let from_other = next_state.current_user.as_ref() != Some(&msg.sender);
+                let is_open = next_state.current_chat_id.as_ref() == Some(&msg.chat_id);
+                if from_other && !is_open {
+                    let mentions_me = next_state
+                        .current_user
+                        .as_ref()
+                        .is_some_and(|user| msg.mentions_user(user));
+                    if let Some(conv) = next_state
+                        .conversations
+                        .iter_mut()
+                        .find(|c| c.chat_id == msg.chat_id)
+                    {
+                        conv.unread_count += 1;
+                        if mentions_me {
+                            conv.mention_count += 1;
+                        }
+                    }
                 }
+                next_state
+                    .typing
+                    .retain(|t| !(t.chat_id == msg.chat_id && t.username == msg.sender));
+                next_state.touch_conversation(&msg);
+
+                let chat_id = msg.chat_id.clone();
+                next_state.chat_mut(&chat_id).insert(msg);
+                next_state.apply_receipts(&chat_id);
             }
             ChatAction::UpdateMessageStatus(id, status) => {
-                if let Some(msg) = next_state.messages.iter_mut().find(|m| m.message_id == id) {
-                    msg.status = status;
+                next_state.update_message(&id, |msg| msg.status = status);
+            }
+            ChatAction::DiscardMessage(id) => {
+                let Some(chat_id) = next_state
+                    .chats
+                    .iter()
+                    .find(|(_, chat)| chat.find(|m| m.message_id == id).is_some())
+                    .map(|(chat_id, _)| chat_id.clone())
+                else {
+                    return self;
+                };
+                let chat = next_state.chat_mut(&chat_id);
+                chat.remove(&id);
+                let newest = chat.messages().last().cloned();
+                if let Some(conv) = next_state
+                    .conversations
+                    .iter_mut()
+                    .find(|c| c.last_message.as_ref().is_some_and(|m| m.message_id == id))
+                {
+                    conv.last_message = newest;
                 }
+                next_state.sort_conversations();
+            }
+            ChatAction::SetUploadProgress(id, progress) => {
+                next_state.update_message(&id, |msg| msg.upload_progress = Some(progress));
             }
             ChatAction::SetError(error) => {
                 next_state.error = Some(error);
             }
             ChatAction::ClearError => {
                 next_state.error = None;
+                if let Some(chat_id) = next_state.current_chat_id.clone() {
+                    if next_state.current_chat().is_some_and(|c| c.error.is_some()) {
+                        next_state.chat_mut(&chat_id).error = None;
+                    }
+                }
             }
             ChatAction::SetLoading(is_loading) => {
                 next_state.is_loading = is_loading;
             }
-            ChatAction::SetMessages(messages) => {
-                next_state.messages = messages;
+            ChatAction::SetMessages(chat_id, messages, cursor) => {
+                // Keeps anything that arrived over a subscription meanwhile
+                let chat = next_state.chat_mut(&chat_id);
+                // A refreshed page that doesn't reach what we hold leaves a
+                // gap; paging back from its cursor fills it, since older
+                // pages merge without duplicates
+                let leaves_gap = cursor.is_some()
+                    && !messages
+                        .iter()
+                        .any(|m| chat.find(|held| held.is_same_message(m)).is_some());
+                chat.merge(messages);
+                if !chat.loaded || leaves_gap {
+                    chat.history_cursor = cursor;
+                }
+                chat.loaded = true;
+                chat.is_loading = false;
+                chat.error = None;
+                next_state.apply_receipts(&chat_id);
+            }
+            ChatAction::PrependMessages(chat_id, older, cursor) => {
+                let chat = next_state.chat_mut(&chat_id);
+                chat.merge(older);
+                chat.history_cursor = cursor;
+                chat.loading_history = false;
+                next_state.apply_receipts(&chat_id);
+            }
+            ChatAction::InvalidateHistory(chat_id) => {
+                next_state.chat_mut(&chat_id).loaded = false;
+            }
+            ChatAction::SetLoadingHistory(chat_id, loading) => {
+                next_state.chat_mut(&chat_id).loading_history = loading;
+            }
+            ChatAction::SetChatLoading(chat_id, loading) => {
+                next_state.chat_mut(&chat_id).is_loading = loading;
+            }
+            ChatAction::SetChatError(chat_id, error) => {
+                let chat = next_state.chat_mut(&chat_id);
+                chat.error = Some(error);
+                chat.is_loading = false;
+                chat.loading_history = false;
             }
             ChatAction::UpdateMessage(id, new_message) => {
-                if let Some(msg) = next_state.messages.iter_mut().find(|m| m.message_id == id) {
-                    *msg = new_message;
-                    msg.status = MessageStatus::Sent;
-                }
-                next_state
-                    .messages
-                    .sort_by(|a, b| a.timestamp.partial_cmp(&b.timestamp).unwrap());
+                next_state.reconcile_sent(&id, new_message);
             }
             ChatAction::SetCurrentChatId(chat_id) => {
+                if let Some(conv) = next_state
+                    .conversations
+                    .iter_mut()
+                    .find(|c| Some(&c.chat_id) == chat_id.as_ref())
+                {
+                    conv.unread_count = 0;
+                    conv.mention_count = 0;
+                }
                 next_state.current_chat_id = chat_id;
             }
             ChatAction::SetConversations(conversations) => {
+                let members = conversations.iter().map(|c| c.other_user.clone()).collect();
+                next_state.add_users(members);
                 next_state.conversations = conversations;
+                next_state.sort_conversations();
+            }
+            ChatAction::AddUsers(users) => {
+                next_state.add_users(users);
+            }
+            ChatAction::SetTyping(event, now) => {
+                if next_state.current_user.as_ref() == Some(&event.username) {
+                    return self;
+                }
+                next_state
+                    .typing
+                    .retain(|t| !(t.chat_id == event.chat_id && t.username == event.username));
+                if event.is_typing {
+                    next_state.typing.push(TypingUser {
+                        chat_id: event.chat_id,
+                        username: event.username,
+                        expires_at: now + TYPING_TIMEOUT_MS,
+                    });
+                }
+            }
+            ChatAction::ExpireTyping(now) => {
+                if self.typing.iter().all(|t| t.expires_at > now) {
+                    return self;
+                }
+                next_state.typing.retain(|t| t.expires_at > now);
             }
-            ChatAction::SetUsers(users) => {
-                next_state.users = users;
+            ChatAction::SetUserStatus(update) => {
+                let users = next_state.users.iter_mut().chain(
+                    next_state
+                        .conversations
+                        .iter_mut()
+                        .map(|c| &mut c.other_user),
+                );
+                for user in users.filter(|u| u.username == update.username) {
+                    user.status = update.status.clone();
+                    user.last_seen = update.last_seen.or(user.last_seen);
+                }
+            }
+            ChatAction::HydrateFromCache(cached) => {
+                if next_state.conversations.is_empty() {
+                    next_state.conversations = cached.conversations;
+                    next_state.sort_conversations();
+                }
+                if next_state.users.is_empty() {
+                    next_state.users = cached.users;
+                }
+                for chat in cached.chats {
+                    next_state.chats.entry(chat.chat_id).or_insert_with(|| {
+                        Rc::new(ChatMessages::from_cache(chat.messages, chat.history_cursor))
+                    });
+                }
+            }
+            ChatAction::ApplyReceipt(receipt) => {
+                if next_state.current_user.as_ref() == Some(&receipt.username) {
+                    return self;
+                }
+                match next_state
+                    .receipts
+                    .iter_mut()
+                    .find(|r| r.chat_id == receipt.chat_id && r.username == receipt.username)
+                {
+                    Some(existing) => existing.merge(&receipt),
+                    None => next_state.receipts.push(receipt.clone()),
+                }
+                next_state.apply_receipts(&receipt.chat_id);
             }
         }
 
-        let new_state = Rc::new(next_state);
-        new_state
+        Rc::new(next_state)
     }
 }
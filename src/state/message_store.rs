@@ -0,0 +1,147 @@
+use crate::models::message::Message;
+use crate::models::receipt::ReadReceipt;
+
+/// One chat's messages in display order, plus its loading and paging state.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChatMessages {
+    messages: Vec<Message>,
+    /// Whether the newest page has been fetched. Until then the store only
+    /// holds messages that arrived over subscriptions.
+    pub loaded: bool,
+    pub is_loading: bool,
+    /// Cursor for the next older page; `None` once it's all loaded.
+    pub history_cursor: Option<String>,
+    pub loading_history: bool,
+    pub error: Option<String>,
+}
+
+impl ChatMessages {
+    /// A chat restored from the local cache. It still counts as not loaded,
+    /// so opening it fetches the newest page.
+    pub fn from_cache(messages: Vec<Message>, history_cursor: Option<String>) -> Self {
+        let mut chat = Self {
+            history_cursor,
+            ..Self::default()
+        };
+        chat.merge(messages);
+        chat
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    pub fn find(&self, matches: impl Fn(&Message) -> bool) -> Option<&Message> {
+        self.messages.iter().find(|m| matches(m))
+    }
+
+    pub fn find_mut(&mut self, message_id: &str) -> Option<&mut Message> {
+        self.messages
+            .iter_mut()
+            .find(|m| m.message_id == message_id)
+    }
+
+    /// Inserts `msg` at its ordered position. Returns `false` without
+    /// inserting if the store already holds the same message.
+    pub fn insert(&mut self, msg: Message) -> bool {
+        let inserted = self.add(msg);
+        if inserted {
+            Message::sort_chat(&mut self.messages);
+        }
+        inserted
+    }
+
+    pub fn merge(&mut self, messages: impl IntoIterator<Item = Message>) {
+        let mut inserted = false;
+        for msg in messages {
+            inserted |= self.add(msg);
+        }
+        if inserted {
+            Message::sort_chat(&mut self.messages);
+        }
+    }
+
+    /// Removes the messages matching `matches` and inserts `msg`, which may
+    /// belong somewhere else now that it has a sequence number.
+    pub fn replace(&mut self, matches: impl Fn(&Message) -> bool, msg: Message) {
+        self.messages.retain(|m| !matches(m));
+        self.insert(msg);
+    }
+
+    /// Appends `msg` unless the store already holds it; the caller re-sorts.
+    fn add(&mut self, msg: Message) -> bool {
+        if self.messages.iter().any(|m| m.is_same_message(&msg)) {
+            return false;
+        }
+        self.messages.push(msg);
+        true
+    }
+
+    pub fn remove(&mut self, message_id: &str) -> Option<Message> {
+        let index = self
+            .messages
+            .iter()
+            .position(|m| m.message_id == message_id)?;
+        Some(self.messages.remove(index))
+    }
+
+    pub fn apply_receipt(&mut self, receipt: &ReadReceipt) {
+        for msg in self.messages.iter_mut() {
+            msg.apply_receipt(receipt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::{MessageStatus, MessageType};
+
+    fn message(id: &str, timestamp: f64, sequence: Option<u64>) -> Message {
+        Message {
+            message_id: id.to_string(),
+            client_message_id: Some(format!("client-{}", id)),
+            content: String::new(),
+            sender: "alice".to_string(),
+            timestamp,
+            sequence,
+            status: MessageStatus::Sent,
+            message_type: MessageType::Text,
+            chat_id: "CHAT#alice#bob".to_string(),
+            attachment: None,
+            mentions: Vec::new(),
+            imported_by: None,
+            upload_progress: None,
+        }
+    }
+
+    fn ids(store: &ChatMessages) -> Vec<&str> {
+        store
+            .messages()
+            .iter()
+            .map(|m| m.message_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn insert_keeps_order_and_skips_duplicates() {
+        let mut store = ChatMessages::default();
+        store.merge([
+            message("c", 30.0, Some(3)),
+            message("a", 10.0, Some(1)),
+            message("pending", 40.0, None),
+            message("b", 20.0, Some(2)),
+        ]);
+        assert!(!store.insert(message("a", 10.0, Some(1))));
+        assert_eq!(ids(&store), ["a", "b", "c", "pending"]);
+
+        let mut stored = message("server", 35.0, Some(4));
+        stored.client_message_id = Some("client-pending".to_string());
+        store.replace(|m| m.message_id == "pending", stored);
+        assert_eq!(ids(&store), ["a", "b", "c", "server"]);
+
+        assert!(store.remove("b").is_some());
+        assert!(store.remove("b").is_none());
+        assert_eq!(ids(&store), ["a", "c", "server"]);
+    }
+}
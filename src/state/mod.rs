@@ -1,2 +1,3 @@
 pub mod auth_state;
 pub mod chat_state;
+pub mod message_store;
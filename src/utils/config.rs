@@ -6,10 +6,19 @@ pub struct Config {
     pub websocket_endpoint: String,
     pub cognito_client_id: String,
     pub cognito_endpoint: String,
+    /// Public half of the push Lambda's VAPID key, base64url encoded. Empty
+    /// turns Web Push off.
+    pub vapid_public_key: String,
 }
 
 // Global config instance with hardcoded values
-pub static CONFIG: Lazy<Config> = Lazy::new(|| Config::new());
+pub static CONFIG: Lazy<Config> = Lazy::new(Config::new);
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Config {
     pub fn new() -> Self {
@@ -18,6 +27,7 @@ impl Config {
             websocket_endpoint: "wss://4psoayuvcnfu7ekadjzgs6erli.appsync-realtime-api.us-east-1.amazonaws.com/graphql".to_string(),
             cognito_client_id: "p7c55gqav2r7633fgqfbh0rcs".to_string(),
             cognito_endpoint: "https://cognito-idp.us-east-1.amazonaws.com".to_string(),
+            vapid_public_key: String::new(),
         }
     }
 
@@ -27,16 +37,18 @@ impl Config {
              GraphQL Endpoint: {}\n\
              WebSocket Endpoint: {}\n\
              Cognito Client ID: {}\n\
-             Cognito Endpoint: {}\n",
+             Cognito Endpoint: {}\n\
+             VAPID Public Key: {}\n",
             self.graphql_endpoint,
             self.websocket_endpoint,
             self.cognito_client_id,
-            self.cognito_endpoint
+            self.cognito_endpoint,
+            self.vapid_public_key
         )
     }
 }
 
 #[cfg(debug_assertions)]
 pub fn print_config() {
-    web_sys::console::log_1(&format!("{}", CONFIG.debug_string()).into());
+    web_sys::console::log_1(&CONFIG.debug_string().into());
 }
@@ -0,0 +1,419 @@
+use crate::models::attachment::Attachment;
+use crate::models::mention::Mention;
+use crate::models::message::{Message, MessageStatus, MessageType};
+use crate::utils::format::{
+    format_date, format_local, format_time, local_time, should_show_sender,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use wasm_bindgen::JsCast;
+
+/// Bumped whenever the JSON layout changes incompatibly.
+pub const EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Json,
+    Markdown,
+    Html,
+    Text,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 4] = [Self::Json, Self::Markdown, Self::Html, Self::Text];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Json => "JSON",
+            Self::Markdown => "Markdown",
+            Self::Html => "HTML",
+            Self::Text => "Plain text",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Markdown => "md",
+            Self::Html => "html",
+            Self::Text => "txt",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.extension() == extension)
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Markdown => "text/markdown;charset=utf-8",
+            Self::Html => "text/html;charset=utf-8",
+            Self::Text => "text/plain;charset=utf-8",
+        }
+    }
+}
+
+/// A conversation archive. The JSON form is versioned and reads back with
+/// [`ConversationExport::from_json`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationExport {
+    pub version: u32,
+    pub chat_id: String,
+    pub participants: Vec<String>,
+    pub exported_by: String,
+    pub exported_at: f64,
+    /// Oldest first.
+    pub messages: Vec<ExportedMessage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedMessage {
+    pub message_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_message_id: Option<String>,
+    pub sender: String,
+    pub content: String,
+    pub timestamp: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+    pub message_type: MessageType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<Attachment>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mentions: Vec<Mention>,
+}
+
+impl From<&Message> for ExportedMessage {
+    fn from(msg: &Message) -> Self {
+        Self {
+            message_id: msg.message_id.clone(),
+            client_message_id: msg.client_message_id.clone(),
+            sender: msg.sender.clone(),
+            content: msg.content.clone(),
+            timestamp: msg.timestamp,
+            sequence: msg.sequence,
+            message_type: msg.message_type.clone(),
+            attachment: msg.attachment.clone(),
+            mentions: msg.mentions.clone(),
+        }
+    }
+}
+
+impl ConversationExport {
+    /// Stored messages only; pending sends and local notices are left out.
+    pub fn new(chat_id: &str, exported_by: &str, messages: &[Message]) -> Self {
+        let mut participants: Vec<String> = chat_id
+            .trim_start_matches("CHAT#")
+            .split('#')
+            .map(str::to_string)
+            .collect();
+        participants.dedup();
+
+        Self {
+            version: EXPORT_VERSION,
+            chat_id: chat_id.to_string(),
+            participants,
+            exported_by: exported_by.to_string(),
+            exported_at: Utc::now().timestamp_millis() as f64,
+            messages: messages
+                .iter()
+                .filter(|m| !m.is_pending())
+                .filter(|m| !matches!(m.message_type, MessageType::System | MessageType::Error))
+                .map(ExportedMessage::from)
+                .collect(),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let export: Self = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if export.version > EXPORT_VERSION {
+            return Err(format!(
+                "Export version {} is newer than this app supports",
+                export.version
+            ));
+        }
+        Ok(export)
+    }
+
+    pub fn messages(&self) -> Vec<Message> {
+        self.messages
+            .iter()
+            .map(|m| Message {
+                message_id: m.message_id.clone(),
+                client_message_id: m.client_message_id.clone(),
+                content: m.content.clone(),
+                sender: m.sender.clone(),
+                timestamp: m.timestamp,
+                sequence: m.sequence,
+                status: MessageStatus::Sent,
+                message_type: m.message_type.clone(),
+                chat_id: self.chat_id.clone(),
+                attachment: m.attachment.clone(),
+                mentions: m.mentions.clone(),
+                imported_by: None,
+                upload_progress: None,
+            })
+            .collect()
+    }
+
+    pub fn file_name(&self, format: ExportFormat) -> String {
+        let other = self
+            .participants
+            .iter()
+            .find(|p| **p != self.exported_by)
+            .unwrap_or(&self.exported_by);
+        let date = local_time(self.exported_at).map_or_else(
+            || "undated".to_string(),
+            |t| t.format("%Y-%m-%d").to_string(),
+        );
+        format!("chat-{}-{}.{}", other, date, format.extension())
+    }
+
+    pub fn render(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Json => serde_json::to_string_pretty(self).unwrap_or_default(),
+            ExportFormat::Markdown => self.to_markdown(),
+            ExportFormat::Html => self.to_html(),
+            ExportFormat::Text => self.to_text(),
+        }
+    }
+
+    fn title(&self) -> String {
+        format!("Conversation between {}", self.participants.join(" and "))
+    }
+
+    fn exported_at_label(&self) -> String {
+        format_local(self.exported_at, "%B %d, %Y %I:%M %p")
+    }
+
+    /// Walks the messages like `MessageList` does: a heading per day and a
+    /// sender label per run of messages.
+    fn for_each_row(&self, mut row: impl FnMut(Option<String>, bool, &Message)) {
+        let messages = self.messages();
+        let mut last_date = None;
+        for (index, msg) in messages.iter().enumerate() {
+            let date = format_date(msg.timestamp);
+            let new_date = (last_date.as_ref() != Some(&date)).then(|| date.clone());
+            last_date = Some(date);
+            row(new_date, should_show_sender(&messages, index), msg);
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "# {}\n\n_Exported by {} on {}_\n",
+            self.title(),
+            self.exported_by,
+            self.exported_at_label()
+        );
+        self.for_each_row(|date, show_sender, msg| {
+            if let Some(date) = date {
+                let _ = writeln!(out, "\n## {}", date);
+            }
+            if show_sender {
+                let _ = writeln!(out, "\n**{}**", msg.sender);
+            }
+            let body = match &msg.attachment {
+                Some(attachment) => format!(
+                    "📎 {} ({})",
+                    attachment.file_name,
+                    attachment.display_size()
+                ),
+                None => msg.content.clone(),
+            };
+            let _ = writeln!(out, "\n_{}_ — {}", format_time(msg.timestamp), body);
+        });
+        out
+    }
+
+    fn to_html(&self) -> String {
+        let mut body = String::new();
+        self.for_each_row(|date, show_sender, msg| {
+            if let Some(date) = date {
+                let _ = writeln!(body, "<h2>{}</h2>", escape_html(&date));
+            }
+            let side = if msg.sender == self.exported_by {
+                "sent"
+            } else {
+                "received"
+            };
+            let _ = writeln!(body, "<div class=\"message {}\">", side);
+            if show_sender {
+                let _ = writeln!(
+                    body,
+                    "<div class=\"sender\">{}</div>",
+                    escape_html(&msg.sender)
+                );
+            }
+            match &msg.attachment {
+                Some(attachment) => {
+                    let _ = writeln!(
+                        body,
+                        "<div class=\"content\">📎 {} ({})</div>",
+                        escape_html(&attachment.file_name),
+                        attachment.display_size()
+                    );
+                }
+                None => {
+                    let _ = writeln!(
+                        body,
+                        "<div class=\"content\">{}</div>",
+                        escape_html(&msg.content)
+                    );
+                }
+            }
+            let _ = writeln!(
+                body,
+                "<div class=\"time\">{}</div>\n</div>",
+                format_time(msg.timestamp)
+            );
+        });
+
+        format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 720px; margin: 2rem auto; padding: 0 1rem; color: #222; }}
+h2 {{ text-align: center; font-size: 0.85rem; color: #777; margin: 1.5rem 0 0.5rem; }}
+.message {{ margin: 0.25rem 0; max-width: 80%; }}
+.message.sent {{ margin-left: auto; text-align: right; }}
+.sender {{ font-weight: 600; font-size: 0.85rem; margin-top: 0.75rem; }}
+.content {{ display: inline-block; text-align: left; white-space: pre-wrap; padding: 0.5rem 0.75rem; border-radius: 8px; background: #f0e6ee; }}
+.sent .content {{ background: #e8bcb9; }}
+.time {{ font-size: 0.7rem; color: #999; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p><em>Exported by {exported_by} on {exported_at}</em></p>
+{body}</body>
+</html>
+"#,
+            title = escape_html(&self.title()),
+            exported_by = escape_html(&self.exported_by),
+            exported_at = self.exported_at_label(),
+            body = body
+        )
+    }
+
+    /// WhatsApp's `.txt` export layout: one `date, time - sender: text` line
+    /// per message, with continuation lines for multi-line messages.
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+        for msg in self.messages() {
+            let time = format_local(msg.timestamp, "%d/%m/%Y, %H:%M");
+            let text = match &msg.attachment {
+                Some(attachment) => format!("{} (file attached)", attachment.file_name),
+                None => msg.content.clone(),
+            };
+            let _ = writeln!(out, "{} - {}: {}", time, msg.sender, text);
+        }
+        out
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Saves `contents` through the browser's download prompt.
+pub fn download(file_name: &str, mime_type: &str, contents: &str) -> Result<(), String> {
+    let parts = js_sys::Array::of1(&contents.into());
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime_type);
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(&parts, &options)
+        .map_err(|e| format!("{:?}", e))?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(|e| format!("{:?}", e))?;
+
+    let document = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or("No document")?;
+    let link: web_sys::HtmlAnchorElement = document
+        .create_element("a")
+        .map_err(|e| format!("{:?}", e))?
+        .dyn_into()
+        .map_err(|_| "Not an anchor element".to_string())?;
+    link.set_href(&url);
+    link.set_download(file_name);
+    link.click();
+
+    web_sys::Url::revoke_object_url(&url).map_err(|e| format!("{:?}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn message(id: &str, sender: &str, content: &str, timestamp: f64) -> Message {
+        Message {
+            message_id: id.to_string(),
+            client_message_id: None,
+            content: content.to_string(),
+            sender: sender.to_string(),
+            timestamp,
+            sequence: Some(1),
+            status: MessageStatus::Read,
+            message_type: MessageType::Text,
+            chat_id: "CHAT#alice#bob".to_string(),
+            attachment: None,
+            mentions: Vec::new(),
+            imported_by: None,
+            upload_progress: None,
+        }
+    }
+
+    #[test]
+    fn json_round_trips_stored_messages() {
+        let mut pending = message("p", "alice", "not sent yet", 3.0);
+        pending.status = MessageStatus::Sending;
+        let messages = [
+            message("1", "alice", "hi <bob>", 1_700_000_000_000.0),
+            message("2", "bob", "line one\nline two", 1_700_000_060_000.0),
+            pending,
+        ];
+        let export = ConversationExport::new("CHAT#alice#bob", "alice", &messages);
+        assert_eq!(export.participants, ["alice", "bob"]);
+
+        let restored = ConversationExport::from_json(&export.render(ExportFormat::Json)).unwrap();
+        assert_eq!(restored, export);
+        let ids: Vec<String> = restored
+            .messages()
+            .into_iter()
+            .map(|m| m.message_id)
+            .collect();
+        assert_eq!(ids, ["1", "2"]);
+
+        let text = export.render(ExportFormat::Text);
+        let first = Local.timestamp_millis_opt(1_700_000_000_000).unwrap();
+        assert!(text.starts_with(&format!(
+            "{} - alice: hi <bob>\n",
+            first.format("%d/%m/%Y, %H:%M")
+        )));
+        assert!(text.ends_with("bob: line one\nline two\n"));
+        assert!(export.render(ExportFormat::Html).contains("hi &lt;bob&gt;"));
+
+        let mut future = export;
+        future.version = EXPORT_VERSION + 1;
+        let json = serde_json::to_string(&future).unwrap();
+        assert!(ConversationExport::from_json(&json).is_err());
+    }
+}
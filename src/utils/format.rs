@@ -0,0 +1,54 @@
+use crate::models::message::Message;
+use chrono::{DateTime, Local, TimeZone};
+
+/// Messages from the same sender within this gap share one sender label.
+const SENDER_GROUP_GAP_MS: f64 = 5.0 * 60.0 * 1000.0;
+
+/// Shown for timestamps outside the range chrono can represent, which
+/// imported or hand-edited data may carry.
+const UNKNOWN_DATE: &str = "Unknown date";
+
+/// `timestamp` in local time, if it's representable.
+pub fn local_time(timestamp: f64) -> Option<DateTime<Local>> {
+    Local.timestamp_millis_opt(timestamp as i64).earliest()
+}
+
+/// `timestamp` in local time formatted with `format`.
+pub fn format_local(timestamp: f64, format: &str) -> String {
+    local_time(timestamp).map_or_else(
+        || UNKNOWN_DATE.to_string(),
+        |t| t.format(format).to_string(),
+    )
+}
+
+/// Day heading for a message: "Today", "Yesterday" or the full date.
+pub fn format_date(timestamp: f64) -> String {
+    let now = Local::now();
+    let Some(message_date) = local_time(timestamp) else {
+        return UNKNOWN_DATE.to_string();
+    };
+
+    if message_date.date_naive() == now.date_naive() {
+        "Today".to_string()
+    } else if message_date.date_naive() == now.date_naive().pred_opt().unwrap() {
+        "Yesterday".to_string()
+    } else {
+        message_date.format("%B %d, %Y").to_string()
+    }
+}
+
+pub fn format_time(timestamp: f64) -> String {
+    local_time(timestamp).map_or_else(String::new, |t| t.format("%I:%M %p").to_string())
+}
+
+/// Whether `messages[index]` starts a new run of messages from one sender.
+pub fn should_show_sender(messages: &[Message], index: usize) -> bool {
+    if index == 0 {
+        return true;
+    }
+    let current = &messages[index];
+    let previous = &messages[index - 1];
+
+    current.sender != previous.sender
+        || (current.timestamp - previous.timestamp) > SENDER_GROUP_GAP_MS
+}
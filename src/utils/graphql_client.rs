@@ -3,6 +3,26 @@ use crate::utils::config::CONFIG;
 use reqwest::Client as ReqwestClient;
 use serde::{de::DeserializeOwned, Serialize};
 
+/// AppSync answered with a 5xx or 429 instead of a GraphQL response.
+#[derive(Debug)]
+pub struct ServerError {
+    pub status: u16,
+}
+
+impl std::fmt::Display for ServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Server error (HTTP {})", self.status)
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+/// Whether an `execute_query` error may go away on a retry: the request
+/// never reached AppSync, or AppSync was overloaded or failing.
+pub fn is_transient(error: &(dyn std::error::Error + 'static)) -> bool {
+    error.is::<reqwest::Error>() || error.is::<ServerError>()
+}
+
 pub struct GraphQLClient {
     http_client: ReqwestClient,
     endpoint: String,
@@ -51,6 +71,12 @@ impl GraphQLClient {
         }
 
         let response = request.json(&request_body).send().await?;
+        let status = response.status();
+        if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(Box::new(ServerError {
+                status: status.as_u16(),
+            }));
+        }
         let response_text = response.text().await?;
         web_sys::console::log_1(&format!("Response: {:?}", &response_text).into());
         let response_body: GraphQLResponse<T> = serde_json::from_str(&response_text)?;
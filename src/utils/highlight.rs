@@ -0,0 +1,327 @@
+//! Lightweight syntax highlighting for fenced code blocks.
+//!
+//! This is a tokenizer, not a parser: it recognises comments, strings,
+//! numbers and keywords for a handful of common languages, which is enough to
+//! make pasted snippets readable. Unknown languages render as plain text.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    Literal,
+    String,
+    Number,
+    Comment,
+}
+
+impl TokenKind {
+    pub fn class(self) -> Option<&'static str> {
+        match self {
+            TokenKind::Plain => None,
+            TokenKind::Keyword => Some("tok-keyword"),
+            TokenKind::Literal => Some("tok-literal"),
+            TokenKind::String => Some("tok-string"),
+            TokenKind::Number => Some("tok-number"),
+            TokenKind::Comment => Some("tok-comment"),
+        }
+    }
+}
+
+struct Language {
+    keywords: &'static [&'static str],
+    literals: &'static [&'static str],
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    case_insensitive: bool,
+}
+
+const RUST: Language = Language {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type",
+        "unsafe", "use", "where", "while",
+    ],
+    literals: &["true", "false", "None", "Some", "Ok", "Err"],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    case_insensitive: false,
+};
+
+const JAVASCRIPT: Language = Language {
+    keywords: &[
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "export",
+        "extends",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "interface",
+        "let",
+        "new",
+        "of",
+        "return",
+        "switch",
+        "this",
+        "throw",
+        "try",
+        "type",
+        "typeof",
+        "var",
+        "void",
+        "while",
+        "yield",
+    ],
+    literals: &["true", "false", "null", "undefined", "NaN"],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+    case_insensitive: false,
+};
+
+const PYTHON: Language = Language {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+        "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with",
+        "yield",
+    ],
+    literals: &["True", "False", "None"],
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"', '\''],
+    case_insensitive: false,
+};
+
+const SHELL: Language = Language {
+    keywords: &[
+        "if", "then", "else", "elif", "fi", "for", "while", "do", "done", "case", "esac",
+        "function", "in", "export", "local", "return",
+    ],
+    literals: &["true", "false"],
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"', '\''],
+    case_insensitive: false,
+};
+
+const JSON: Language = Language {
+    keywords: &[],
+    literals: &["true", "false", "null"],
+    line_comment: None,
+    block_comment: None,
+    quotes: &['"'],
+    case_insensitive: false,
+};
+
+const SQL: Language = Language {
+    keywords: &[
+        "select", "from", "where", "insert", "into", "values", "update", "set", "delete", "join",
+        "left", "right", "inner", "outer", "on", "group", "by", "order", "having", "limit",
+        "create", "table", "drop", "alter", "and", "or", "not", "as", "distinct",
+    ],
+    literals: &["null", "true", "false"],
+    line_comment: Some("--"),
+    block_comment: Some(("/*", "*/")),
+    quotes: &['\'', '"'],
+    case_insensitive: true,
+};
+
+const GO: Language = Language {
+    keywords: &[
+        "break",
+        "case",
+        "chan",
+        "const",
+        "continue",
+        "default",
+        "defer",
+        "else",
+        "for",
+        "func",
+        "go",
+        "if",
+        "import",
+        "interface",
+        "map",
+        "package",
+        "range",
+        "return",
+        "select",
+        "struct",
+        "switch",
+        "type",
+        "var",
+    ],
+    literals: &["true", "false", "nil"],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '`'],
+    case_insensitive: false,
+};
+
+fn language(name: &str) -> Option<&'static Language> {
+    match name {
+        "rust" | "rs" => Some(&RUST),
+        "js" | "javascript" | "ts" | "typescript" | "jsx" | "tsx" => Some(&JAVASCRIPT),
+        "py" | "python" => Some(&PYTHON),
+        "sh" | "bash" | "shell" | "zsh" => Some(&SHELL),
+        "json" => Some(&JSON),
+        "sql" => Some(&SQL),
+        "go" | "golang" => Some(&GO),
+        _ => None,
+    }
+}
+
+/// Splits `code` into tokens. Concatenating the token texts gives back `code`.
+pub fn highlight(language_name: Option<&str>, code: &str) -> Vec<(TokenKind, String)> {
+    let Some(lang) = language_name.and_then(language) else {
+        return vec![(TokenKind::Plain, code.to_string())];
+    };
+
+    let chars: Vec<char> = code.chars().collect();
+    let mut tokens: Vec<(TokenKind, String)> = Vec::new();
+    let mut i = 0;
+
+    let push = |tokens: &mut Vec<(TokenKind, String)>, kind: TokenKind, text: String| match tokens
+        .last_mut()
+    {
+        Some((last_kind, last_text)) if *last_kind == kind => last_text.push_str(&text),
+        _ => tokens.push((kind, text)),
+    };
+
+    while i < chars.len() {
+        let rest = &chars[i..];
+
+        if lang.line_comment.is_some_and(|m| starts_with(rest, m)) {
+            let len = rest.iter().position(|&c| c == '\n').unwrap_or(rest.len());
+            push(
+                &mut tokens,
+                TokenKind::Comment,
+                rest[..len].iter().collect(),
+            );
+            i += len;
+            continue;
+        }
+
+        if let Some((open, close)) = lang
+            .block_comment
+            .filter(|(open, _)| starts_with(rest, open))
+        {
+            let open_len = open.chars().count();
+            let close_len = close.chars().count();
+            let len = (open_len..rest.len())
+                .find(|&j| starts_with(&rest[j..], close))
+                .map_or(rest.len(), |j| j + close_len);
+            push(
+                &mut tokens,
+                TokenKind::Comment,
+                rest[..len].iter().collect(),
+            );
+            i += len;
+            continue;
+        }
+
+        let c = rest[0];
+
+        if lang.quotes.contains(&c) {
+            let mut len = 1;
+            while len < rest.len() && rest[len] != c {
+                if rest[len] == '\\' {
+                    len += 1;
+                }
+                if rest.get(len) == Some(&'\n') && c != '`' {
+                    break;
+                }
+                len += 1;
+            }
+            len = (len + 1).min(rest.len());
+            push(&mut tokens, TokenKind::String, rest[..len].iter().collect());
+            i += len;
+            continue;
+        }
+
+        if c.is_ascii_digit() {
+            let len = rest
+                .iter()
+                .position(|c| !(c.is_ascii_alphanumeric() || *c == '.' || *c == '_'))
+                .unwrap_or(rest.len());
+            push(&mut tokens, TokenKind::Number, rest[..len].iter().collect());
+            i += len;
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .iter()
+                .position(|c| !(c.is_alphanumeric() || *c == '_'))
+                .unwrap_or(rest.len());
+            let word: String = rest[..len].iter().collect();
+            let lookup = if lang.case_insensitive {
+                word.to_lowercase()
+            } else {
+                word.clone()
+            };
+            let kind = if lang.keywords.contains(&lookup.as_str()) {
+                TokenKind::Keyword
+            } else if lang.literals.contains(&lookup.as_str()) {
+                TokenKind::Literal
+            } else {
+                TokenKind::Plain
+            };
+            push(&mut tokens, kind, word);
+            i += len;
+            continue;
+        }
+
+        push(&mut tokens, TokenKind::Plain, c.to_string());
+        i += 1;
+    }
+
+    tokens
+}
+
+fn starts_with(chars: &[char], prefix: &str) -> bool {
+    let mut prefix_chars = prefix.chars();
+    let count = prefix.chars().count();
+    chars.len() >= count
+        && chars[..count]
+            .iter()
+            .all(|&c| Some(c) == prefix_chars.next())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_round_trip_the_source() {
+        let code = "fn main() {\n    // hi \"there\"\n    let s = \"a\\\"b\"; /* x */ 42\n}";
+        let tokens = highlight(Some("rust"), code);
+        assert_eq!(
+            tokens.iter().map(|(_, t)| t.as_str()).collect::<String>(),
+            code
+        );
+        assert!(tokens.contains(&(TokenKind::Keyword, "fn".to_string())));
+        assert!(tokens.contains(&(TokenKind::String, "\"a\\\"b\"".to_string())));
+        assert!(tokens.contains(&(TokenKind::Number, "42".to_string())));
+    }
+}
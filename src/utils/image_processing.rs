@@ -0,0 +1,228 @@
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// Longest edge of an uploaded image, in pixels.
+pub const MAX_IMAGE_DIMENSION: u32 = 2048;
+const JPEG_QUALITY: u8 = 82;
+const PLACEHOLDER_COMPONENTS: (u32, u32) = (4, 3);
+const PLACEHOLDER_SAMPLE_SIZE: u32 = 32;
+
+pub struct ProcessedImage {
+    pub bytes: Vec<u8>,
+    pub mime_type: &'static str,
+    pub extension: &'static str,
+    pub width: u32,
+    pub height: u32,
+    /// Blurhash of the image, shown while the full image loads.
+    pub placeholder: String,
+}
+
+/// Image formats uploaded untouched so animations and vectors survive. None
+/// of them carries EXIF.
+const PASSTHROUGH_IMAGE_TYPES: [&str; 3] = ["image/gif", "image/svg+xml", "image/bmp"];
+
+/// Formats we decode and re-encode.
+pub fn is_processable(mime_type: &str) -> bool {
+    matches!(mime_type, "image/jpeg" | "image/png" | "image/webp")
+}
+
+/// Images we can neither re-encode nor trust to be free of EXIF (TIFF,
+/// HEIC, AVIF...). Sending them would leak their metadata, GPS included.
+pub fn keeps_metadata(mime_type: &str) -> bool {
+    mime_type.starts_with("image/")
+        && !is_processable(mime_type)
+        && !PASSTHROUGH_IMAGE_TYPES.contains(&mime_type)
+}
+
+/// Decodes an image, applies its EXIF orientation, downscales it to
+/// `MAX_IMAGE_DIMENSION` and re-encodes it. Re-encoding drops every metadata
+/// block, so EXIF (including GPS position) never leaves the device.
+pub fn process_image(bytes: &[u8]) -> Result<ProcessedImage, String> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?
+        .into_decoder()
+        .map_err(|e| e.to_string())?;
+    let orientation = decoder.orientation().map_err(|e| e.to_string())?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+    image.apply_orientation(orientation);
+
+    if image.width() > MAX_IMAGE_DIMENSION || image.height() > MAX_IMAGE_DIMENSION {
+        image = image.resize(
+            MAX_IMAGE_DIMENSION,
+            MAX_IMAGE_DIMENSION,
+            FilterType::Lanczos3,
+        );
+    }
+
+    let placeholder = encode_placeholder(&image)?;
+
+    let mut output = Cursor::new(Vec::new());
+    let (mime_type, extension) = if image.color().has_alpha() {
+        image
+            .write_to(&mut output, ImageFormat::Png)
+            .map_err(|e| e.to_string())?;
+        ("image/png", "png")
+    } else {
+        JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY)
+            .encode_image(&image.to_rgb8())
+            .map_err(|e| e.to_string())?;
+        ("image/jpeg", "jpg")
+    };
+
+    Ok(ProcessedImage {
+        bytes: output.into_inner(),
+        mime_type,
+        extension,
+        width: image.width(),
+        height: image.height(),
+        placeholder,
+    })
+}
+
+/// Swaps the extension to match the re-encoded format, e.g. `IMG_1.webp` -> `IMG_1.jpg`.
+pub fn with_extension(file_name: &str, extension: &str) -> String {
+    let stem = file_name
+        .rsplit_once('.')
+        .map_or(file_name, |(stem, _)| stem);
+    format!("{}.{}", stem, extension)
+}
+
+fn encode_placeholder(image: &DynamicImage) -> Result<String, String> {
+    let sample = image
+        .thumbnail(PLACEHOLDER_SAMPLE_SIZE, PLACEHOLDER_SAMPLE_SIZE)
+        .to_rgba8();
+    blurhash::encode(
+        PLACEHOLDER_COMPONENTS.0,
+        PLACEHOLDER_COMPONENTS.1,
+        sample.width(),
+        sample.height(),
+        sample.as_raw(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+thread_local! {
+    /// Data URLs by blurhash and sample size.
+    static PLACEHOLDER_CACHE: RefCell<HashMap<(String, u32, u32), String>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Renders a blurhash as a tiny PNG data URL suitable for a CSS background.
+/// Results are cached since message lists re-render often.
+pub fn placeholder_data_url(hash: &str, width: u32, height: u32) -> Option<String> {
+    // Keep the aspect ratio; the browser stretches the tiny image to size.
+    // The dimensions come from another user's metadata, so don't trust them
+    // to fit in u32 math.
+    let scaled = |side: u32, longest: u32| {
+        (u64::from(PLACEHOLDER_SAMPLE_SIZE) * u64::from(side) / u64::from(longest.max(1)))
+            .clamp(1, u64::from(PLACEHOLDER_SAMPLE_SIZE)) as u32
+    };
+    let (w, h) = if width >= height {
+        (PLACEHOLDER_SAMPLE_SIZE, scaled(height, width))
+    } else {
+        (scaled(width, height), PLACEHOLDER_SAMPLE_SIZE)
+    };
+
+    let key = (hash.to_string(), w, h);
+    if let Some(url) = PLACEHOLDER_CACHE.with(|cache| cache.borrow().get(&key).cloned()) {
+        return Some(url);
+    }
+
+    let pixels = blurhash::decode(hash, w, h, 1.0).ok()?;
+    let image = image::RgbaImage::from_raw(w, h, pixels)?;
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png).ok()?;
+
+    let url = format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(png.into_inner())
+    );
+    PLACEHOLDER_CACHE.with(|cache| {
+        cache.borrow_mut().insert(key, url.clone());
+    });
+    Some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jpeg_with_exif(width: u32, height: u32) -> Vec<u8> {
+        let image = image::RgbImage::from_pixel(width, height, image::Rgb([200, 80, 90]));
+        let mut jpeg = Cursor::new(Vec::new());
+        image.write_to(&mut jpeg, ImageFormat::Jpeg).unwrap();
+        let jpeg = jpeg.into_inner();
+
+        // APP1 segment with a little-endian TIFF header and no IFD entries
+        let payload = b"Exif\0\0II*\0\x08\0\0\0\0\0";
+        let mut app1 = vec![0xFF, 0xE1];
+        app1.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+        app1.extend_from_slice(payload);
+
+        let mut bytes = jpeg[..2].to_vec();
+        bytes.extend(app1);
+        bytes.extend_from_slice(&jpeg[2..]);
+        bytes
+    }
+
+    #[test]
+    fn downscales_and_strips_exif() {
+        let input = jpeg_with_exif(2560, 1280);
+        assert!(input.windows(4).any(|w| w == b"Exif"));
+
+        let processed = process_image(&input).unwrap();
+
+        assert_eq!((processed.width, processed.height), (2048, 1024));
+        assert_eq!(processed.mime_type, "image/jpeg");
+        assert!(!processed.bytes.windows(4).any(|w| w == b"Exif"));
+        assert!(placeholder_data_url(&processed.placeholder, 2048, 1024).is_some());
+    }
+
+    #[test]
+    fn keeps_small_images_at_their_size() {
+        let processed = process_image(&jpeg_with_exif(640, 480)).unwrap();
+        assert_eq!((processed.width, processed.height), (640, 480));
+        assert_eq!(
+            with_extension("IMG_0001.jpeg", processed.extension),
+            "IMG_0001.jpg"
+        );
+    }
+
+    #[test]
+    fn placeholder_tolerates_huge_dimensions() {
+        let processed = process_image(&jpeg_with_exif(64, 32)).unwrap();
+        assert!(placeholder_data_url(&processed.placeholder, u32::MAX, 3).is_some());
+    }
+
+    #[test]
+    fn placeholder_cache_keeps_each_shape() {
+        let processed = process_image(&jpeg_with_exif(64, 32)).unwrap();
+        let size = |width, height| {
+            let url = placeholder_data_url(&processed.placeholder, width, height).unwrap();
+            let png = base64::engine::general_purpose::STANDARD
+                .decode(url.trim_start_matches("data:image/png;base64,"))
+                .unwrap();
+            let image = image::load_from_memory(&png).unwrap();
+            (image.width(), image.height())
+        };
+        assert_eq!(size(400, 200), (32, 16));
+        assert_eq!(size(200, 400), (16, 32));
+        assert_eq!(size(400, 200), (32, 16));
+    }
+
+    #[test]
+    fn only_metadata_free_images_skip_processing() {
+        for mime_type in ["image/tiff", "image/heic", "image/heif", "image/avif"] {
+            assert!(keeps_metadata(mime_type), "{mime_type}");
+        }
+        for mime_type in ["image/jpeg", "image/webp", "image/gif", "application/pdf"] {
+            assert!(!keeps_metadata(mime_type), "{mime_type}");
+        }
+    }
+}
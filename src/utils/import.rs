@@ -0,0 +1,519 @@
+use crate::models::message::{Message, MessageStatus, MessageType, MAX_CONTENT_LENGTH};
+use chrono::{Local, NaiveDate, TimeZone};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Imported message ids start with this; the import mutation only writes
+/// ids of this form, so an import can never overwrite a message sent here.
+pub const IMPORT_ID_PREFIX: &str = "import-";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportSource {
+    Slack,
+    WhatsApp,
+}
+
+impl ImportSource {
+    pub const ALL: [ImportSource; 2] = [Self::Slack, Self::WhatsApp];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Slack => "Slack",
+            Self::WhatsApp => "WhatsApp",
+        }
+    }
+
+    /// `accept` attribute for the file picker.
+    pub fn accept(self) -> &'static str {
+        match self {
+            Self::Slack => ".json,application/json",
+            Self::WhatsApp => ".txt,text/plain",
+        }
+    }
+
+    /// What the user should pick, shown next to the file input.
+    pub fn hint(self) -> &'static str {
+        match self {
+            Self::Slack => {
+                "Select the channel's day files from the unzipped export, plus users.json for names."
+            }
+            Self::WhatsApp => "Select the .txt file from \"Export chat\" (without media).",
+        }
+    }
+
+    fn tag(self) -> &'static str {
+        match self {
+            Self::Slack => "slack",
+            Self::WhatsApp => "whatsapp",
+        }
+    }
+}
+
+pub struct ImportFile {
+    pub name: String,
+    pub contents: String,
+}
+
+/// Parses an export into messages for `chat_id`, oldest first. Senders are
+/// still the foreign names; see [`apply_mapping`].
+pub fn parse(
+    source: ImportSource,
+    files: &[ImportFile],
+    chat_id: &str,
+) -> Result<Vec<Message>, String> {
+    let entries = match source {
+        ImportSource::Slack => parse_slack(files)?,
+        ImportSource::WhatsApp => files
+            .iter()
+            .map(|file| parse_whatsapp(&file.contents))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect(),
+    };
+    if entries.is_empty() {
+        return Err(format!("No {} messages found", source.label()));
+    }
+    Ok(to_messages(source, entries, chat_id))
+}
+
+/// Foreign sender names, in order of first appearance.
+pub fn authors(messages: &[Message]) -> Vec<String> {
+    let mut authors: Vec<String> = Vec::new();
+    for msg in messages {
+        if !authors.contains(&msg.sender) {
+            authors.push(msg.sender.clone());
+        }
+    }
+    authors
+}
+
+/// Replaces foreign senders with local usernames. Messages from authors
+/// missing from `mapping` are dropped.
+pub fn apply_mapping(messages: &[Message], mapping: &HashMap<String, String>) -> Vec<Message> {
+    messages
+        .iter()
+        .filter_map(|msg| {
+            mapping.get(&msg.sender).map(|username| Message {
+                sender: username.clone(),
+                ..msg.clone()
+            })
+        })
+        .collect()
+}
+
+struct Entry {
+    author: String,
+    content: String,
+    timestamp: f64,
+}
+
+/// Ids derive from the original message, not from the mapping, so importing
+/// the same export twice writes the same items.
+fn to_messages(source: ImportSource, mut entries: Vec<Entry>, chat_id: &str) -> Vec<Message> {
+    entries.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    let mut seen = HashMap::<u64, u32>::new();
+    entries
+        .into_iter()
+        .map(|entry| {
+            let hash = fnv1a(&[
+                source.tag(),
+                &entry.author,
+                &entry.timestamp.to_string(),
+                &entry.content,
+            ]);
+            // Identical lines in the same minute are still separate messages
+            let occurrence = seen.entry(hash).or_default();
+            *occurrence += 1;
+            Message {
+                message_id: format!("{}{:016x}-{}", IMPORT_ID_PREFIX, hash, occurrence),
+                client_message_id: None,
+                content: truncate_content(entry.content),
+                sender: entry.author,
+                timestamp: entry.timestamp,
+                sequence: None,
+                status: MessageStatus::Read,
+                message_type: MessageType::Text,
+                chat_id: chat_id.to_string(),
+                attachment: None,
+                mentions: Vec::new(),
+                imported_by: None,
+                upload_progress: None,
+            }
+        })
+        .collect()
+}
+
+/// Cuts content the server would reject down to `MAX_CONTENT_LENGTH`.
+fn truncate_content(mut content: String) -> String {
+    let mut units = 0;
+    if let Some((end, _)) = content.char_indices().find(|(_, c)| {
+        units += c.len_utf16();
+        units > MAX_CONTENT_LENGTH
+    }) {
+        content.truncate(end);
+    }
+    content
+}
+
+fn fnv1a(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in part.bytes().chain([0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+struct WhatsAppLine<'a> {
+    date: [u32; 3],
+    hour: u32,
+    minute: u32,
+    second: u32,
+    /// `Name: text`, or a notice like "Messages are end-to-end encrypted".
+    body: &'a str,
+}
+
+/// Reads both export layouts, `31/12/2023, 21:15 - Name: text` (Android)
+/// and `[31/12/2023, 21:15:03] Name: text` (iOS), with 12 or 24 hour
+/// times. Whether dates are day or month first is worked out from the file;
+/// times are taken as local time.
+fn parse_whatsapp(text: &str) -> Result<Vec<Entry>, String> {
+    let mut lines: Vec<(WhatsAppLine, String)> = Vec::new();
+    for line in text.lines() {
+        match parse_whatsapp_line(line) {
+            Some(parsed) => lines.push((parsed, String::new())),
+            // Continuation of a multi-line message
+            None => match lines.last_mut() {
+                Some((_, more)) => {
+                    more.push('\n');
+                    more.push_str(line);
+                }
+                None if line.trim().is_empty() => {}
+                None => return Err("This doesn't look like a WhatsApp chat export".to_string()),
+            },
+        }
+    }
+
+    let day_first = if lines.iter().any(|(l, _)| l.date[0] > 12) {
+        true
+    } else {
+        !lines.iter().any(|(l, _)| l.date[1] > 12)
+    };
+
+    let mut entries = Vec::new();
+    for (line, more) in lines {
+        // Notices have no sender
+        let Some((author, content)) = line.body.split_once(": ") else {
+            continue;
+        };
+        let (day, month) = if day_first {
+            (line.date[0], line.date[1])
+        } else {
+            (line.date[1], line.date[0])
+        };
+        let year = match line.date[2] {
+            y if y < 100 => 2000 + y as i32,
+            y => y as i32,
+        };
+        let timestamp = NaiveDate::from_ymd_opt(year, month, day)
+            .and_then(|date| date.and_hms_opt(line.hour, line.minute, line.second))
+            .and_then(|time| Local.from_local_datetime(&time).earliest())
+            .ok_or_else(|| format!("Invalid date in line: {}", line.body))?
+            .timestamp_millis() as f64;
+        entries.push(Entry {
+            author: author.trim().to_string(),
+            content: format!("{}{}", content.trim_start_matches('\u{200e}'), more),
+            timestamp,
+        });
+    }
+    Ok(entries)
+}
+
+fn parse_whatsapp_line(line: &str) -> Option<WhatsAppLine<'_>> {
+    let line = line.trim_start_matches(['\u{feff}', '\u{200e}']);
+    let (bracketed, rest) = match line.strip_prefix('[') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+
+    let (date, rest) = rest.split_once([',', ' '])?;
+    let mut date_parts = date.split(['/', '.', '-']).map(|p| p.parse::<u32>().ok());
+    let date = [
+        date_parts.next()??,
+        date_parts.next()??,
+        date_parts.next()??,
+    ];
+    if date_parts.next().is_some() {
+        return None;
+    }
+
+    let (time, body) = if bracketed {
+        rest.split_once("] ")?
+    } else {
+        rest.split_once(" - ")?
+    };
+    let time = time.trim();
+    let (time, meridiem) = match time.rsplit_once([' ', '\u{202f}', '\u{a0}']) {
+        Some((time, m)) => (time, Some(m.to_ascii_uppercase().replace('.', ""))),
+        None => (time, None),
+    };
+    let mut time_parts = time.split(':').map(|p| p.parse::<u32>().ok());
+    let mut hour = time_parts.next()??;
+    let minute = time_parts.next()??;
+    let second = time_parts.next().unwrap_or(Some(0))?;
+    match meridiem.as_deref() {
+        Some("AM") if hour == 12 => hour = 0,
+        Some("PM") if hour < 12 => hour += 12,
+        Some("AM" | "PM") | None => {}
+        Some(_) => return None,
+    }
+
+    Some(WhatsAppLine {
+        date,
+        hour,
+        minute,
+        second,
+        body,
+    })
+}
+
+#[derive(Deserialize)]
+struct SlackUser {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct SlackMessage {
+    user: Option<String>,
+    #[serde(default)]
+    text: String,
+    ts: String,
+    subtype: Option<String>,
+    user_profile: Option<SlackProfile>,
+}
+
+#[derive(Deserialize)]
+struct SlackProfile {
+    name: String,
+}
+
+/// Message subtypes that are real messages rather than channel events.
+const SLACK_MESSAGE_SUBTYPES: [&str; 3] = ["thread_broadcast", "file_share", "me_message"];
+
+/// Latest time a JavaScript `Date` can hold, in milliseconds.
+const MAX_TIMESTAMP_MS: f64 = 8.64e15;
+
+/// Reads the day files of one channel (`2023-12-31.json`, arrays of
+/// messages). `users.json`, if given, turns user ids into handles.
+fn parse_slack(files: &[ImportFile]) -> Result<Vec<Entry>, String> {
+    let mut names = HashMap::new();
+    for file in files.iter().filter(|f| f.name.ends_with("users.json")) {
+        let users: Vec<SlackUser> =
+            serde_json::from_str(&file.contents).map_err(|e| format!("{}: {}", file.name, e))?;
+        names.extend(users.into_iter().map(|u| (u.id, u.name)));
+    }
+
+    let mut messages = Vec::new();
+    for file in files.iter().filter(|f| !f.name.ends_with("users.json")) {
+        let day: Vec<SlackMessage> =
+            serde_json::from_str(&file.contents).map_err(|e| format!("{}: {}", file.name, e))?;
+        messages.extend(day);
+    }
+    // user_profile is only on some messages; learn names from it too
+    for msg in &messages {
+        if let (Some(user), Some(profile)) = (&msg.user, &msg.user_profile) {
+            names
+                .entry(user.clone())
+                .or_insert_with(|| profile.name.clone());
+        }
+    }
+
+    let mut entries = Vec::new();
+    for msg in messages {
+        if msg
+            .subtype
+            .as_deref()
+            .is_some_and(|s| !SLACK_MESSAGE_SUBTYPES.contains(&s))
+        {
+            continue;
+        }
+        let Some(user) = msg.user else {
+            continue;
+        };
+        let timestamp = msg
+            .ts
+            .parse::<f64>()
+            .ok()
+            .map(|seconds| (seconds * 1000.0).round())
+            // Also rejects "inf" and "NaN", which parse as floats
+            .filter(|ms| (0.0..=MAX_TIMESTAMP_MS).contains(ms))
+            .ok_or_else(|| format!("Invalid Slack timestamp {}", msg.ts))?;
+        entries.push(Entry {
+            author: names.get(&user).cloned().unwrap_or(user),
+            content: slack_text(&msg.text, &names),
+            timestamp,
+        });
+    }
+    Ok(entries)
+}
+
+/// Turns Slack's markup (`<@U123>`, `<#C1|general>`, `<https://x|label>`,
+/// `<!here>`) and entity escapes into plain text.
+fn slack_text(text: &str, names: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let token = &rest[start + 1..start + end];
+        let (target, label) = match token.split_once('|') {
+            Some((target, label)) => (target, Some(label)),
+            None => (token, None),
+        };
+        match (target.chars().next(), label) {
+            (Some('@'), _) => {
+                let id = &target[1..];
+                out.push('@');
+                out.push_str(names.get(id).map_or(label.unwrap_or(id), String::as_str));
+            }
+            (Some('#'), Some(label)) => {
+                out.push('#');
+                out.push_str(label);
+            }
+            (Some('!'), _) => {
+                out.push('@');
+                out.push_str(label.unwrap_or(&target[1..]));
+            }
+            (_, Some(label)) => out.push_str(label),
+            _ => out.push_str(target),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, contents: &str) -> ImportFile {
+        ImportFile {
+            name: name.to_string(),
+            contents: contents.to_string(),
+        }
+    }
+
+    fn local_ms(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> f64 {
+        Local
+            .with_ymd_and_hms(y, mo, d, h, mi, s)
+            .earliest()
+            .unwrap()
+            .timestamp_millis() as f64
+    }
+
+    #[test]
+    fn parses_whatsapp_exports() {
+        let android = "\
+12/31/23, 9:15 PM - Messages and calls are end-to-end encrypted.
+12/31/23, 9:15 PM - Ana María: Happy new year!
+See you
+12/31/23, 9:15 PM - Ana María: Happy new year!
+1/1/24, 12:05 AM - Bob: Thanks: you too
+";
+        let messages = parse(
+            ImportSource::WhatsApp,
+            &[file("chat.txt", android)],
+            "CHAT#alice#bob",
+        )
+        .unwrap();
+        let rows: Vec<_> = messages
+            .iter()
+            .map(|m| (m.sender.as_str(), m.content.as_str(), m.timestamp))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (
+                    "Ana María",
+                    "Happy new year!\nSee you",
+                    local_ms(2023, 12, 31, 21, 15, 0)
+                ),
+                (
+                    "Ana María",
+                    "Happy new year!",
+                    local_ms(2023, 12, 31, 21, 15, 0)
+                ),
+                ("Bob", "Thanks: you too", local_ms(2024, 1, 1, 0, 5, 0)),
+            ]
+        );
+        assert!(messages
+            .iter()
+            .all(|m| m.message_id.starts_with(IMPORT_ID_PREFIX)));
+
+        let ios = "[31/12/2023, 21:15:03] Bob: hi\n";
+        let messages = parse(ImportSource::WhatsApp, &[file("_chat.txt", ios)], "c").unwrap();
+        assert_eq!(messages[0].timestamp, local_ms(2023, 12, 31, 21, 15, 3));
+
+        // Same export, same ids
+        let again = parse(ImportSource::WhatsApp, &[file("_chat.txt", ios)], "c").unwrap();
+        assert_eq!(messages[0].message_id, again[0].message_id);
+    }
+
+    #[test]
+    fn parses_slack_exports_and_maps_authors() {
+        let users = r#"[{"id": "U1", "name": "ana"}, {"id": "U2", "name": "bob"}]"#;
+        let day = r#"[
+            {"type": "message", "user": "U2", "text": "later", "ts": "1700000060.000200"},
+            {"type": "message", "user": "U1", "text": "hey <@U2> see <https://x.dev|the docs> &amp; <#C1|general>", "ts": "1700000000.000100"},
+            {"type": "message", "subtype": "channel_join", "user": "U3", "text": "<@U3> has joined", "ts": "1700000001.000000"}
+        ]"#;
+        let messages = parse(
+            ImportSource::Slack,
+            &[file("users.json", users), file("2023-11-14.json", day)],
+            "CHAT#alice#bob",
+        )
+        .unwrap();
+        assert_eq!(authors(&messages), ["ana", "bob"]);
+        assert_eq!(messages[0].content, "hey @bob see the docs & #general");
+        assert_eq!(messages[0].timestamp, 1_700_000_000_000.0);
+
+        let mapping = HashMap::from([("ana".to_string(), "alice".to_string())]);
+        let mapped = apply_mapping(&messages, &mapping);
+        assert_eq!(mapped.len(), 1);
+        assert_eq!(mapped[0].sender, "alice");
+        assert_eq!(mapped[0].message_id, messages[0].message_id);
+    }
+
+    #[test]
+    fn truncates_content_the_server_would_reject() {
+        let long = format!("{}\n", "😀".repeat(MAX_CONTENT_LENGTH));
+        let chat = format!("1/1/24, 12:05 AM - Bob: {}", long);
+        let messages = parse(ImportSource::WhatsApp, &[file("chat.txt", &chat)], "c").unwrap();
+        assert_eq!(messages[0].content, "😀".repeat(MAX_CONTENT_LENGTH / 2));
+    }
+
+    #[test]
+    fn rejects_out_of_range_slack_timestamps() {
+        for ts in ["inf", "NaN", "1e300", "-5"] {
+            let day = format!(
+                r#"[{{"type": "message", "user": "U1", "text": "hi", "ts": "{}"}}]"#,
+                ts
+            );
+            let result = parse(ImportSource::Slack, &[file("2023-11-14.json", &day)], "c");
+            assert_eq!(
+                result.unwrap_err(),
+                format!("Invalid Slack timestamp {}", ts)
+            );
+        }
+    }
+}
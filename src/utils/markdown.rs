@@ -0,0 +1,648 @@
+//! A small, chat-oriented Markdown subset.
+//!
+//! Supported: `**bold**`, `*italic*`, `` `code` ``, fenced code blocks,
+//! `-`/`1.` lists, `>` blockquotes and bare `http(s)://`/`www.` URLs.
+//!
+//! The parser only produces a tree of [`Block`]s and [`Inline`]s holding plain
+//! text. There is no raw HTML node: whatever the sender types (tags, entities,
+//! `javascript:` URLs) ends up as text, and the view layer escapes it. Links are
+//! only ever created from URLs that start with `http://`, `https://` or `www.`.
+//!
+//! Messages come from other users, so parsing is linear in the input and
+//! nesting is capped: quotes and emphasis deeper than the limits below are
+//! kept as text rather than recursed into.
+
+use crate::utils::mentions::extract_mentions;
+
+/// Deepest `>` nesting that becomes a quote.
+const MAX_QUOTE_DEPTH: usize = 8;
+/// Deepest `*`/`_` nesting that becomes emphasis.
+const MAX_EMPHASIS_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Paragraph(Vec<Inline>),
+    CodeBlock {
+        language: Option<String>,
+        code: String,
+    },
+    List {
+        ordered: bool,
+        start: u32,
+        items: Vec<Vec<Inline>>,
+    },
+    Quote(Vec<Block>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inline {
+    Text(String),
+    Bold(Vec<Inline>),
+    Italic(Vec<Inline>),
+    Code(String),
+    Link {
+        href: String,
+        text: String,
+    },
+    /// `@username` of a user mentioned in the message, as typed.
+    Mention {
+        username: String,
+        text: String,
+    },
+}
+
+pub fn parse(input: &str) -> Vec<Block> {
+    let lines: Vec<&str> = input.lines().collect();
+    parse_blocks(&lines, 0)
+}
+
+/// Like [`parse`], but also turns `@username` for the given users into
+/// [`Inline::Mention`]s. Code spans and code blocks are left alone.
+pub fn parse_with_mentions(input: &str, usernames: &[String]) -> Vec<Block> {
+    let blocks = parse(input);
+    if usernames.is_empty() {
+        return blocks;
+    }
+    blocks
+        .into_iter()
+        .map(|block| mark_mentions_in_block(block, usernames))
+        .collect()
+}
+
+fn mark_mentions_in_block(block: Block, usernames: &[String]) -> Block {
+    match block {
+        Block::Paragraph(inlines) => Block::Paragraph(mark_mentions(inlines, usernames)),
+        Block::List {
+            ordered,
+            start,
+            items,
+        } => Block::List {
+            ordered,
+            start,
+            items: items
+                .into_iter()
+                .map(|item| mark_mentions(item, usernames))
+                .collect(),
+        },
+        Block::Quote(blocks) => Block::Quote(
+            blocks
+                .into_iter()
+                .map(|block| mark_mentions_in_block(block, usernames))
+                .collect(),
+        ),
+        code @ Block::CodeBlock { .. } => code,
+    }
+}
+
+fn mark_mentions(inlines: Vec<Inline>, usernames: &[String]) -> Vec<Inline> {
+    let mut out = Vec::new();
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => {
+                let chars: Vec<char> = text.chars().collect();
+                let mut last = 0;
+                for mention in extract_mentions(&text, usernames) {
+                    let (start, end) = (
+                        mention.offset as usize,
+                        (mention.offset + mention.length) as usize,
+                    );
+                    if start > last {
+                        out.push(Inline::Text(chars[last..start].iter().collect()));
+                    }
+                    out.push(Inline::Mention {
+                        username: mention.username,
+                        text: chars[start..end].iter().collect(),
+                    });
+                    last = end;
+                }
+                if last < chars.len() {
+                    out.push(Inline::Text(chars[last..].iter().collect()));
+                }
+            }
+            Inline::Bold(children) => out.push(Inline::Bold(mark_mentions(children, usernames))),
+            Inline::Italic(children) => {
+                out.push(Inline::Italic(mark_mentions(children, usernames)))
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn parse_blocks(lines: &[&str], depth: usize) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    let quotes = depth < MAX_QUOTE_DEPTH;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        if line.trim().is_empty() {
+            i += 1;
+        } else if let Some(info) = fence_start(line) {
+            let mut code = Vec::new();
+            i += 1;
+            while i < lines.len() && fence_start(lines[i]).is_none() {
+                code.push(lines[i]);
+                i += 1;
+            }
+            // Skip the closing fence, if there is one
+            i += 1;
+            blocks.push(Block::CodeBlock {
+                language: sanitize_language(info),
+                code: code.join("\n"),
+            });
+        } else if quotes && quote_line(line).is_some() {
+            let mut quoted = Vec::new();
+            while i < lines.len() {
+                match quote_line(lines[i]) {
+                    Some(rest) => quoted.push(rest),
+                    None => break,
+                }
+                i += 1;
+            }
+            blocks.push(Block::Quote(parse_blocks(&quoted, depth + 1)));
+        } else if let Some((ordered, start, _)) = list_item(line) {
+            let mut items: Vec<String> = Vec::new();
+            while i < lines.len() {
+                match list_item(lines[i]) {
+                    Some((item_ordered, _, rest)) if item_ordered == ordered => {
+                        items.push(rest.to_string());
+                    }
+                    // Indented lines continue the previous item
+                    None if !lines[i].trim().is_empty()
+                        && lines[i].starts_with("  ")
+                        && !items.is_empty() =>
+                    {
+                        let last = items.last_mut().expect("checked non-empty");
+                        last.push('\n');
+                        last.push_str(lines[i].trim());
+                    }
+                    _ => break,
+                }
+                i += 1;
+            }
+            blocks.push(Block::List {
+                ordered,
+                start,
+                items: items.iter().map(|item| parse_inlines(item)).collect(),
+            });
+        } else {
+            let mut paragraph = Vec::new();
+            while i < lines.len() && !lines[i].trim().is_empty() && !starts_block(lines[i], quotes)
+            {
+                paragraph.push(lines[i]);
+                i += 1;
+            }
+            blocks.push(Block::Paragraph(parse_inlines(&paragraph.join("\n"))));
+        }
+    }
+
+    blocks
+}
+
+fn starts_block(line: &str, quotes: bool) -> bool {
+    fence_start(line).is_some()
+        || (quotes && quote_line(line).is_some())
+        || list_item(line).is_some()
+}
+
+fn fence_start(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    (line.len() - trimmed.len() <= 3)
+        .then(|| trimmed.strip_prefix("```"))
+        .flatten()
+}
+
+fn quote_line(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('>')?;
+    Some(rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+/// Returns `(ordered, start number, item text)`.
+fn list_item(line: &str) -> Option<(bool, u32, &str)> {
+    let trimmed = line.trim_start();
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(rest) = trimmed.strip_prefix(marker) {
+            return Some((false, 1, rest));
+        }
+    }
+
+    let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+    if (1..=9).contains(&digits) {
+        let rest = &trimmed[digits..];
+        if let Some(rest) = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")) {
+            return Some((true, trimmed[..digits].parse().ok()?, rest));
+        }
+    }
+    None
+}
+
+/// Info strings end up in a `class` attribute, so keep them boring.
+fn sanitize_language(info: &str) -> Option<String> {
+    let language: String = info
+        .split_whitespace()
+        .next()?
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '#'))
+        .take(20)
+        .collect::<String>()
+        .to_lowercase();
+    (!language.is_empty()).then_some(language)
+}
+
+pub fn parse_inlines(text: &str) -> Vec<Inline> {
+    parse_inlines_at(text, 0)
+}
+
+fn parse_inlines_at(text: &str, depth: usize) -> Vec<Inline> {
+    let chars: Vec<char> = text.chars().collect();
+    let delimiters = Delimiters::scan(&chars);
+    let mut inlines = Vec::new();
+    let mut buffer = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '\\' && chars.get(i + 1).is_some_and(|n| n.is_ascii_punctuation()) {
+            buffer.push(chars[i + 1]);
+            i += 2;
+            continue;
+        }
+
+        if c == '`' {
+            if let Some(end) = delimiters.code_end[i] {
+                flush(&mut buffer, &mut inlines);
+                inlines.push(Inline::Code(chars[i + 1..end].iter().collect()));
+                i = end + 1;
+                continue;
+            }
+        }
+
+        if (c == '*' || c == '_') && depth < MAX_EMPHASIS_DEPTH {
+            let double = chars.get(i + 1) == Some(&c);
+            let width = if double { 2 } else { 1 };
+            if can_open(&chars, i, width) {
+                if let Some(end) = delimiters.closing(c, width, i + width + 1) {
+                    let inner: String = chars[i + width..end].iter().collect();
+                    flush(&mut buffer, &mut inlines);
+                    let children = parse_inlines_at(&inner, depth + 1);
+                    inlines.push(if double {
+                        Inline::Bold(children)
+                    } else {
+                        Inline::Italic(children)
+                    });
+                    i = end + width;
+                    continue;
+                }
+            }
+        }
+
+        if at_word_start(&chars, i) {
+            if let Some((href, len)) = match_url(&chars[i..]) {
+                flush(&mut buffer, &mut inlines);
+                inlines.push(Inline::Link {
+                    href,
+                    text: chars[i..i + len].iter().collect(),
+                });
+                i += len;
+                continue;
+            }
+        }
+
+        buffer.push(c);
+        i += 1;
+    }
+
+    flush(&mut buffer, &mut inlines);
+    inlines
+}
+
+fn flush(buffer: &mut String, inlines: &mut Vec<Inline>) {
+    if !buffer.is_empty() {
+        inlines.push(Inline::Text(std::mem::take(buffer)));
+    }
+}
+
+/// Code spans and the positions that can close each kind of emphasis,
+/// found in one pass so matching a delimiter never rescans the text.
+struct Delimiters {
+    /// For a backtick that opens a code span, the index of its closing one.
+    code_end: Vec<Option<usize>>,
+    /// Closing positions outside code spans, ascending, for `*`, `**`, `_`
+    /// and `__`.
+    closers: [Vec<usize>; 4],
+}
+
+impl Delimiters {
+    fn scan(chars: &[char]) -> Self {
+        let mut code_end = vec![None; chars.len()];
+        let mut in_code = vec![false; chars.len()];
+        let mut next_backtick = None;
+        let mut next_backticks = vec![None; chars.len()];
+        for j in (0..chars.len()).rev() {
+            next_backticks[j] = next_backtick;
+            if chars[j] == '`' {
+                next_backtick = Some(j);
+            }
+        }
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i] == '\\' && chars.get(i + 1).is_some_and(|n| n.is_ascii_punctuation()) {
+                i += 2;
+            } else if chars[i] == '`' && next_backticks[i].is_some() {
+                let end = next_backticks[i].expect("checked above");
+                code_end[i] = Some(end);
+                in_code[i..=end].fill(true);
+                i = end + 1;
+            } else {
+                i += 1;
+            }
+        }
+
+        let closers = [('*', 1), ('*', 2), ('_', 1), ('_', 2)].map(|(delimiter, width)| {
+            (1..(chars.len() + 1).saturating_sub(width))
+                .filter(|&j| !in_code[j] && closes(chars, j, delimiter, width))
+                .collect()
+        });
+        Self { code_end, closers }
+    }
+
+    /// The first position at or after `from` that closes `delimiter` repeated
+    /// `width` times.
+    fn closing(&self, delimiter: char, width: usize, from: usize) -> Option<usize> {
+        let closers = &self.closers[usize::from(delimiter == '_') * 2 + width - 1];
+        closers.get(closers.partition_point(|&j| j < from)).copied()
+    }
+}
+
+/// Whether `width` `delimiter`s at `j` (never the first char) can close
+/// emphasis.
+fn closes(chars: &[char], j: usize, delimiter: char, width: usize) -> bool {
+    let run = chars[j..j + width].iter().all(|&c| c == delimiter);
+    // A longer run belongs to a different delimiter (e.g. `**` inside `*`)
+    let longer =
+        chars.get(j + width) == Some(&delimiter) || (chars[j - 1] == delimiter && width == 1);
+    let after_ok = delimiter == '*' || chars.get(j + width).is_none_or(|c| !c.is_alphanumeric());
+    run && !longer && !chars[j - 1].is_whitespace() && after_ok
+}
+
+fn at_word_start(chars: &[char], i: usize) -> bool {
+    i == 0 || !chars[i - 1].is_alphanumeric()
+}
+
+/// Emphasis opens before a non-space; `_` additionally never opens mid-word
+/// so `snake_case_names` stay intact.
+fn can_open(chars: &[char], i: usize, width: usize) -> bool {
+    let next = chars.get(i + width);
+    let next_ok = next.is_some_and(|c| !c.is_whitespace());
+    next_ok && (chars[i] == '*' || at_word_start(chars, i))
+}
+
+/// Matches a bare URL, returning the link target and how many chars it spans.
+fn match_url(chars: &[char]) -> Option<(String, usize)> {
+    let rest: String = chars.iter().take(8).collect::<String>().to_lowercase();
+    let prefix = ["https://", "http://", "www."]
+        .into_iter()
+        .find(|p| rest.starts_with(p))?;
+
+    let mut len = chars
+        .iter()
+        .position(|&c| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '\'' | '`'))
+        .unwrap_or(chars.len());
+
+    // Trailing punctuation is almost always part of the sentence
+    let opening_parens = chars[..len].iter().filter(|&&c| c == '(').count();
+    let mut closing_parens = chars[..len].iter().filter(|&&c| c == ')').count();
+    while len > prefix.len() {
+        let last = chars[len - 1];
+        let unbalanced_paren = last == ')' && opening_parens < closing_parens;
+        if matches!(last, '.' | ',' | ';' | ':' | '!' | '?' | '*' | '_') || unbalanced_paren {
+            len -= 1;
+            if last == ')' {
+                closing_parens -= 1;
+            }
+        } else {
+            break;
+        }
+    }
+
+    if len <= prefix.len() {
+        return None;
+    }
+
+    let text: String = chars[..len].iter().collect();
+    let href = if prefix == "www." {
+        format!("https://{}", text)
+    } else {
+        text
+    };
+    Some((href, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Inline {
+        Inline::Text(s.to_string())
+    }
+
+    fn hrefs(blocks: &[Block]) -> Vec<String> {
+        fn from_inlines(inlines: &[Inline], out: &mut Vec<String>) {
+            for inline in inlines {
+                match inline {
+                    Inline::Link { href, .. } => out.push(href.clone()),
+                    Inline::Bold(children) | Inline::Italic(children) => {
+                        from_inlines(children, out)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let mut out = Vec::new();
+        for block in blocks {
+            match block {
+                Block::Paragraph(inlines) => from_inlines(inlines, &mut out),
+                Block::List { items, .. } => items.iter().for_each(|i| from_inlines(i, &mut out)),
+                Block::Quote(blocks) => out.extend(hrefs(blocks)),
+                Block::CodeBlock { .. } => {}
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn html_is_kept_as_text() {
+        for payload in [
+            "<script>alert(1)</script>",
+            "<img src=x onerror=alert(1)>",
+            "<a href=\"javascript:alert(1)\">click</a>",
+            "&lt;b&gt;entities&lt;/b&gt;",
+            "<svg/onload=alert(1)>",
+        ] {
+            assert_eq!(parse(payload), vec![Block::Paragraph(vec![text(payload)])]);
+        }
+    }
+
+    #[test]
+    fn dangerous_schemes_are_never_linked() {
+        for payload in [
+            "javascript:alert(1)",
+            "[x](javascript:alert(1))",
+            "data:text/html;base64,PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==",
+            "vbscript:msgbox(1)",
+            "JaVaScRiPt:alert(1)",
+            "xjavascript:alert(1)//https://example.com",
+        ] {
+            let blocks = parse(payload);
+            assert!(
+                hrefs(&blocks)
+                    .iter()
+                    .all(|h| h.starts_with("http://") || h.starts_with("https://")),
+                "{payload} produced {blocks:?}"
+            );
+            assert!(hrefs(&blocks)
+                .iter()
+                .all(|h| !h.to_lowercase().contains("script:")));
+        }
+    }
+
+    #[test]
+    fn urls_stop_before_markup() {
+        let blocks = parse("https://example.com/\"><script>alert(1)</script>");
+        assert_eq!(hrefs(&blocks), vec!["https://example.com/".to_string()]);
+    }
+
+    #[test]
+    fn code_blocks_keep_content_verbatim() {
+        let blocks = parse("```\"><script>alert(1)</script>\n</code><img onerror=x>\n```");
+        assert_eq!(
+            blocks,
+            vec![Block::CodeBlock {
+                language: Some("scriptalert1script".to_string()),
+                code: "</code><img onerror=x>".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn emphasis_and_code() {
+        assert_eq!(
+            parse_inlines("**bold** and *it* `<b>`"),
+            vec![
+                Inline::Bold(vec![text("bold")]),
+                text(" and "),
+                Inline::Italic(vec![text("it")]),
+                text(" "),
+                Inline::Code("<b>".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_inlines("snake_case_name"),
+            vec![text("snake_case_name")]
+        );
+        assert_eq!(parse_inlines("2 * 3 * 4"), vec![text("2 * 3 * 4")]);
+    }
+
+    #[test]
+    fn lists_quotes_and_autolinks() {
+        let blocks = parse("> quoted\n\n1. one\n2. see www.example.com.\n- other");
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Quote(vec![Block::Paragraph(vec![text("quoted")])]),
+                Block::List {
+                    ordered: true,
+                    start: 1,
+                    items: vec![
+                        vec![text("one")],
+                        vec![
+                            text("see "),
+                            Inline::Link {
+                                href: "https://www.example.com".to_string(),
+                                text: "www.example.com".to_string(),
+                            },
+                            text("."),
+                        ],
+                    ],
+                },
+                Block::List {
+                    ordered: false,
+                    start: 1,
+                    items: vec![vec![text("other")]],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn deep_quotes_stop_nesting_at_the_limit() {
+        let input = ">".repeat(100_000);
+        let mut blocks = parse(&input);
+        for _ in 0..MAX_QUOTE_DEPTH {
+            match blocks.as_slice() {
+                [Block::Quote(inner)] => blocks = inner.clone(),
+                other => panic!("expected a quote, got {other:?}"),
+            }
+        }
+        assert_eq!(
+            blocks,
+            vec![Block::Paragraph(vec![text(
+                &">".repeat(100_000 - MAX_QUOTE_DEPTH)
+            )])]
+        );
+    }
+
+    #[test]
+    fn deep_emphasis_stops_nesting_at_the_limit() {
+        let depth = MAX_EMPHASIS_DEPTH + 4;
+        let input = format!("{}x{}", "*a ".repeat(depth), " a*".repeat(depth));
+        let mut inlines = parse_inlines(&input);
+        let mut levels = 0;
+        while let Some(Inline::Italic(children)) =
+            inlines.iter().find(|i| matches!(i, Inline::Italic(_)))
+        {
+            inlines = children.clone();
+            levels += 1;
+        }
+        assert!(levels <= MAX_EMPHASIS_DEPTH, "nested {levels} levels");
+    }
+
+    #[test]
+    fn long_delimiter_runs_parse_in_linear_time() {
+        // Each of these took seconds per 100 KB when closers were searched
+        // for by rescanning the rest of the text
+        for unit in ["*a ", "**a ", "_a ", "a* "] {
+            let input = unit.repeat(50_000);
+            assert_eq!(parse_inlines(&input), vec![text(&input)], "{unit}");
+        }
+        let input = "`*a ".repeat(50_000);
+        assert!(!parse_inlines(&input).is_empty());
+        let input = format!("https://x{}", ")".repeat(100_000));
+        assert_eq!(hrefs(&parse(&input)), vec!["https://x".to_string()]);
+    }
+
+    #[test]
+    fn marks_mentions_outside_code() {
+        let users = vec!["alice".to_string()];
+        let blocks = parse_with_mentions("hi **@Alice** `@alice`", &users);
+        assert_eq!(
+            blocks,
+            vec![Block::Paragraph(vec![
+                text("hi "),
+                Inline::Bold(vec![Inline::Mention {
+                    username: "alice".to_string(),
+                    text: "@Alice".to_string(),
+                }]),
+                text(" "),
+                Inline::Code("@alice".to_string()),
+            ])]
+        );
+    }
+}
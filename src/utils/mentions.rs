@@ -0,0 +1,112 @@
+use crate::models::mention::Mention;
+
+fn is_username_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '-')
+}
+
+/// The `@prefix` being typed right before the caret, if any.
+/// Returns the char index of the `@` and the prefix after it.
+pub fn active_query(text: &str, caret: usize) -> Option<(usize, String)> {
+    let chars: Vec<char> = text.chars().take(caret).collect();
+    let at = chars.iter().rposition(|&c| c == '@')?;
+
+    let prefix: String = chars[at + 1..].iter().collect();
+    let at_word_start = at == 0 || chars[at - 1].is_whitespace();
+    (at_word_start && prefix.chars().all(is_username_char)).then_some((at, prefix))
+}
+
+/// Replaces the active `@prefix` with the full username. Returns the new text
+/// and the caret position (in chars) right after the inserted mention.
+pub fn complete(text: &str, at: usize, caret: usize, username: &str) -> (String, usize) {
+    let chars: Vec<char> = text.chars().collect();
+    let before: String = chars[..at].iter().collect();
+    let after: String = chars[caret.min(chars.len())..].iter().collect();
+    let inserted = format!("@{} ", username);
+    let caret = at + inserted.chars().count();
+    (
+        format!("{}{}{}", before, inserted, after.trim_start()),
+        caret,
+    )
+}
+
+/// Finds `@username` spans for the given users. Matching is case-insensitive,
+/// and trailing sentence punctuation (`@bob.`) is ignored.
+pub fn extract_mentions(text: &str, usernames: &[String]) -> Vec<Mention> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut mentions = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i] == '@' && (i == 0 || !is_username_char(chars[i - 1])) {
+            let mut len = chars[i + 1..]
+                .iter()
+                .take_while(|&&c| is_username_char(c))
+                .count();
+            while len > 0 {
+                let candidate: String = chars[i + 1..i + 1 + len].iter().collect();
+                if let Some(username) = usernames
+                    .iter()
+                    .find(|u| u.eq_ignore_ascii_case(&candidate))
+                {
+                    mentions.push(Mention {
+                        username: username.clone(),
+                        offset: i as u32,
+                        length: len as u32 + 1,
+                    });
+                    break;
+                }
+                if !matches!(chars[i + len], '.' | '-') {
+                    len = 0;
+                    break;
+                }
+                len -= 1;
+            }
+            i += len + 1;
+        } else {
+            i += 1;
+        }
+    }
+
+    mentions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users() -> Vec<String> {
+        vec!["alice".to_string(), "bob.smith".to_string()]
+    }
+
+    #[test]
+    fn finds_mentions_with_char_offsets() {
+        let mentions = extract_mentions("héllo @Alice and @bob.smith. not@alice @carol", &users());
+        assert_eq!(
+            mentions,
+            vec![
+                Mention {
+                    username: "alice".to_string(),
+                    offset: 6,
+                    length: 6,
+                },
+                Mention {
+                    username: "bob.smith".to_string(),
+                    offset: 17,
+                    length: 10,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn completes_the_active_query() {
+        let text = "hey @al how are you";
+        let (at, prefix) = active_query(text, 7).unwrap();
+        assert_eq!((at, prefix.as_str()), (4, "al"));
+        assert_eq!(
+            complete(text, at, 7, "alice"),
+            ("hey @alice how are you".to_string(), 11)
+        );
+        assert_eq!(active_query("mail me@host", 12), None);
+    }
+}
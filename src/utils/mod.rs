@@ -1,3 +1,12 @@
 pub mod config;
+pub mod export;
+pub mod format;
 pub mod graphql_client;
+pub mod highlight;
+pub mod image_processing;
+pub mod import;
+pub mod markdown;
+pub mod mentions;
+pub mod search;
+pub mod virtual_list;
 pub mod websocket;
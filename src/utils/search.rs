@@ -0,0 +1,261 @@
+use crate::models::message::{Message, MessageType};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+/// Lowercases and strips common Latin diacritics, so "Café" matches "cafe".
+pub fn fold(word: &str) -> String {
+    let mut folded = String::with_capacity(word.len());
+    for c in word.chars().flat_map(char::to_lowercase) {
+        match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => folded.push('a'),
+            'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => folded.push('c'),
+            'ď' | 'đ' => folded.push('d'),
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => folded.push('e'),
+            'ĝ' | 'ğ' | 'ġ' | 'ģ' => folded.push('g'),
+            'ĥ' | 'ħ' => folded.push('h'),
+            'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => folded.push('i'),
+            'ĵ' => folded.push('j'),
+            'ķ' => folded.push('k'),
+            'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => folded.push('l'),
+            'ñ' | 'ń' | 'ņ' | 'ň' => folded.push('n'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => folded.push('o'),
+            'ŕ' | 'ŗ' | 'ř' => folded.push('r'),
+            'ś' | 'ŝ' | 'ş' | 'š' | 'ș' => folded.push('s'),
+            'ţ' | 'ť' | 'ŧ' | 'ț' => folded.push('t'),
+            'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => folded.push('u'),
+            'ŵ' => folded.push('w'),
+            'ý' | 'ÿ' | 'ŷ' => folded.push('y'),
+            'ź' | 'ż' | 'ž' => folded.push('z'),
+            'ß' => folded.push_str("ss"),
+            'æ' => folded.push_str("ae"),
+            'œ' => folded.push_str("oe"),
+            'þ' => folded.push_str("th"),
+            c => folded.push(c),
+        }
+    }
+    folded
+}
+
+/// Byte ranges of the words in `text`: runs of letters and digits.
+pub fn words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                words.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push(s..text.len());
+    }
+    words
+}
+
+/// Folded search terms of `text`, in order, without duplicates.
+pub fn terms(text: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for range in words(text) {
+        let term = fold(&text[range]);
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+    terms
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub chat_id: String,
+    pub message_id: String,
+    pub timestamp: f64,
+}
+
+/// Inverted index over message text. Every query term matches as a prefix
+/// and all of them must match.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    docs: Vec<SearchHit>,
+    by_message_id: HashMap<String, usize>,
+    /// Folded term -> ascending doc ids.
+    postings: BTreeMap<String, Vec<usize>>,
+}
+
+impl SearchIndex {
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    /// Indexes a stored message once. Pending sends are skipped because their
+    /// id changes when the server acknowledges them.
+    pub fn add(&mut self, msg: &Message) {
+        if self.by_message_id.contains_key(&msg.message_id)
+            || msg.is_pending()
+            || matches!(msg.message_type, MessageType::System | MessageType::Error)
+        {
+            return;
+        }
+        let doc = self.docs.len();
+        self.docs.push(SearchHit {
+            chat_id: msg.chat_id.clone(),
+            message_id: msg.message_id.clone(),
+            timestamp: msg.timestamp,
+        });
+        self.by_message_id.insert(msg.message_id.clone(), doc);
+        for term in terms(&msg.content) {
+            self.postings.entry(term).or_default().push(doc);
+        }
+    }
+
+    /// Newest matches first.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let mut matching: Option<Vec<usize>> = None;
+        for term in terms(query) {
+            let mut docs: Vec<usize> = self
+                .postings
+                .range(term.clone()..)
+                .take_while(|(t, _)| t.starts_with(&term))
+                .flat_map(|(_, docs)| docs.iter().copied())
+                .collect();
+            docs.sort_unstable();
+            docs.dedup();
+            matching = Some(match matching {
+                None => docs,
+                Some(previous) => previous
+                    .into_iter()
+                    .filter(|d| docs.binary_search(d).is_ok())
+                    .collect(),
+            });
+        }
+
+        let mut hits: Vec<SearchHit> = matching
+            .unwrap_or_default()
+            .into_iter()
+            .map(|doc| self.docs[doc].clone())
+            .collect();
+        hits.sort_by(|a, b| b.timestamp.total_cmp(&a.timestamp));
+        hits.truncate(limit);
+        hits
+    }
+}
+
+/// A short excerpt of `text` around the first match of `query`, as
+/// `(text, is_match)` segments. Whole words matching a query term are marked.
+pub fn snippet(text: &str, query: &str, max_chars: usize) -> Vec<(String, bool)> {
+    let query_terms = terms(query);
+    let matches: Vec<Range<usize>> = words(text)
+        .into_iter()
+        .filter(|range| {
+            let word = fold(&text[range.clone()]);
+            query_terms
+                .iter()
+                .any(|term| word.starts_with(term.as_str()))
+        })
+        .collect();
+
+    // Start a little before the first match, on a char boundary
+    let first = matches.first().map_or(0, |r| r.start);
+    let lead = max_chars / 4;
+    let start = text[..first]
+        .char_indices()
+        .rev()
+        .nth(lead.saturating_sub(1))
+        .map_or(0, |(i, _)| i);
+    let end = text[start..]
+        .char_indices()
+        .nth(max_chars)
+        .map_or(text.len(), |(i, _)| start + i);
+
+    let mut segments = Vec::new();
+    if start > 0 {
+        segments.push(("…".to_string(), false));
+    }
+    let mut pos = start;
+    for range in matches.iter().filter(|r| r.start >= start && r.end <= end) {
+        if range.start > pos {
+            segments.push((text[pos..range.start].to_string(), false));
+        }
+        segments.push((text[range.clone()].to_string(), true));
+        pos = range.end;
+    }
+    if end > pos {
+        segments.push((text[pos..end].to_string(), false));
+    }
+    if end < text.len() {
+        segments.push(("…".to_string(), false));
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::MessageStatus;
+
+    fn message(id: &str, chat_id: &str, content: &str, timestamp: f64) -> Message {
+        Message {
+            message_id: id.to_string(),
+            client_message_id: None,
+            content: content.to_string(),
+            sender: "alice".to_string(),
+            timestamp,
+            sequence: None,
+            status: MessageStatus::Sent,
+            message_type: MessageType::Text,
+            chat_id: chat_id.to_string(),
+            attachment: None,
+            mentions: Vec::new(),
+            imported_by: None,
+            upload_progress: None,
+        }
+    }
+
+    #[test]
+    fn matches_prefixes_of_every_term_ignoring_accents() {
+        let mut index = SearchIndex::default();
+        index.add(&message("1", "CHAT#a#b", "Meet at the Café tomorrow", 1.0));
+        index.add(&message("2", "CHAT#a#c", "cafeteria is closed", 2.0));
+        index.add(&message("3", "CHAT#a#b", "See you tomorrow!", 3.0));
+        index.add(&message("3", "CHAT#a#b", "duplicate id is ignored", 4.0));
+
+        let ids = |query: &str| -> Vec<String> {
+            index
+                .search(query, 10)
+                .into_iter()
+                .map(|h| h.message_id)
+                .collect()
+        };
+        assert_eq!(ids("cafe"), ["2", "1"]);
+        assert_eq!(ids("CAFÉ tom"), ["1"]);
+        assert_eq!(ids("tomorrow"), ["3", "1"]);
+        assert!(ids("duplicate").is_empty());
+        assert!(ids("").is_empty());
+    }
+
+    #[test]
+    fn snippet_marks_matching_words() {
+        let segments = snippet("Let's grab crêpes later", "crepe", 80);
+        assert_eq!(
+            segments,
+            [
+                ("Let's grab ".to_string(), false),
+                ("crêpes".to_string(), true),
+                (" later".to_string(), false),
+            ]
+        );
+
+        let long = format!("{} needle {}", "a ".repeat(50), "b ".repeat(50));
+        let segments = snippet(&long, "needle", 40);
+        assert_eq!(segments.first().unwrap().0, "…");
+        assert!(segments.iter().any(|(text, hit)| *hit && text == "needle"));
+        assert_eq!(segments.last().unwrap().0, "…");
+    }
+}
@@ -0,0 +1,148 @@
+use std::ops::Range;
+
+/// Height assumed for rows that haven't been rendered and measured yet.
+pub const ESTIMATED_ROW_HEIGHT: f64 = 72.0;
+
+/// Vertical positions of a list of variable-height rows. Heights live in a
+/// Fenwick tree, so measuring a row or appending one doesn't touch the rest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowLayout {
+    heights: Vec<f64>,
+    /// 1-based; `tree[i]` sums the heights of rows `i - lowbit(i)..i`.
+    tree: Vec<f64>,
+}
+
+fn lowbit(i: usize) -> usize {
+    i & i.wrapping_neg()
+}
+
+impl Default for RowLayout {
+    fn default() -> Self {
+        Self::new([])
+    }
+}
+
+impl RowLayout {
+    pub fn new(heights: impl IntoIterator<Item = f64>) -> Self {
+        let heights: Vec<f64> = heights.into_iter().collect();
+        let mut tree = vec![0.0; heights.len() + 1];
+        for i in 1..tree.len() {
+            tree[i] += heights[i - 1];
+            let parent = i + lowbit(i);
+            if parent < tree.len() {
+                tree[parent] += tree[i];
+            }
+        }
+        Self { heights, tree }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn total_height(&self) -> f64 {
+        self.offset(self.len())
+    }
+
+    /// Top of row `index`; `index == len()` gives the total height.
+    pub fn offset(&self, index: usize) -> f64 {
+        let mut i = index.min(self.len());
+        let mut top = 0.0;
+        while i > 0 {
+            top += self.tree[i];
+            i -= lowbit(i);
+        }
+        top
+    }
+
+    /// Changes one row's height. Returns whether it actually changed.
+    pub fn set_height(&mut self, index: usize, height: f64) -> bool {
+        let delta = height - self.heights[index];
+        if delta == 0.0 {
+            return false;
+        }
+        self.heights[index] = height;
+        let mut i = index + 1;
+        while i < self.tree.len() {
+            self.tree[i] += delta;
+            i += lowbit(i);
+        }
+        true
+    }
+
+    /// Adds a row at the bottom.
+    pub fn push(&mut self, height: f64) {
+        let i = self.tree.len();
+        // The new node covers rows i - lowbit(i)..i
+        let node = height + self.offset(i - 1) - self.offset(i - lowbit(i));
+        self.heights.push(height);
+        self.tree.push(node);
+    }
+
+    /// The row covering `y`, clamped to the last row.
+    pub fn index_at(&self, y: f64) -> usize {
+        // Walk down the tree for the number of rows whose bottom is <= y
+        let mut after = 0;
+        let mut remaining = y;
+        let mut step = self.tree.len().next_power_of_two();
+        while step > 0 {
+            let next = after + step;
+            if next < self.tree.len() && self.tree[next] <= remaining {
+                after = next;
+                remaining -= self.tree[next];
+            }
+            step /= 2;
+        }
+        after.min(self.len().saturating_sub(1))
+    }
+
+    /// Rows intersecting the viewport, plus `overscan` pixels either side.
+    pub fn window(&self, scroll_top: f64, viewport_height: f64, overscan: f64) -> Range<usize> {
+        if self.is_empty() {
+            return 0..0;
+        }
+        let start = self.index_at((scroll_top - overscan).max(0.0));
+        let end = self.index_at(scroll_top + viewport_height + overscan) + 1;
+        start..end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_covers_viewport_and_overscan() {
+        let layout = RowLayout::new([10.0, 20.0, 30.0, 40.0, 50.0]);
+        assert_eq!(layout.total_height(), 150.0);
+        assert_eq!(layout.index_at(0.0), 0);
+        assert_eq!(layout.index_at(29.9), 1);
+        assert_eq!(layout.index_at(30.0), 2);
+        assert_eq!(layout.index_at(1000.0), 4);
+        assert_eq!(layout.window(30.0, 30.0, 0.0), 2..4);
+        assert_eq!(layout.window(30.0, 30.0, 15.0), 1..4);
+        assert_eq!(RowLayout::new([]).window(0.0, 100.0, 50.0), 0..0);
+    }
+
+    #[test]
+    fn updates_and_appends_match_a_fresh_layout() {
+        let mut layout = RowLayout::new([10.0, 20.0, 30.0]);
+        for height in [40.0, 50.0, 60.0, 70.0] {
+            layout.push(height);
+        }
+        assert!(layout.set_height(1, 25.0));
+        assert!(!layout.set_height(1, 25.0));
+
+        let fresh = RowLayout::new([10.0, 25.0, 30.0, 40.0, 50.0, 60.0, 70.0]);
+        for index in 0..=fresh.len() {
+            assert_eq!(layout.offset(index), fresh.offset(index));
+        }
+        assert_eq!(layout.index_at(35.0), 2);
+        assert_eq!(layout.index_at(104.9), 3);
+        assert_eq!(layout.index_at(105.0), 4);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::utils::config::CONFIG;
 use base64::Engine;
+use futures::lock::Mutex;
 use futures::stream::SplitSink;
 use futures::{SinkExt, StreamExt};
 use gloo_net::websocket::{futures::WebSocket, Message};
@@ -18,8 +19,8 @@ pub struct SubscriptionResponse {
 }
 
 pub struct AppSyncWebSocket {
-    subscription_id: String,
-    writer: Rc<RefCell<SplitSink<WebSocket, Message>>>,
+    subscription_ids: Vec<String>,
+    writer: Rc<Mutex<SplitSink<WebSocket, Message>>>,
 }
 
 impl AppSyncWebSocket {
@@ -28,6 +29,23 @@ impl AppSyncWebSocket {
         subscription_query: &str,
         variables: Option<serde_json::Value>,
         on_message: impl Fn(serde_json::Value) + 'static,
+    ) -> Self {
+        Self::with_subscriptions(
+            token,
+            vec![(
+                subscription_query.to_string(),
+                variables.unwrap_or(serde_json::json!({})),
+            )],
+            on_message,
+        )
+    }
+
+    /// Starts several subscriptions over a single connection. Every data
+    /// payload goes to `on_message`, whichever subscription it belongs to.
+    pub fn with_subscriptions(
+        token: &str,
+        subscriptions: Vec<(String, serde_json::Value)>,
+        on_message: impl Fn(serde_json::Value) + 'static,
     ) -> Self {
         let endpoint = &CONFIG.websocket_endpoint;
 
@@ -62,42 +80,48 @@ impl AppSyncWebSocket {
         let ws = WebSocket::open_with_protocol(&ws_url, "graphql-ws")
             .expect("Failed to create WebSocket");
 
-        let subscription_id = uuid::Uuid::new_v4().to_string();
-
         let connection_init = serde_json::json!({
             "type": "connection_init"
         });
 
         let init_msg = Message::Text(serde_json::to_string(&connection_init).unwrap());
 
-        let subscription_query_json = serde_json::json!({
-            "query": subscription_query,
-            "variables": variables.unwrap_or(serde_json::json!({}))
-        });
-
-        let start_subscription = serde_json::json!({
-            "id": subscription_id,
-            "type": "start",
-            "payload": {
-                "data": serde_json::to_string(&subscription_query_json).unwrap(),
-                "extensions": {
-                    "authorization": {
-                        "Authorization": auth_token,
-                        "host": api_endpoint
+        let mut subscription_ids = Vec::new();
+        let mut sub_msgs = Vec::new();
+        for (query, variables) in subscriptions {
+            let subscription_id = uuid::Uuid::new_v4().to_string();
+            let subscription_query_json = serde_json::json!({
+                "query": query,
+                "variables": variables
+            });
+
+            let start_subscription = serde_json::json!({
+                "id": subscription_id,
+                "type": "start",
+                "payload": {
+                    "data": serde_json::to_string(&subscription_query_json).unwrap(),
+                    "extensions": {
+                        "authorization": {
+                            "Authorization": auth_token,
+                            "host": api_endpoint
+                        }
                     }
                 }
-            }
-        });
+            });
 
-        let sub_msg = Message::Text(serde_json::to_string(&start_subscription).unwrap());
+            sub_msgs.push(Message::Text(
+                serde_json::to_string(&start_subscription).unwrap(),
+            ));
+            subscription_ids.push(subscription_id);
+        }
 
         let (write, mut read) = ws.split();
-        let write = Rc::new(RefCell::new(write));
+        let write = Rc::new(Mutex::new(write));
         let write_clone = write.clone();
-        let sub_msg = Rc::new(sub_msg);
+        let sub_msgs = Rc::new(sub_msgs);
 
         let write_future = async move {
-            write_clone.borrow_mut().send(init_msg).await.unwrap();
+            write_clone.lock().await.send(init_msg).await.unwrap();
         };
         spawn_local(write_future);
 
@@ -119,10 +143,13 @@ impl AppSyncWebSocket {
                                         &"Connection acknowledged, starting subscription".into(),
                                     );
                                     let write = write.clone();
-                                    let sub_msg = sub_msg.clone();
+                                    let sub_msgs = sub_msgs.clone();
                                     spawn_local(async move {
                                         gloo_timers::future::TimeoutFuture::new(100).await;
-                                        write.borrow_mut().send((*sub_msg).clone()).await.unwrap();
+                                        let mut write = write.lock().await;
+                                        for sub_msg in sub_msgs.iter() {
+                                            write.send(sub_msg.clone()).await.unwrap();
+                                        }
                                     });
                                     if let Some(payload) = response.payload {
                                         if let Some(timeout_ms) = payload.get("connectionTimeoutMs")
@@ -182,24 +209,31 @@ impl AppSyncWebSocket {
         spawn_local(read_future);
 
         Self {
-            subscription_id,
+            subscription_ids,
             writer: write_for_return,
         }
     }
 
     pub fn close(&self) {
-        let subscription_id = self.subscription_id.clone();
         let writer = self.writer.clone();
 
-        let stop_subscription = serde_json::json!({
-            "id": subscription_id,
-            "type": "stop"
-        });
-        let stop_msg = Message::Text(serde_json::to_string(&stop_subscription).unwrap());
+        let stop_msgs: Vec<Message> = self
+            .subscription_ids
+            .iter()
+            .map(|subscription_id| {
+                let stop_subscription = serde_json::json!({
+                    "id": subscription_id,
+                    "type": "stop"
+                });
+                Message::Text(serde_json::to_string(&stop_subscription).unwrap())
+            })
+            .collect();
 
         spawn_local(async move {
-            if let Ok(mut writer) = writer.try_borrow_mut() {
-                let _ = writer.send(stop_msg).await;
+            if let Some(mut writer) = writer.try_lock() {
+                for stop_msg in stop_msgs {
+                    let _ = writer.send(stop_msg).await;
+                }
             }
         });
     }
@@ -24,6 +24,16 @@ resource "aws_dynamodb_table" "chat" {
     type = "S"
   }
 
+  attribute {
+    name = "GSI2PK"
+    type = "S"
+  }
+
+  attribute {
+    name = "GSI2SK"
+    type = "S"
+  }
+
   global_secondary_index {
     name            = "GSI1"
     hash_key        = "GSI1PK"
@@ -31,6 +41,19 @@ resource "aws_dynamodb_table" "chat" {
     projection_type = "ALL"
   }
 
+  # User directory: GSI2PK is USERDIR#<first letter>, GSI2SK the lowercased
+  # username, so searchUsers can query one partition with begins_with
+  global_secondary_index {
+    name            = "GSI2"
+    hash_key        = "GSI2PK"
+    range_key       = "GSI2SK"
+    projection_type = "ALL"
+  }
+
+  # Feeds the search indexer
+  stream_enabled   = true
+  stream_view_type = "NEW_IMAGE"
+
   tags = {
     Environment = "dev"
     Name        = var.dynamodb_table_name
@@ -41,8 +64,7 @@ resource "aws_dynamodb_table" "chat" {
     ignore_changes = [
       name,
       hash_key,
-      range_key,
-      attribute
+      range_key
     ]
   }
 }
@@ -52,6 +74,11 @@ output "table_name" {
   value       = aws_dynamodb_table.chat.name
 }
 
+output "stream_arn" {
+  description = "ARN of the DynamoDB chat table's stream"
+  value       = aws_dynamodb_table.chat.stream_arn
+}
+
 output "table_arn" {
   description = "ARN of the DynamoDB chat table"
   value       = aws_dynamodb_table.chat.arn
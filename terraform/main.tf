@@ -68,10 +68,20 @@ locals {
   resolver_names = [
     "Query.getUser",
     "Query.getConversation",
-    "Query.listUsers",
-    "Mutation.createMessage",
+    "Query.searchUsers",
+    "Query.getReadReceipts",
+    "Query.listConversations",
+    "Query.getNotificationSettings",
+    "Conversation.otherUser",
     "Mutation.createUser",
-    "Mutation.updateUserStatus"
+    "Mutation.updateUserStatus",
+    "Mutation.updateLastSeen",
+    "Mutation.markConversationRead",
+    "Mutation.importMessages",
+    "Mutation.registerPushSubscription",
+    "Mutation.unregisterPushSubscription",
+    "Mutation.setChatNotificationSetting",
+    "Mutation.setDoNotDisturb"
   ]
 }
 
@@ -87,6 +97,98 @@ resource "aws_appsync_resolver" "resolvers" {
   field       = each.value.field
   data_source = "ChatTable"
 
+  # Transactions have to name their table, so templates may use a placeholder
+  request_template = replace(
+    file("${path.module}/appsync/resolvers/${each.key}.req.vtl"),
+    "__TABLE_NAME__",
+    module.dynamodb.table_name
+  )
+  response_template = file("${path.module}/appsync/resolvers/${each.key}.res.vtl")
+
+  depends_on = [module.appsync]
+}
+
+# Pipeline resolvers: the field's own templates run before/after the listed
+# functions, which live in appsync/functions
+locals {
+  pipeline_resolvers = {
+    "Mutation.createMessage" = ["findClientMessage", "nextChatSequence", "putMessage", "storedClientMessage"]
+  }
+  pipeline_functions = toset(flatten(values(local.pipeline_resolvers)))
+}
+
+resource "aws_appsync_function" "functions" {
+  for_each = local.pipeline_functions
+
+  api_id      = module.appsync.appsync_graphql_api_id
+  name        = each.key
+  data_source = "ChatTable"
+
+  request_mapping_template = replace(
+    file("${path.module}/appsync/functions/${each.key}.req.vtl"),
+    "__TABLE_NAME__",
+    module.dynamodb.table_name
+  )
+  response_mapping_template = file("${path.module}/appsync/functions/${each.key}.res.vtl")
+
+  depends_on = [module.appsync]
+}
+
+resource "aws_appsync_resolver" "pipeline_resolvers" {
+  for_each = local.pipeline_resolvers
+
+  api_id = module.appsync.appsync_graphql_api_id
+  type   = split(".", each.key)[0]
+  field  = split(".", each.key)[1]
+  kind   = "PIPELINE"
+
+  pipeline_config {
+    functions = [for name in each.value : aws_appsync_function.functions[name].function_id]
+  }
+
+  request_template  = file("${path.module}/appsync/resolvers/${each.key}.req.vtl")
+  response_template = file("${path.module}/appsync/resolvers/${each.key}.res.vtl")
+}
+
+# Direct Lambda resolvers (no mapping templates)
+locals {
+  lambda_resolvers = {
+    "Mutation.createUploadUrl" = "AttachmentsLambda"
+    "Attachment.url"           = "AttachmentsLambda"
+    "Query.searchMessages"     = "SearchLambda"
+  }
+}
+
+resource "aws_appsync_resolver" "lambda_resolvers" {
+  for_each = local.lambda_resolvers
+
+  api_id      = module.appsync.appsync_graphql_api_id
+  type        = split(".", each.key)[0]
+  field       = split(".", each.key)[1]
+  data_source = each.value
+
+  depends_on = [module.appsync]
+}
+
+# Resolvers on the NONE data source: ephemeral events that only exist as
+# subscription traffic, and membership checks for chat subscriptions
+locals {
+  local_resolvers = [
+    "Mutation.sendTyping",
+    "Subscription.onNewMessage",
+    "Subscription.onReadReceipt",
+    "Subscription.onTyping"
+  ]
+}
+
+resource "aws_appsync_resolver" "local_resolvers" {
+  for_each = toset(local.local_resolvers)
+
+  api_id      = module.appsync.appsync_graphql_api_id
+  type        = split(".", each.key)[0]
+  field       = split(".", each.key)[1]
+  data_source = "Realtime"
+
   request_template  = file("${path.module}/appsync/resolvers/${each.key}.req.vtl")
   response_template = file("${path.module}/appsync/resolvers/${each.key}.res.vtl")
 
@@ -110,7 +212,7 @@ module "appsync" {
 
   schema = file("${path.module}/appsync/schema.graphql")
 
-  # Single data source
+  # DynamoDB table, Lambda-backed fields and local (NONE) resolvers
   datasources = {
     "ChatTable" = {
       type = "AMAZON_DYNAMODB"
@@ -123,6 +225,21 @@ module "appsync" {
       table_name          = module.dynamodb.table_name
       create_service_role = false
     }
+    "AttachmentsLambda" = {
+      type                = "AWS_LAMBDA"
+      function_arn        = aws_lambda_function.attachments.arn
+      service_role_arn    = aws_iam_role.appsync_lambda_role.arn
+      create_service_role = false
+    }
+    "SearchLambda" = {
+      type                = "AWS_LAMBDA"
+      function_arn        = aws_lambda_function.search.arn
+      service_role_arn    = aws_iam_role.appsync_lambda_role.arn
+      create_service_role = false
+    }
+    "Realtime" = {
+      type = "NONE"
+    }
   }
 
   # Empty resolvers map - we're managing them separately
@@ -168,6 +285,7 @@ resource "aws_iam_role_policy" "dynamodb_policy" {
           "dynamodb:PutItem",
           "dynamodb:DeleteItem",
           "dynamodb:UpdateItem",
+          "dynamodb:BatchWriteItem",
           "dynamodb:Query",
           "dynamodb:Scan"
         ]
@@ -180,6 +298,348 @@ resource "aws_iam_role_policy" "dynamodb_policy" {
   })
 }
 
+# S3 bucket for message attachments, written through presigned URLs
+resource "aws_s3_bucket" "attachments" {
+  bucket = "${var.project_name}-attachments"
+}
+
+resource "aws_s3_bucket_public_access_block" "attachments" {
+  bucket = aws_s3_bucket.attachments.id
+
+  block_public_acls       = true
+  block_public_policy     = true
+  ignore_public_acls      = true
+  restrict_public_buckets = true
+}
+
+resource "aws_s3_bucket_cors_configuration" "attachments" {
+  bucket = aws_s3_bucket.attachments.id
+
+  cors_rule {
+    allowed_methods = ["PUT", "GET"]
+    allowed_origins = ["https://${aws_cloudfront_distribution.frontend.domain_name}", "http://localhost:8080"]
+    allowed_headers = ["content-type"]
+    max_age_seconds = 3600
+  }
+}
+
+resource "aws_iam_role" "attachments_lambda_role" {
+  name = "attachments-lambda-role"
+
+  assume_role_policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [
+      {
+        Action = "sts:AssumeRole"
+        Effect = "Allow"
+        Principal = {
+          Service = "lambda.amazonaws.com"
+        }
+      }
+    ]
+  })
+}
+
+resource "aws_iam_role_policy_attachment" "attachments_lambda_logs" {
+  role       = aws_iam_role.attachments_lambda_role.name
+  policy_arn = "arn:aws:iam::aws:policy/service-role/AWSLambdaBasicExecutionRole"
+}
+
+resource "aws_iam_role_policy" "attachments_lambda_s3" {
+  name = "attachments-lambda-s3-policy"
+  role = aws_iam_role.attachments_lambda_role.id
+
+  policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [
+      {
+        Effect   = "Allow"
+        Action   = ["s3:PutObject", "s3:GetObject"]
+        Resource = "${aws_s3_bucket.attachments.arn}/uploads/*"
+      }
+    ]
+  })
+}
+
+# Built by scripts/build_lambdas.sh
+resource "aws_lambda_function" "attachments" {
+  function_name    = "${var.project_name}-attachments"
+  role             = aws_iam_role.attachments_lambda_role.arn
+  runtime          = "provided.al2023"
+  architectures    = ["arm64"]
+  handler          = "bootstrap"
+  filename         = "${path.module}/../target/lambda/attachments/bootstrap.zip"
+  source_code_hash = filebase64sha256("${path.module}/../target/lambda/attachments/bootstrap.zip")
+  timeout          = 5
+
+  environment {
+    variables = {
+      ATTACHMENTS_BUCKET = aws_s3_bucket.attachments.id
+    }
+  }
+}
+
+resource "aws_iam_role" "appsync_lambda_role" {
+  name = "appsync-lambda-role"
+
+  assume_role_policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [
+      {
+        Action = "sts:AssumeRole"
+        Effect = "Allow"
+        Principal = {
+          Service = "appsync.amazonaws.com"
+        }
+      }
+    ]
+  })
+}
+
+resource "aws_iam_role_policy" "appsync_lambda_policy" {
+  name = "appsync-lambda-policy"
+  role = aws_iam_role.appsync_lambda_role.id
+
+  policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [
+      {
+        Effect   = "Allow"
+        Action   = "lambda:InvokeFunction"
+        Resource = [aws_lambda_function.attachments.arn, aws_lambda_function.search.arn]
+      }
+    ]
+  })
+}
+
+# Message search: a tantivy index on EFS, written by a stream consumer and
+# read by the searchMessages resolver. Both run the search Lambda binary.
+resource "aws_efs_file_system" "search" {
+  creation_token = "${var.project_name}-search"
+  encrypted      = true
+}
+
+resource "aws_efs_mount_target" "search" {
+  for_each = toset(var.search_subnet_ids)
+
+  file_system_id  = aws_efs_file_system.search.id
+  subnet_id       = each.value
+  security_groups = var.search_security_group_ids
+}
+
+resource "aws_efs_access_point" "search" {
+  file_system_id = aws_efs_file_system.search.id
+
+  posix_user {
+    uid = 1000
+    gid = 1000
+  }
+
+  root_directory {
+    path = "/search"
+    creation_info {
+      owner_uid   = 1000
+      owner_gid   = 1000
+      permissions = "750"
+    }
+  }
+}
+
+resource "aws_iam_role" "search_lambda_role" {
+  name = "search-lambda-role"
+
+  assume_role_policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [
+      {
+        Action = "sts:AssumeRole"
+        Effect = "Allow"
+        Principal = {
+          Service = "lambda.amazonaws.com"
+        }
+      }
+    ]
+  })
+}
+
+resource "aws_iam_role_policy_attachment" "search_lambda" {
+  for_each = toset([
+    "arn:aws:iam::aws:policy/service-role/AWSLambdaVPCAccessExecutionRole",
+    "arn:aws:iam::aws:policy/service-role/AWSLambdaDynamoDBExecutionRole",
+    "arn:aws:iam::aws:policy/AmazonElasticFileSystemClientReadWriteAccess"
+  ])
+
+  role       = aws_iam_role.search_lambda_role.name
+  policy_arn = each.value
+}
+
+locals {
+  search_lambda_zip = "${path.module}/../target/lambda/search/bootstrap.zip"
+}
+
+# Built by scripts/build_lambdas.sh
+resource "aws_lambda_function" "search" {
+  function_name    = "${var.project_name}-search"
+  role             = aws_iam_role.search_lambda_role.arn
+  runtime          = "provided.al2023"
+  architectures    = ["arm64"]
+  handler          = "bootstrap"
+  filename         = local.search_lambda_zip
+  source_code_hash = filebase64sha256(local.search_lambda_zip)
+  timeout          = 10
+  memory_size      = 512
+
+  environment {
+    variables = {
+      SEARCH_INDEX_DIR = "/mnt/search/index"
+    }
+  }
+
+  file_system_config {
+    arn              = aws_efs_access_point.search.arn
+    local_mount_path = "/mnt/search"
+  }
+
+  vpc_config {
+    subnet_ids         = var.search_subnet_ids
+    security_group_ids = var.search_security_group_ids
+  }
+
+  depends_on = [aws_efs_mount_target.search, aws_iam_role_policy_attachment.search_lambda]
+}
+
+resource "aws_lambda_function" "search_indexer" {
+  function_name    = "${var.project_name}-search-indexer"
+  role             = aws_iam_role.search_lambda_role.arn
+  runtime          = "provided.al2023"
+  architectures    = ["arm64"]
+  handler          = "bootstrap"
+  filename         = local.search_lambda_zip
+  source_code_hash = filebase64sha256(local.search_lambda_zip)
+  timeout          = 60
+  memory_size      = 512
+  # tantivy allows a single writer per index
+  reserved_concurrent_executions = 1
+
+  environment {
+    variables = {
+      SEARCH_INDEX_DIR = "/mnt/search/index"
+    }
+  }
+
+  file_system_config {
+    arn              = aws_efs_access_point.search.arn
+    local_mount_path = "/mnt/search"
+  }
+
+  vpc_config {
+    subnet_ids         = var.search_subnet_ids
+    security_group_ids = var.search_security_group_ids
+  }
+
+  depends_on = [aws_efs_mount_target.search, aws_iam_role_policy_attachment.search_lambda]
+}
+
+resource "aws_lambda_event_source_mapping" "search_indexer" {
+  event_source_arn  = module.dynamodb.stream_arn
+  function_name     = aws_lambda_function.search_indexer.arn
+  starting_position = "TRIM_HORIZON"
+  batch_size        = 100
+
+  # Only message items; summaries, receipts and counters aren't searchable
+  filter_criteria {
+    filter {
+      pattern = jsonencode({
+        dynamodb = {
+          Keys = {
+            SK = { S = [{ prefix = "MSG#" }] }
+          }
+        }
+      })
+    }
+  }
+}
+
+resource "aws_iam_role" "push_lambda_role" {
+  name = "push-lambda-role"
+
+  assume_role_policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [
+      {
+        Action = "sts:AssumeRole"
+        Effect = "Allow"
+        Principal = {
+          Service = "lambda.amazonaws.com"
+        }
+      }
+    ]
+  })
+}
+
+resource "aws_iam_role_policy_attachment" "push_lambda" {
+  role       = aws_iam_role.push_lambda_role.name
+  policy_arn = "arn:aws:iam::aws:policy/service-role/AWSLambdaDynamoDBExecutionRole"
+}
+
+# Reads recipients' presence and push subscriptions, and forgets expired ones
+resource "aws_iam_role_policy" "push_lambda_dynamodb" {
+  name = "push-lambda-dynamodb-policy"
+  role = aws_iam_role.push_lambda_role.id
+
+  policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [
+      {
+        Effect   = "Allow"
+        Action   = ["dynamodb:GetItem", "dynamodb:Query", "dynamodb:DeleteItem"]
+        Resource = module.dynamodb.table_arn
+      }
+    ]
+  })
+}
+
+# Built by scripts/build_lambdas.sh
+resource "aws_lambda_function" "push" {
+  function_name    = "${var.project_name}-push"
+  role             = aws_iam_role.push_lambda_role.arn
+  runtime          = "provided.al2023"
+  architectures    = ["arm64"]
+  handler          = "bootstrap"
+  filename         = "${path.module}/../target/lambda/push/bootstrap.zip"
+  source_code_hash = filebase64sha256("${path.module}/../target/lambda/push/bootstrap.zip")
+  timeout          = 30
+
+  environment {
+    variables = {
+      TABLE_NAME        = module.dynamodb.table_name
+      VAPID_PRIVATE_KEY = var.vapid_private_key
+      VAPID_SUBJECT     = var.vapid_subject
+    }
+  }
+}
+
+resource "aws_lambda_event_source_mapping" "push" {
+  event_source_arn  = module.dynamodb.stream_arn
+  function_name     = aws_lambda_function.push.arn
+  starting_position = "LATEST"
+  batch_size        = 25
+
+  # Newly created messages only
+  filter_criteria {
+    filter {
+      pattern = jsonencode({
+        eventName = ["INSERT"]
+        dynamodb = {
+          Keys = {
+            SK = { S = [{ prefix = "MSG#" }] }
+          }
+        }
+      })
+    }
+  }
+}
+
 # S3 bucket for frontend hosting
 resource "aws_s3_bucket" "frontend" {
   bucket = "${var.project_name}-frontend"
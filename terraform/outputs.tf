@@ -42,3 +42,8 @@ output "cloudfront_domain_name" {
   description = "Domain name of the CloudFront distribution"
   value       = aws_cloudfront_distribution.frontend.domain_name
 }
+
+output "attachments_bucket_name" {
+  description = "Name of the S3 bucket holding message attachments"
+  value       = aws_s3_bucket.attachments.id
+}
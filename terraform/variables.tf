@@ -27,3 +27,24 @@ variable "project_name" {
   type        = string
   default     = "rusty-chat"
 }
+
+variable "search_subnet_ids" {
+  description = "Subnets for the search Lambdas and their EFS mount targets"
+  type        = list(string)
+}
+
+variable "search_security_group_ids" {
+  description = "Security groups for the search Lambdas; must allow NFS to the EFS mount targets"
+  type        = list(string)
+}
+
+variable "vapid_private_key" {
+  description = "Base64url P-256 private key signing Web Push requests; its public key goes in the client's config"
+  type        = string
+  sensitive   = true
+}
+
+variable "vapid_subject" {
+  description = "Contact for push services, as a mailto: or https: URL"
+  type        = string
+}